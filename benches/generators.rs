@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mazemazemaze::direction::Bias;
+use mazemazemaze::generator::Generator;
+
+const GENERATORS: [Generator; 3] = [
+    Generator::HuntAndKill,
+    Generator::BinaryTree,
+    Generator::Sidewinder,
+];
+
+fn generate2d(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate2d");
+    group.sample_size(10);
+
+    for size in [10, 100, 1000] {
+        for generator in GENERATORS {
+            group.bench_with_input(
+                BenchmarkId::new(generator.name(), size),
+                &size,
+                |b, &size| b.iter(|| generator.maze2d(size, size, 0, Bias::UNIFORM)),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn generate3d(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate3d");
+    group.sample_size(10);
+
+    for size in [5, 20, 100] {
+        for generator in GENERATORS {
+            group.bench_with_input(
+                BenchmarkId::new(generator.name(), size),
+                &size,
+                |b, &size| b.iter(|| generator.maze3d(size, size, size, 0, Bias::UNIFORM)),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+// the mesh of a huge maze takes far longer than generating it
+fn geometry2d(c: &mut Criterion) {
+    let mut group = c.benchmark_group("geometry2d");
+    group.sample_size(10);
+
+    for size in [100, 1000] {
+        let maze = Generator::Sidewinder.maze2d(size, size, 0, Bias::UNIFORM);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &maze, |b, maze| {
+            b.iter(|| maze.geometry())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, generate2d, generate3d, geometry2d);
+criterion_main!(benches);
@@ -0,0 +1,406 @@
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+use std::str::FromStr;
+
+use crate::maze2d::Maze2D;
+use crate::maze3d::Maze3D;
+
+// Numbers describing how hard a maze is to solve. Lengths are counted in cells of `cells`, so
+// walking from one logical cell to the next is 2 long.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MazeStats {
+    // open cells with only one way out
+    pub dead_ends: usize,
+
+    // open cells with three or more ways out
+    pub junctions: usize,
+
+    // mean length of the passages between dead ends and junctions
+    pub average_corridor_length: f32,
+
+    // shortest path from start to goal, the river factor and tortuosity are also zero when the
+    // goal can't be reached
+    pub solution_length: usize,
+
+    // wrong turns offered along the solution per cell of it
+    pub river_factor: f32,
+
+    // solution length divided by the manhattan distance from start to goal
+    pub tortuosity: f32,
+}
+
+impl MazeStats {
+    pub fn of_maze2d(maze: &Maze2D) -> MazeStats {
+        let open: Vec<_> = (0..maze.cells.len())
+            .flat_map(|y| (0..maze.cells[y].len()).map(move |x| (x, y)))
+            .filter(|&(x, y)| !maze.cells[y][x])
+            .collect();
+
+        let distance = maze.start.0.abs_diff(maze.goal.0) + maze.start.1.abs_diff(maze.goal.1);
+
+        MazeStats::measure(
+            &open,
+            |cell| maze.neighbours(cell).count(),
+            &maze.solve().unwrap_or_default(),
+            distance,
+        )
+    }
+
+    pub fn of_maze3d(maze: &Maze3D) -> MazeStats {
+        let open: Vec<_> = (0..maze.cells.len())
+            .flat_map(|z| {
+                (0..maze.cells[z].len())
+                    .flat_map(move |y| (0..maze.cells[z][y].len()).map(move |x| (x, y, z)))
+            })
+            .filter(|&(x, y, z)| !maze.cells[z][y][x])
+            .collect();
+
+        let distance = maze.start.0.abs_diff(maze.goal.0)
+            + maze.start.1.abs_diff(maze.goal.1)
+            + maze.start.2.abs_diff(maze.goal.2);
+
+        MazeStats::measure(
+            &open,
+            |cell| maze.neighbours(cell).count(),
+            &maze.solve().unwrap_or_default(),
+            distance,
+        )
+    }
+
+    fn measure<C: Copy>(
+        open: &[C],
+        ways_out: impl Fn(C) -> usize,
+        solution: &[C],
+        distance: usize,
+    ) -> MazeStats {
+        let degrees: Vec<usize> = open.iter().map(|&cell| ways_out(cell)).collect();
+
+        // every corridor ends in two cells that aren't in the middle of a corridor
+        let steps = degrees.iter().sum::<usize>() / 2;
+        let corridors = degrees.iter().filter(|&&d| d != 2).sum::<usize>() / 2;
+
+        let solution_length = solution.len().saturating_sub(1);
+        let wrong_turns: usize = solution
+            .iter()
+            .map(|&cell| ways_out(cell).saturating_sub(2))
+            .sum();
+
+        let (river_factor, tortuosity) = if solution_length == 0 {
+            (0.0, 0.0)
+        } else {
+            (
+                wrong_turns as f32 / solution_length as f32,
+                solution_length as f32 / distance.max(1) as f32,
+            )
+        };
+
+        MazeStats {
+            dead_ends: degrees.iter().filter(|&&d| d == 1).count(),
+            junctions: degrees.iter().filter(|&&d| d >= 3).count(),
+            average_corridor_length: steps as f32 / corridors.max(1) as f32,
+            solution_length,
+            river_factor,
+            tortuosity,
+        }
+    }
+
+    // solution length weighted by how many wrong turns can be taken along it
+    pub fn score(&self) -> f32 {
+        self.solution_length as f32 * (1.0 + self.river_factor)
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::from_score(self.score())
+    }
+}
+
+// Statistics a generated maze has to have, see `Generator::maze2d_in_band`
+#[derive(Clone, PartialEq, Debug)]
+pub struct DifficultyBand {
+    pub solution_length: RangeInclusive<usize>,
+    pub dead_ends: RangeInclusive<usize>,
+}
+
+impl Default for DifficultyBand {
+    fn default() -> Self {
+        DifficultyBand {
+            solution_length: 0..=usize::MAX,
+            dead_ends: 0..=usize::MAX,
+        }
+    }
+}
+
+impl DifficultyBand {
+    pub fn contains(&self, stats: &MazeStats) -> bool {
+        self.solution_length.contains(&stats.solution_length)
+            && self.dead_ends.contains(&stats.dead_ends)
+    }
+
+    // how far outside the band `stats` is, relative to the lower end of each range
+    pub fn distance(&self, stats: &MazeStats) -> f32 {
+        let outside = |range: &RangeInclusive<usize>, value: usize| {
+            let distance = range.start().saturating_sub(value) + value.saturating_sub(*range.end());
+            distance as f32 / (*range.start()).max(1) as f32
+        };
+
+        outside(&self.solution_length, stats.solution_length)
+            + outside(&self.dead_ends, stats.dead_ends)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    #[default]
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+    ];
+
+    // `MazeStats::score`s rated this difficulty
+    pub fn scores(self) -> Range<f32> {
+        match self {
+            Difficulty::Easy => 0.0..40.0,
+            Difficulty::Medium => 40.0..90.0,
+            Difficulty::Hard => 90.0..180.0,
+            Difficulty::Expert => 180.0..f32::INFINITY,
+        }
+    }
+
+    pub fn from_score(score: f32) -> Difficulty {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.scores().contains(&score))
+            .unwrap_or_default()
+    }
+
+    // how far `score` is from being rated this difficulty
+    pub fn distance(self, score: f32) -> f32 {
+        let scores = self.scores();
+
+        (scores.start - score).max(score - scores.end).max(0.0)
+    }
+
+    // the next difficulty up, or this one if it's already the hardest
+    pub fn harder(self) -> Difficulty {
+        Difficulty::ALL
+            .into_iter()
+            .find(|&difficulty| difficulty > self)
+            .unwrap_or(self)
+    }
+
+    // the next difficulty down, or this one if it's already the easiest
+    pub fn easier(self) -> Difficulty {
+        Difficulty::ALL
+            .into_iter()
+            .rev()
+            .find(|&difficulty| difficulty < self)
+            .unwrap_or(self)
+    }
+
+    // logical sizes whose hunt and kill mazes usually get rated this difficulty
+    pub fn maze2d_size(self) -> (usize, usize) {
+        match self {
+            Difficulty::Easy => (5, 5),
+            Difficulty::Medium => (10, 10),
+            Difficulty::Hard => (15, 15),
+            Difficulty::Expert => (24, 24),
+        }
+    }
+
+    pub fn maze3d_size(self) -> (usize, usize, usize) {
+        match self {
+            Difficulty::Easy => (3, 3, 3),
+            Difficulty::Medium => (4, 4, 4),
+            Difficulty::Hard => (6, 6, 6),
+            Difficulty::Expert => (8, 8, 8),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+            Difficulty::Expert => write!(f, "expert"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.to_string() == s)
+            .ok_or_else(|| format!("unknown difficulty {s}, expected easy, medium, hard or expert"))
+    }
+}
+
+// one `name: value` line per statistic
+impl fmt::Display for MazeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "dead ends: {}", self.dead_ends)?;
+        writeln!(f, "junctions: {}", self.junctions)?;
+        writeln!(
+            f,
+            "average corridor length: {:.2}",
+            self.average_corridor_length
+        )?;
+        writeln!(f, "solution length: {}", self.solution_length)?;
+        writeln!(f, "river factor: {:.3}", self.river_factor)?;
+        writeln!(f, "tortuosity: {:.2}", self.tortuosity)?;
+        writeln!(f, "score: {:.0}", self.score())?;
+        writeln!(f, "difficulty: {}", self.difficulty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a maze with nothing carved yet, from its top left logical cell to its bottom right one
+    fn walled(width: usize, height: usize) -> Maze2D {
+        Maze2D {
+            cells: vec![vec![true; width * 2 + 1]; height * 2 + 1],
+            width,
+            height,
+            start: (1, 1),
+            goal: (width * 2 - 1, height * 2 - 1),
+            ..Default::default()
+        }
+    }
+
+    // opens up a passage between two neighbouring logical cells
+    fn carve(maze: &mut Maze2D, (ax, ay): (usize, usize), (bx, by): (usize, usize)) {
+        maze.cells[ay * 2 + 1][ax * 2 + 1] = false;
+        maze.cells[ay + by + 1][ax + bx + 1] = false;
+        maze.cells[by * 2 + 1][bx * 2 + 1] = false;
+    }
+
+    fn corridor(length: usize) -> Maze2D {
+        let mut maze = walled(length, 1);
+        for x in 1..length {
+            carve(&mut maze, (x - 1, 0), (x, 0));
+        }
+
+        maze
+    }
+
+    // a corridor along the top with a dead end hanging off every cell along it
+    fn comb(length: usize, teeth: usize) -> Maze2D {
+        let mut maze = walled(length, teeth + 1);
+        maze.goal = (length * 2 - 1, 1);
+
+        for x in 0..length {
+            if x > 0 {
+                carve(&mut maze, (x - 1, 0), (x, 0));
+            }
+
+            for y in 0..teeth {
+                carve(&mut maze, (x, y), (x, y + 1));
+            }
+        }
+
+        maze
+    }
+
+    // back and forth across every row
+    fn serpentine(size: usize) -> Maze2D {
+        let mut maze = walled(size, size);
+
+        for y in 0..size {
+            for x in 1..size {
+                carve(&mut maze, (x - 1, y), (x, y));
+            }
+
+            if y > 0 {
+                let x = if y % 2 == 1 { size - 1 } else { 0 };
+                carve(&mut maze, (x, y - 1), (x, y));
+            }
+        }
+
+        maze.goal = if size % 2 == 1 {
+            (size * 2 - 1, size * 2 - 1)
+        } else {
+            (1, size * 2 - 1)
+        };
+
+        maze
+    }
+
+    #[test]
+    fn straight_corridors_are_easy() {
+        let stats = MazeStats::of_maze2d(&corridor(10));
+
+        assert_eq!(stats.solution_length, 18);
+        assert_eq!(stats.dead_ends, 2);
+        assert_eq!(stats.junctions, 0);
+        assert_eq!(stats.river_factor, 0.0);
+        assert_eq!(stats.tortuosity, 1.0);
+        assert_eq!(stats.difficulty(), Difficulty::Easy);
+    }
+
+    #[test]
+    fn dead_ends_along_the_solution_make_it_harder() {
+        let corridor = MazeStats::of_maze2d(&corridor(16));
+        let comb = MazeStats::of_maze2d(&comb(16, 2));
+
+        assert_eq!(comb.solution_length, corridor.solution_length);
+        assert_eq!(comb.dead_ends, 16);
+        assert_eq!(comb.junctions, 14);
+        assert!(comb.river_factor > 0.0);
+        assert!(comb.score() > corridor.score());
+
+        assert_eq!(corridor.difficulty(), Difficulty::Easy);
+        assert_eq!(comb.difficulty(), Difficulty::Medium);
+    }
+
+    #[test]
+    fn long_winding_solutions_are_expert() {
+        let stats = MazeStats::of_maze2d(&serpentine(10));
+
+        assert_eq!(stats.solution_length, 198);
+        assert_eq!(stats.dead_ends, 2);
+        assert!(stats.tortuosity > 10.0);
+        assert_eq!(stats.difficulty(), Difficulty::Expert);
+    }
+
+    #[test]
+    fn unsolvable_mazes_have_no_solution_stats() {
+        let mut maze = corridor(5);
+        maze.cells[1][4] = true;
+
+        let stats = MazeStats::of_maze2d(&maze);
+
+        assert_eq!(stats.solution_length, 0);
+        assert_eq!(stats.score(), 0.0);
+        assert_eq!(stats.difficulty(), Difficulty::Easy);
+    }
+
+    #[test]
+    fn scores_are_rated_by_band() {
+        assert_eq!(Difficulty::from_score(39.9), Difficulty::Easy);
+        assert_eq!(Difficulty::from_score(40.0), Difficulty::Medium);
+        assert_eq!(Difficulty::from_score(179.9), Difficulty::Hard);
+        assert_eq!(Difficulty::from_score(1000.0), Difficulty::Expert);
+
+        assert_eq!(Difficulty::Medium.distance(50.0), 0.0);
+        assert_eq!(Difficulty::Medium.distance(30.0), 10.0);
+        assert_eq!(Difficulty::Easy.harder(), Difficulty::Medium);
+        assert_eq!(Difficulty::Expert.harder(), Difficulty::Expert);
+        assert_eq!(Difficulty::Hard.easier(), Difficulty::Medium);
+        assert_eq!(Difficulty::Easy.easier(), Difficulty::Easy);
+    }
+}
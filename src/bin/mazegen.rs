@@ -0,0 +1,312 @@
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::process;
+use std::time::Duration;
+
+use fastrand::Rng;
+use mazemazemaze::analysis::{Difficulty, DifficultyBand, MazeStats};
+use mazemazemaze::direction::Bias;
+use mazemazemaze::dungeon::Dungeon;
+use mazemazemaze::export::{self, IMAGE_CELL_SIZE};
+use mazemazemaze::generator::Generator;
+use mazemazemaze::maze2d::Maze2D;
+use mazemazemaze::maze3d::Maze3D;
+use mazemazemaze::maze_file::MazeFile;
+use mazemazemaze::maze_image::MazeImage;
+
+const USAGE: &str = "\
+usage: mazegen [options]
+
+Generates a maze without opening a window.
+
+options:
+    --generator <name>     hunt_and_kill (default), binary_tree, sidewinder or dungeon,
+                           which is 2D only
+    --size <WxH|WxHxD>     logical size, 2D unless a depth is given (default 10x10)
+    --difficulty <rating>  easy, medium, hard or expert, overrides the size but not
+                           whether it's 2D or 3D
+    --solution-length <a-b>
+    --dead-ends <a-b>      recarve parts of the maze until these are within range, the
+                           result depends on the budget so it can't be made again from
+                           its seed, keep it with --format maze or json
+    --budget <ms>          time allowed for recarving (default 1000)
+    --seed <n>             random if not given
+    --bias <x,y[,z]>       how often to carve along each axis, y is vertical in 3D
+                           (default 1,1,1)
+    --braid <0.0-1.0>      fraction of dead ends to remove (default 0.0)
+    --prune-dead-ends      fill in dungeon corridors that don't lead anywhere
+    --format <format>      ascii (default), stats, json, maze, svg, png, obj or glb
+    --output <path>        write to a file instead of stdout
+";
+
+struct Options {
+    generator: Generator,
+    size: Vec<usize>,
+    difficulty: Option<Difficulty>,
+    band: Option<DifficultyBand>,
+    budget: Duration,
+    seed: u64,
+    bias: Bias,
+    braid: f32,
+    prune_dead_ends: bool,
+    format: String,
+    output: Option<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            generator: Generator::HuntAndKill,
+            size: vec![10, 10],
+            difficulty: None,
+            band: None,
+            budget: Duration::from_millis(1000),
+            seed: fastrand::u64(..),
+            bias: Bias::UNIFORM,
+            braid: 0.0,
+            prune_dead_ends: false,
+            format: "ascii".to_string(),
+            output: None,
+        };
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(USAGE.to_string());
+            }
+
+            if arg == "--prune-dead-ends" {
+                options.prune_dead_ends = true;
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {arg}"))?;
+
+            match arg.as_str() {
+                "--generator" => options.generator = value.parse()?,
+                "--size" => {
+                    options.size = value
+                        .split('x')
+                        .map(|n| n.parse().map_err(|_| format!("invalid size {value}")))
+                        .collect::<Result<_, _>>()?;
+
+                    if !(2..=3).contains(&options.size.len()) || options.size.contains(&0) {
+                        return Err(format!("invalid size {value}"));
+                    }
+                }
+                "--difficulty" => options.difficulty = Some(value.parse()?),
+                "--solution-length" => {
+                    options
+                        .band
+                        .get_or_insert_with(Default::default)
+                        .solution_length = parse_range(&value)?
+                }
+                "--dead-ends" => {
+                    options.band.get_or_insert_with(Default::default).dead_ends =
+                        parse_range(&value)?
+                }
+                "--budget" => {
+                    options.budget = Duration::from_millis(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid budget {value}"))?,
+                    )
+                }
+                "--seed" => {
+                    options.seed = value.parse().map_err(|_| format!("invalid seed {value}"))?
+                }
+                "--bias" => options.bias = value.parse()?,
+                "--braid" => {
+                    options.braid = value
+                        .parse()
+                        .map_err(|_| format!("invalid braid factor {value}"))?
+                }
+                "--format" => options.format = value,
+                "--output" => options.output = Some(value),
+                _ => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            }
+        }
+
+        if options.band.is_some() && options.braid > 0.0 {
+            return Err("--braid can't be combined with --solution-length or --dead-ends".into());
+        }
+
+        if options.generator == Generator::Dungeon && options.size.len() == 3 {
+            return Err("the dungeon generator only makes 2D mazes".into());
+        }
+
+        if options.prune_dead_ends && options.generator != Generator::Dungeon {
+            return Err("--prune-dead-ends only works with the dungeon generator".into());
+        }
+
+        if options.prune_dead_ends && (options.band.is_some() || options.difficulty.is_some()) {
+            return Err(
+                "--prune-dead-ends can't be combined with --difficulty, --solution-length or \
+                 --dead-ends"
+                    .into(),
+            );
+        }
+
+        Ok(options)
+    }
+
+    // along with the seed that reproduces it, recarved mazes don't have one
+    fn maze2d(&self, width: usize, height: usize) -> Result<(Maze2D, Option<u64>), String> {
+        if let Some(band) = &self.band {
+            let maze = self
+                .generator
+                .maze2d_in_band(width, height, band, self.seed, self.bias, self.budget)
+                .map_err(|e| e.to_string())?;
+
+            return Ok((maze, None));
+        }
+
+        let mut maze = match self.difficulty {
+            Some(difficulty) => self
+                .generator
+                .rated_maze2d(difficulty, self.seed, self.bias),
+            None if self.prune_dead_ends => Dungeon {
+                bias: self.bias,
+                prune_dead_ends: true,
+                ..Default::default()
+            }
+            .generate(width, height, self.seed),
+            None => self.generator.maze2d(width, height, self.seed, self.bias),
+        };
+        maze.braid(self.braid, &Rng::with_seed(maze.seed));
+
+        let seed = maze.seed;
+        Ok((maze, Some(seed)))
+    }
+
+    fn maze3d(
+        &self,
+        width: usize,
+        height: usize,
+        depth: usize,
+    ) -> Result<(Maze3D, Option<u64>), String> {
+        if let Some(band) = &self.band {
+            let maze = self
+                .generator
+                .maze3d_in_band(
+                    width,
+                    height,
+                    depth,
+                    band,
+                    self.seed,
+                    self.bias,
+                    self.budget,
+                )
+                .map_err(|e| e.to_string())?;
+
+            return Ok((maze, None));
+        }
+
+        let mut maze = match self.difficulty {
+            Some(difficulty) => self
+                .generator
+                .rated_maze3d(difficulty, self.seed, self.bias),
+            None => self
+                .generator
+                .maze3d(width, height, depth, self.seed, self.bias),
+        };
+        maze.braid(self.braid, &Rng::with_seed(maze.seed));
+
+        let seed = maze.seed;
+        Ok((maze, Some(seed)))
+    }
+}
+
+// `a-b`, both included
+fn parse_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    value
+        .split_once('-')
+        .and_then(|(start, end)| Some(start.parse().ok()?..=end.parse().ok()?))
+        .ok_or_else(|| format!("invalid range {value}, expected something like 10-20"))
+}
+
+fn render2d(maze: &Maze2D, format: &str) -> Result<Vec<u8>, String> {
+    let solution = maze.solve().unwrap_or_default();
+
+    Ok(match format {
+        "ascii" => maze.to_string().into_bytes(),
+        "stats" => MazeStats::of_maze2d(maze).to_string().into_bytes(),
+        "json" => MazeFile::from_maze2d(maze, &[])
+            .to_json()
+            .map_err(|e| e.to_string())?
+            .into_bytes(),
+        "maze" => MazeFile::from_maze2d(maze, &[])
+            .to_binary()
+            .map_err(|e| e.to_string())?,
+        "svg" => MazeImage::new(maze)
+            .with_solution(&solution)
+            .to_svg(IMAGE_CELL_SIZE)
+            .into_bytes(),
+        "png" => MazeImage::new(maze)
+            .with_solution(&solution)
+            .to_png(IMAGE_CELL_SIZE),
+        "obj" => export::to_obj(&export::maze2d_geometry(maze)).into_bytes(),
+        "glb" => export::to_glb(&export::maze2d_geometry(maze)),
+        _ => return Err(format!("unknown format {format}")),
+    })
+}
+
+fn render3d(maze: &Maze3D, format: &str) -> Result<Vec<u8>, String> {
+    Ok(match format {
+        "ascii" => maze.to_string().into_bytes(),
+        "stats" => MazeStats::of_maze3d(maze).to_string().into_bytes(),
+        "json" => MazeFile::from_maze3d(maze, &[])
+            .to_json()
+            .map_err(|e| e.to_string())?
+            .into_bytes(),
+        "maze" => MazeFile::from_maze3d(maze, &[])
+            .to_binary()
+            .map_err(|e| e.to_string())?,
+        "svg" | "png" => return Err(format!("{format} is only supported for 2D mazes")),
+        "obj" => export::to_obj(&export::maze3d_geometry(maze)).into_bytes(),
+        "glb" => export::to_glb(&export::maze3d_geometry(maze)),
+        _ => return Err(format!("unknown format {format}")),
+    })
+}
+
+fn run() -> Result<(), String> {
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    let (bytes, seed) = match options.size[..] {
+        [width, height] => {
+            let (maze, seed) = options.maze2d(width, height)?;
+            (render2d(&maze, &options.format)?, seed)
+        }
+        [width, height, depth] => {
+            let (maze, seed) = options.maze3d(width, height, depth)?;
+            (render3d(&maze, &options.format)?, seed)
+        }
+        _ => unreachable!("sizes are checked while parsing"),
+    };
+
+    match options.output {
+        Some(path) => std::fs::write(&path, bytes).map_err(|e| format!("{path}: {e}"))?,
+        None => io::stdout().write_all(&bytes).map_err(|e| e.to_string())?,
+    }
+
+    // both are needed to get the same maze again
+    match seed {
+        Some(seed) if options.bias == Bias::UNIFORM => eprintln!("seed {seed}"),
+        Some(seed) => eprintln!("seed {seed} bias {}", options.bias),
+        None => eprintln!(
+            "recarved from seed {}, which won't give the same maze again, keep it with \
+             --format maze or json",
+            options.seed
+        ),
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{e}");
+        process::exit(2);
+    }
+}
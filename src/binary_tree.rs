@@ -0,0 +1,101 @@
+use fastrand::Rng;
+
+use crate::direction::{Bias, Direction2D, Direction3D};
+use crate::maze2d::Maze2D;
+use crate::maze3d::Maze3D;
+
+// Every logical cell opens a passage up or left, whichever `bias` picks, so there's one pass over
+// the maze and nothing to remember. The top row and the left column always end up as long
+// straight corridors.
+pub fn maze2d(width: usize, height: usize, seed: u64, bias: Bias) -> Maze2D {
+    let rng = Rng::with_seed(seed);
+
+    let mut maze = Maze2D {
+        cells: vec![vec![true; width * 2 + 1]; height * 2 + 1],
+        width,
+        height,
+        seed,
+        generator: "binary_tree".to_string(),
+        bias,
+        start: (1, 1),
+        goal: (width * 2 - 1, height * 2 - 1),
+        portals: vec![],
+    };
+
+    let mut directions = Vec::with_capacity(2);
+
+    for y in (1..height * 2).step_by(2) {
+        for x in (1..width * 2).step_by(2) {
+            maze.cells[y][x] = false;
+
+            directions.clear();
+            if y > 1 {
+                directions.push(Direction2D::Up);
+            }
+            if x > 1 {
+                directions.push(Direction2D::Left);
+            }
+
+            if directions.is_empty() {
+                continue;
+            }
+
+            match Direction2D::biased_choice(&rng, &bias, &directions) {
+                Direction2D::Left => maze.cells[y][x - 1] = false,
+                _ => maze.cells[y - 1][x] = false,
+            }
+        }
+    }
+
+    maze
+}
+
+// see `maze2d`, passages go up, left or in
+pub fn maze3d(width: usize, height: usize, depth: usize, seed: u64, bias: Bias) -> Maze3D {
+    let rng = Rng::with_seed(seed);
+
+    let mut maze = Maze3D {
+        cells: vec![vec![vec![true; width * 2 + 1]; height * 2 + 1]; depth * 2 + 1],
+        width,
+        height,
+        depth,
+        seed,
+        generator: "binary_tree".to_string(),
+        bias,
+        start: (1, 1, 1),
+        goal: (width * 2 - 1, height * 2 - 1, depth * 2 - 1),
+    };
+
+    let mut directions = Vec::with_capacity(3);
+
+    for z in (1..depth * 2).step_by(2) {
+        for y in (1..height * 2).step_by(2) {
+            for x in (1..width * 2).step_by(2) {
+                maze.cells[z][y][x] = false;
+
+                directions.clear();
+                if y > 1 {
+                    directions.push(Direction3D::Up);
+                }
+                if x > 1 {
+                    directions.push(Direction3D::Left);
+                }
+                if z > 1 {
+                    directions.push(Direction3D::In);
+                }
+
+                if directions.is_empty() {
+                    continue;
+                }
+
+                match Direction3D::biased_choice(&rng, &bias, &directions) {
+                    Direction3D::Left => maze.cells[z][y][x - 1] = false,
+                    Direction3D::In => maze.cells[z - 1][y][x] = false,
+                    _ => maze.cells[z][y - 1][x] = false,
+                }
+            }
+        }
+    }
+
+    maze
+}
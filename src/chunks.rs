@@ -0,0 +1,263 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use mazemazemaze::geometry::MazeGeometry;
+use mazemazemaze::maze2d::{self, Maze2D};
+use mazemazemaze::maze3d::{self, Maze3D};
+
+// cells along each side of a chunk, so 8 logical cells
+const CHUNK_SIZE: usize = 16;
+
+// A maze that's split into chunks, each with its own mesh and collider, so changing a few cells
+// only rebuilds the chunks around them instead of the whole maze.
+pub trait ChunkedMaze: Component {
+    // size of a cell in world space
+    const SCALE: f32;
+
+    // length of `cells` along each axis, x first
+    fn size(&self) -> Vec<usize>;
+
+    // world space walls of the cells from `from` up to but not including `to`
+    fn chunk_geometry(&self, from: &[usize], to: &[usize]) -> MazeGeometry;
+
+    // world space centres of the walls from `from` up to but not including `to`
+    fn walls(&self, from: &[usize], to: &[usize]) -> Vec<Vec3>;
+}
+
+impl ChunkedMaze for Maze2D {
+    const SCALE: f32 = maze2d::MAZE_SCALE;
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.cells[0].len(), self.cells.len()]
+    }
+
+    fn chunk_geometry(&self, from: &[usize], to: &[usize]) -> MazeGeometry {
+        Maze2D::chunk_geometry(self, (from[0], from[1]), (to[0], to[1]))
+            .transformed(maze2d::MAZE_SCALE, maze2d::MAZE_OFFSET)
+    }
+
+    // placed like the walls of `chunk_geometry`
+    fn walls(&self, from: &[usize], to: &[usize]) -> Vec<Vec3> {
+        let offset = Vec3::from(maze2d::MAZE_OFFSET);
+        let mut walls = vec![];
+
+        for y in from[1]..to[1] {
+            for x in from[0]..to[0] {
+                if self.cells[y][x] {
+                    walls.push(
+                        offset + Vec3::new(x as f32 + 1.5, 1.5, y as f32 + 1.5) * Self::SCALE,
+                    );
+                }
+            }
+        }
+
+        walls
+    }
+}
+
+impl ChunkedMaze for Maze3D {
+    const SCALE: f32 = maze3d::MAZE_SCALE;
+
+    fn size(&self) -> Vec<usize> {
+        vec![
+            self.cells[0][0].len(),
+            self.cells[0].len(),
+            self.cells.len(),
+        ]
+    }
+
+    fn chunk_geometry(&self, from: &[usize], to: &[usize]) -> MazeGeometry {
+        Maze3D::chunk_geometry(self, (from[0], from[1], from[2]), (to[0], to[1], to[2]))
+            .transformed(maze3d::MAZE_SCALE, maze3d::MAZE_OFFSET)
+    }
+
+    fn walls(&self, from: &[usize], to: &[usize]) -> Vec<Vec3> {
+        let offset = Vec3::from(maze3d::MAZE_OFFSET);
+        let mut walls = vec![];
+
+        for z in from[2]..to[2] {
+            for y in from[1]..to[1] {
+                for x in from[0]..to[0] {
+                    if self.cells[z][y][x] {
+                        walls.push(
+                            offset
+                                + Vec3::new(x as f32 + 1.5, y as f32 + 1.5, z as f32 + 1.5)
+                                    * Self::SCALE,
+                        );
+                    }
+                }
+            }
+        }
+
+        walls
+    }
+}
+
+// Goes on every chunked maze, with the chunks as its children. Changes to a maze's cells that
+// don't say where they are in `dirty` rebuild every chunk.
+#[derive(Component, Default)]
+pub struct MazeChunks {
+    // ranges of `cells` that changed since the chunks were last built, x first
+    pub dirty: Vec<Vec<RangeInclusive<usize>>>,
+
+    // the size of `cells` the chunks were spawned for, mazes that change size get new ones
+    size: Vec<usize>,
+    chunks: Vec<Entity>,
+}
+
+// the cells from `from` up to but not including `to`
+#[derive(Component)]
+pub struct MazeChunk {
+    from: Vec<usize>,
+    to: Vec<usize>,
+}
+
+impl MazeChunk {
+    // Whether changing cells in `region` changes this chunk. Cells just outside of it hide the
+    // faces of its walls that they touch, so they count as well.
+    fn touches(&self, region: &[RangeInclusive<usize>]) -> bool {
+        region
+            .iter()
+            .zip(self.from.iter().zip(&self.to))
+            .all(|(range, (&from, &to))| *range.start() <= to && range.end() + 1 >= from)
+    }
+}
+
+// every chunk of a maze whose `cells` are `size` long, x first
+fn chunks_of(size: &[usize]) -> Vec<MazeChunk> {
+    size.iter().fold(
+        vec![MazeChunk {
+            from: vec![],
+            to: vec![],
+        }],
+        |chunks, &length| {
+            chunks
+                .iter()
+                .flat_map(|chunk| {
+                    (0..length).step_by(CHUNK_SIZE).map(move |start| MazeChunk {
+                        from: [&chunk.from[..], &[start]].concat(),
+                        to: [&chunk.to[..], &[(start + CHUNK_SIZE).min(length)]].concat(),
+                    })
+                })
+                .collect()
+        },
+    )
+}
+
+// one cube per wall, or nothing if there aren't any as rapier can't have empty compound shapes
+fn chunk_collider<M: ChunkedMaze>(maze: &M, chunk: &MazeChunk) -> Option<Collider> {
+    let half = M::SCALE / 2.0;
+
+    let cubes: Vec<_> = maze
+        .walls(&chunk.from, &chunk.to)
+        .into_iter()
+        .map(|centre| (centre, Rot::default(), Collider::cuboid(half, half, half)))
+        .collect();
+
+    (!cubes.is_empty()).then(|| Collider::compound(cubes))
+}
+
+// The meshes and colliders are built already offset and scaled, so this can run again whenever
+// the maze changes without touching the entity's `Transform`.
+pub fn update_chunks<M: ChunkedMaze>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut maze_query: Query<(Entity, &M, &mut MazeChunks, &Handle<StandardMaterial>), Changed<M>>,
+    chunk_query: Query<(Entity, &MazeChunk, &Handle<Mesh>)>,
+) {
+    for (entity, maze, mut chunks, material) in maze_query.iter_mut() {
+        let size = maze.size();
+
+        if chunks.size != size {
+            for &chunk in chunks.chunks.iter() {
+                commands.entity(chunk).despawn_recursive();
+            }
+
+            let mut spawned = vec![];
+
+            commands.entity(entity).with_children(|parent| {
+                for chunk in chunks_of(&size) {
+                    let mesh = meshes.add(maze.chunk_geometry(&chunk.from, &chunk.to).to_mesh());
+                    let collider = chunk_collider(maze, &chunk);
+
+                    let mut chunk_commands = parent.spawn(PbrBundle {
+                        mesh,
+                        material: material.clone(),
+                        ..default()
+                    });
+
+                    if let Some(collider) = collider {
+                        chunk_commands.insert(collider);
+                    }
+
+                    spawned.push(chunk_commands.insert(chunk).id());
+                }
+            });
+
+            chunks.size = size;
+            chunks.chunks = spawned;
+            chunks.dirty.clear();
+
+            continue;
+        }
+
+        for (chunk_entity, chunk, mesh) in chunk_query.iter_many(&chunks.chunks) {
+            let dirty =
+                chunks.dirty.is_empty() || chunks.dirty.iter().any(|region| chunk.touches(region));
+
+            if !dirty {
+                continue;
+            }
+
+            meshes.set_untracked(mesh, maze.chunk_geometry(&chunk.from, &chunk.to).to_mesh());
+
+            match chunk_collider(maze, chunk) {
+                Some(collider) => commands.entity(chunk_entity).insert(collider),
+                None => commands.entity(chunk_entity).remove::<Collider>(),
+            };
+        }
+
+        chunks.dirty.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_cover_every_cell_once() {
+        let size = [37, 5, 16];
+        let mut covered = vec![0; size.iter().product()];
+
+        for chunk in chunks_of(&size) {
+            for z in chunk.from[2]..chunk.to[2] {
+                for y in chunk.from[1]..chunk.to[1] {
+                    for x in chunk.from[0]..chunk.to[0] {
+                        covered[(z * size[1] + y) * size[0] + x] += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(covered.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn changes_touch_the_chunks_around_them() {
+        let touched = |region: [RangeInclusive<usize>; 2]| {
+            chunks_of(&[48, 48])
+                .into_iter()
+                .filter(|chunk| chunk.touches(&region))
+                .map(|chunk| (chunk.from[0], chunk.from[1]))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(touched([20..=24, 20..=24]), [(16, 16)]);
+
+        // next to a chunk's edge, where the faces of the walls on the other side can change
+        assert_eq!(touched([15..=15, 20..=20]), [(0, 16), (16, 16)]);
+        assert_eq!(touched([32..=32, 20..=20]), [(16, 16), (32, 16)]);
+    }
+}
@@ -0,0 +1,698 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+use serde::{Deserialize, Serialize};
+
+use crate::state::AppState;
+use crate::touch::TouchControls;
+
+const CONTROLS_FILE: &str = "controls.json";
+
+// something the player can do, what triggers it is up to `Controls`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    StrafeLeft,
+    StrafeRight,
+
+    // 2D mazes, and 3D ones with gravity
+    Jump,
+    Sprint,
+    Crouch,
+    FlipGravity,
+
+    // 3D mazes only
+    Ascend,
+    Descend,
+    RollLeft,
+    RollRight,
+    ToggleFlight,
+
+    PlaceMarker,
+
+    // back to the menu, the next maze is a little easier
+    GiveUp,
+
+    // main menu only, whether 3D mazes are walked or flown through
+    ToggleGravity,
+}
+
+impl Action {
+    pub const ALL: [Action; 16] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::Jump,
+        Action::Sprint,
+        Action::Crouch,
+        Action::FlipGravity,
+        Action::Ascend,
+        Action::Descend,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::ToggleFlight,
+        Action::PlaceMarker,
+        Action::GiveUp,
+        Action::ToggleGravity,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move forward",
+            Action::MoveBack => "move back",
+            Action::StrafeLeft => "strafe left",
+            Action::StrafeRight => "strafe right",
+            Action::Jump => "jump",
+            Action::Sprint => "sprint",
+            Action::Crouch => "crouch",
+            Action::FlipGravity => "flip gravity",
+            Action::Ascend => "ascend",
+            Action::Descend => "descend",
+            Action::RollLeft => "roll left",
+            Action::RollRight => "roll right",
+            Action::ToggleFlight => "flight model",
+            Action::PlaceMarker => "place marker",
+            Action::GiveUp => "give up",
+            Action::ToggleGravity => "gravity mode",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    fn pressed(self, keys: &Input<KeyCode>, buttons: &Input<MouseButton>) -> bool {
+        match self {
+            Binding::Key(key) => keys.pressed(key),
+            Binding::Mouse(button) => buttons.pressed(button),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "Mouse {button}"),
+            Binding::Mouse(button) => write!(f, "Mouse {button:?}"),
+        }
+    }
+}
+
+// What each action is bound to, on the keyboard and mouse and on gamepads. Gameplay systems read
+// `Input<Action>` and `Sticks`, which are kept up to date from these and the touch controls,
+// instead of looking at the devices directly.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    bindings: BTreeMap<Action, Binding>,
+    gamepad_bindings: BTreeMap<Action, GamepadButtonType>,
+
+    // how far a stick has to be pushed before it does anything, from 0.0 to `MAX_DEADZONE`
+    pub gamepad_deadzone: f32,
+
+    // radians per second with the look stick or touch joystick pushed all the way, the mouse uses
+    // `Settings::sensitivity` instead
+    pub gamepad_look_speed: f32,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            bindings: BTreeMap::from([
+                (Action::MoveForward, Binding::Key(KeyCode::W)),
+                (Action::MoveBack, Binding::Key(KeyCode::S)),
+                (Action::StrafeLeft, Binding::Key(KeyCode::A)),
+                (Action::StrafeRight, Binding::Key(KeyCode::D)),
+                (Action::Jump, Binding::Key(KeyCode::Space)),
+                (Action::Sprint, Binding::Key(KeyCode::LShift)),
+                (Action::Crouch, Binding::Key(KeyCode::LControl)),
+                (Action::FlipGravity, Binding::Key(KeyCode::Q)),
+                (Action::Ascend, Binding::Key(KeyCode::Space)),
+                (Action::Descend, Binding::Key(KeyCode::LShift)),
+                (Action::RollLeft, Binding::Mouse(MouseButton::Left)),
+                (Action::RollRight, Binding::Mouse(MouseButton::Right)),
+                (Action::ToggleFlight, Binding::Key(KeyCode::F)),
+                (Action::PlaceMarker, Binding::Key(KeyCode::E)),
+                (Action::GiveUp, Binding::Key(KeyCode::Back)),
+                (Action::ToggleGravity, Binding::Key(KeyCode::V)),
+            ]),
+            // the left stick moves as well as the d-pad
+            gamepad_bindings: BTreeMap::from([
+                (Action::MoveForward, GamepadButtonType::DPadUp),
+                (Action::MoveBack, GamepadButtonType::DPadDown),
+                (Action::StrafeLeft, GamepadButtonType::DPadLeft),
+                (Action::StrafeRight, GamepadButtonType::DPadRight),
+                (Action::Jump, GamepadButtonType::South),
+                (Action::Sprint, GamepadButtonType::LeftThumb),
+                (Action::Crouch, GamepadButtonType::East),
+                (Action::FlipGravity, GamepadButtonType::RightThumb),
+                (Action::Ascend, GamepadButtonType::RightTrigger2),
+                (Action::Descend, GamepadButtonType::LeftTrigger2),
+                (Action::RollLeft, GamepadButtonType::LeftTrigger),
+                (Action::RollRight, GamepadButtonType::RightTrigger),
+                (Action::ToggleFlight, GamepadButtonType::North),
+                (Action::PlaceMarker, GamepadButtonType::West),
+                (Action::GiveUp, GamepadButtonType::Select),
+                (Action::ToggleGravity, GamepadButtonType::East),
+            ]),
+            gamepad_deadzone: 0.15,
+            gamepad_look_speed: 3.0,
+        }
+    }
+}
+
+const MAX_DEADZONE: f32 = 0.9;
+
+impl Controls {
+    pub fn binding(&self, action: Action) -> Binding {
+        self.bindings[&action]
+    }
+
+    pub fn gamepad_binding(&self, action: Action) -> GamepadButtonType {
+        self.gamepad_bindings[&action]
+    }
+
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        self.bindings.insert(action, binding);
+    }
+
+    pub fn bind_gamepad(&mut self, action: Action, button: GamepadButtonType) {
+        self.gamepad_bindings.insert(action, button);
+    }
+
+    pub fn load() -> Controls {
+        let Ok(json) = fs::read(CONTROLS_FILE) else {
+            return Controls::default();
+        };
+
+        match Controls::from_json(&json) {
+            Ok(controls) => controls,
+            Err(e) => {
+                error!("failed to load controls from {CONTROLS_FILE}: {e}");
+                Controls::default()
+            }
+        }
+    }
+
+    // anything the json doesn't mention keeps its default
+    fn from_json(json: &[u8]) -> serde_json::Result<Controls> {
+        let mut controls = serde_json::from_slice::<Controls>(json)?;
+        let defaults = Controls::default();
+
+        for (action, binding) in defaults.bindings {
+            controls.bindings.entry(action).or_insert(binding);
+        }
+
+        for (action, button) in defaults.gamepad_bindings {
+            controls.gamepad_bindings.entry(action).or_insert(button);
+        }
+
+        controls.gamepad_deadzone = controls.gamepad_deadzone.clamp(0.0, MAX_DEADZONE);
+
+        Ok(controls)
+    }
+
+    pub fn save(&self) {
+        let saved = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(CONTROLS_FILE, json).map_err(|e| e.to_string()));
+
+        match saved {
+            Ok(()) => info!("saved controls to {CONTROLS_FILE}"),
+            Err(e) => error!("failed to save controls to {CONTROLS_FILE}: {e}"),
+        }
+    }
+}
+
+// 1.0 if only `positive` is held, -1.0 if only `negative` is, 0.0 otherwise
+pub fn action_axis(actions: &Input<Action>, negative: Action, positive: Action) -> f32 {
+    actions.pressed(positive) as i32 as f32 - actions.pressed(negative) as i32 as f32
+}
+
+// whether `button` was just pressed on any gamepad
+pub fn gamepad_just_pressed(
+    gamepads: &Gamepads,
+    buttons: &Input<GamepadButton>,
+    button: GamepadButtonType,
+) -> bool {
+    gamepads
+        .iter()
+        .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button)))
+}
+
+// The sticks of every gamepad and the touch joysticks added together with the deadzone taken out,
+// pushing up is +y. `movement` goes up to 1.0 and `look` is in radians per second.
+#[derive(Resource, Default)]
+pub struct Sticks {
+    pub movement: Vec2,
+    pub look: Vec2,
+}
+
+// scales what's left after the deadzone back up to 0.0 to 1.0, so there's no jump at its edge
+fn without_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+
+    stick / length * ((length - deadzone) / (1.0 - deadzone)).min(1.0)
+}
+
+// Whether gameplay should be listening to the controls. The keyboard, mouse and gamepads only count
+// while the cursor is grabbed, touch counts once the touch controls are up.
+#[derive(Resource, Default)]
+pub struct InputFocus {
+    pub grabbed: bool,
+    pub touch: bool,
+}
+
+impl InputFocus {
+    pub fn active(&self) -> bool {
+        self.grabbed || self.touch
+    }
+}
+
+// runs after the touch controls have been read, see `TouchPlugin`
+#[derive(SystemLabel)]
+pub struct ControlsSystem;
+
+fn update_focus(windows: Res<Windows>, touch: Res<TouchControls>, mut focus: ResMut<InputFocus>) {
+    focus.grabbed = windows
+        .get_primary()
+        .is_some_and(|window| window.cursor_grab_mode() != CursorGrabMode::None);
+    focus.touch = touch.active;
+}
+
+fn update_actions(
+    controls: Res<Controls>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    touch: Res<TouchControls>,
+    mut actions: ResMut<Input<Action>>,
+) {
+    actions.clear();
+
+    for action in Action::ALL {
+        let on_gamepad = gamepads.iter().any(|gamepad| {
+            gamepad_buttons.pressed(GamepadButton::new(
+                gamepad,
+                controls.gamepad_binding(action),
+            ))
+        });
+
+        // browsers turn taps into mouse clicks as well, which shouldn't roll the player around
+        let on_keyboard = match controls.binding(action) {
+            Binding::Mouse(_) if touch.active => false,
+            binding => binding.pressed(&keys, &buttons),
+        };
+
+        if on_gamepad || on_keyboard || touch.pressed(action) {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+fn update_sticks(
+    controls: Res<Controls>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    touch: Res<TouchControls>,
+    mut sticks: ResMut<Sticks>,
+) {
+    let stick = |gamepad, x, y| {
+        Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.0),
+            axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.0),
+        )
+    };
+
+    let mut movement = Vec2::ZERO;
+    let mut look = Vec2::ZERO;
+
+    for gamepad in gamepads.iter() {
+        movement += stick(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        );
+        look += stick(
+            gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        );
+    }
+
+    // a stick and the touch pad held together still only walk at full speed
+    sticks.movement = (without_deadzone(movement, controls.gamepad_deadzone) + touch.movement)
+        .clamp_length_max(1.0);
+    sticks.look = (without_deadzone(look, controls.gamepad_deadzone) + touch.look)
+        * controls.gamepad_look_speed;
+}
+
+#[derive(Resource)]
+struct ControlsScreen {
+    entities: Vec<Entity>,
+
+    // index into `Action::ALL`, or one of the stick settings after it
+    selected: usize,
+
+    // waiting for the new binding of the selected action
+    listening: bool,
+}
+
+const DEADZONE_ROW: usize = Action::ALL.len();
+const LOOK_SPEED_ROW: usize = Action::ALL.len() + 1;
+const ROWS: usize = Action::ALL.len() + 2;
+
+#[derive(Component)]
+struct ControlsText;
+
+fn enter_controls(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::C)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::Select)
+    {
+        state.set(AppState::Controls).unwrap();
+    }
+}
+
+fn setup_controls(mut commands: Commands, server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: server.load("DejaVuSansMono.ttf"),
+        font_size: 28.0,
+        color: Color::WHITE,
+    };
+
+    let hint_style = TextStyle {
+        font_size: 20.0,
+        color: Color::GRAY,
+        ..style.clone()
+    };
+
+    let camera = commands.spawn(Camera2dBundle::default()).id();
+
+    // a heading, one section per row filled in by `update_controls_text`, and then the
+    // instructions
+    let sections = [TextSection::new(
+        format!("  {:<14}{:<16}gamepad\n", "", "keyboard"),
+        hint_style.clone(),
+    )]
+    .into_iter()
+    .chain((0..ROWS).map(|_| TextSection::from_style(style.clone())))
+    .chain([TextSection::new(
+        "\nup and down pick an action, return rebinds it, backspace resets it, left and right \
+         change the stick settings and escape goes back",
+        hint_style,
+    )]);
+
+    let text = commands
+        .spawn(TextBundle::from_sections(sections).with_style(Style {
+            margin: UiRect::all(Val::Px(40.0)),
+            ..default()
+        }))
+        .insert(ControlsText)
+        .id();
+
+    commands.insert_resource(ControlsScreen {
+        entities: vec![camera, text],
+        selected: 0,
+        listening: false,
+    });
+}
+
+fn rebind(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut controls: ResMut<Controls>,
+    mut screen: ResMut<ControlsScreen>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if screen.listening {
+        let action = Action::ALL[screen.selected];
+
+        if keys.just_pressed(KeyCode::Escape) {
+            screen.listening = false;
+            return;
+        }
+
+        let pressed = keys
+            .get_just_pressed()
+            .next()
+            .map(|&key| Binding::Key(key))
+            .or_else(|| {
+                buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|&button| Binding::Mouse(button))
+            });
+
+        if let Some(binding) = pressed {
+            controls.bind(action, binding);
+            screen.listening = false;
+        } else if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+            controls.bind_gamepad(action, button.button_type);
+            screen.listening = false;
+        }
+
+        return;
+    }
+
+    // the screen works with the keyboard or any gamepad
+    let pressed = |key, button| {
+        keys.just_pressed(key) || gamepad_just_pressed(&gamepads, &gamepad_buttons, button)
+    };
+
+    let change = pressed(KeyCode::Right, GamepadButtonType::DPadRight) as i32 as f32
+        - pressed(KeyCode::Left, GamepadButtonType::DPadLeft) as i32 as f32;
+
+    if pressed(KeyCode::Escape, GamepadButtonType::East) {
+        state.set(AppState::MainMenu).unwrap();
+    } else if pressed(KeyCode::Up, GamepadButtonType::DPadUp) {
+        screen.selected = (screen.selected + ROWS - 1) % ROWS;
+    } else if pressed(KeyCode::Down, GamepadButtonType::DPadDown) {
+        screen.selected = (screen.selected + 1) % ROWS;
+    } else if screen.selected == DEADZONE_ROW {
+        if pressed(KeyCode::Back, GamepadButtonType::North) {
+            controls.gamepad_deadzone = Controls::default().gamepad_deadzone;
+        }
+
+        controls.gamepad_deadzone =
+            (controls.gamepad_deadzone + change * 0.05).clamp(0.0, MAX_DEADZONE);
+    } else if screen.selected == LOOK_SPEED_ROW {
+        if pressed(KeyCode::Back, GamepadButtonType::North) {
+            controls.gamepad_look_speed = Controls::default().gamepad_look_speed;
+        }
+
+        controls.gamepad_look_speed = (controls.gamepad_look_speed + change * 0.25).max(0.25);
+    } else if pressed(KeyCode::Return, GamepadButtonType::South) {
+        screen.listening = true;
+    } else if pressed(KeyCode::Back, GamepadButtonType::North) {
+        let action = Action::ALL[screen.selected];
+        let defaults = Controls::default();
+
+        controls.bind(action, defaults.binding(action));
+        controls.bind_gamepad(action, defaults.gamepad_binding(action));
+    }
+}
+
+fn update_controls_text(
+    controls: Res<Controls>,
+    screen: Res<ControlsScreen>,
+    mut query: Query<&mut Text, With<ControlsText>>,
+) {
+    for mut text in query.iter_mut() {
+        for (i, section) in text.sections.iter_mut().skip(1).take(ROWS).enumerate() {
+            let selected = i == screen.selected;
+            let cursor = if selected { '>' } else { ' ' };
+
+            section.value = match i {
+                DEADZONE_ROW => format!(
+                    "{cursor} {:<30}{:.2}\n",
+                    "stick deadzone", controls.gamepad_deadzone
+                ),
+                LOOK_SPEED_ROW => format!(
+                    "{cursor} {:<30}{:.2} rad/s\n",
+                    "stick look speed", controls.gamepad_look_speed
+                ),
+                _ if selected && screen.listening => {
+                    format!("{cursor} {:<14}press something...\n", Action::ALL[i].name())
+                }
+                _ => {
+                    let action = Action::ALL[i];
+
+                    format!(
+                        "{cursor} {:<14}{:<16}{:?}\n",
+                        action.name(),
+                        controls.binding(action).to_string(),
+                        controls.gamepad_binding(action)
+                    )
+                }
+            };
+
+            section.style.color = if selected {
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+        }
+    }
+}
+
+fn cleanup_controls(mut commands: Commands, screen: Res<ControlsScreen>, controls: Res<Controls>) {
+    for &entity in screen.entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    controls.save();
+}
+
+pub struct ControlsPlugin;
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Controls::load())
+            .init_resource::<Input<Action>>()
+            .init_resource::<Sticks>()
+            .init_resource::<InputFocus>()
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::new()
+                    .label(ControlsSystem)
+                    .after(InputSystem)
+                    .with_system(update_focus)
+                    .with_system(update_actions)
+                    .with_system(update_sticks),
+            )
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(enter_controls))
+            .add_system_set(SystemSet::on_enter(AppState::Controls).with_system(setup_controls))
+            .add_system_set(
+                SystemSet::on_update(AppState::Controls)
+                    .with_system(rebind)
+                    .with_system(update_controls_text.after(rebind)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Controls).with_system(cleanup_controls));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_action_is_bound_by_default() {
+        let controls = Controls::default();
+
+        for action in Action::ALL {
+            assert!(controls.bindings.contains_key(&action), "{action:?}");
+            assert!(
+                controls.gamepad_bindings.contains_key(&action),
+                "{action:?}"
+            );
+        }
+
+        assert_eq!(controls.bindings.len(), Action::ALL.len());
+        assert_eq!(controls.gamepad_bindings.len(), Action::ALL.len());
+    }
+
+    #[test]
+    fn rebinds_are_saved_and_loaded_again() {
+        let mut controls = Controls::default();
+        controls.bind(Action::MoveForward, Binding::Key(KeyCode::Z));
+        controls.bind(Action::PlaceMarker, Binding::Mouse(MouseButton::Other(4)));
+        controls.bind_gamepad(Action::Jump, GamepadButtonType::East);
+        controls.gamepad_deadzone = 0.3;
+        controls.gamepad_look_speed = 2.5;
+
+        let json = serde_json::to_vec(&controls).unwrap();
+        let loaded = Controls::from_json(&json).unwrap();
+
+        assert_eq!(loaded.bindings, controls.bindings);
+        assert_eq!(loaded.gamepad_bindings, controls.gamepad_bindings);
+        assert_eq!(loaded.gamepad_deadzone, 0.3);
+        assert_eq!(loaded.gamepad_look_speed, 2.5);
+    }
+
+    #[test]
+    fn files_only_need_what_was_changed() {
+        let json =
+            r#"{ "bindings": { "MoveForward": { "Key": "Z" } }, "gamepad_look_speed": 2.0 }"#;
+        let loaded = Controls::from_json(json.as_bytes()).unwrap();
+        let defaults = Controls::default();
+
+        assert_eq!(
+            loaded.binding(Action::MoveForward),
+            Binding::Key(KeyCode::Z)
+        );
+        assert_eq!(loaded.gamepad_look_speed, 2.0);
+
+        for action in Action::ALL.into_iter().skip(1) {
+            assert_eq!(loaded.binding(action), defaults.binding(action));
+            assert_eq!(
+                loaded.gamepad_binding(action),
+                defaults.gamepad_binding(action)
+            );
+        }
+        assert_eq!(loaded.gamepad_deadzone, defaults.gamepad_deadzone);
+    }
+
+    #[test]
+    fn deadzones_are_kept_within_range() {
+        for (saved, loaded) in [
+            (-0.5, 0.0),
+            (0.5, 0.5),
+            (1.0, MAX_DEADZONE),
+            (5.0, MAX_DEADZONE),
+        ] {
+            let json = format!(r#"{{ "gamepad_deadzone": {saved} }}"#);
+            let controls = Controls::from_json(json.as_bytes()).unwrap();
+
+            assert_eq!(controls.gamepad_deadzone, loaded);
+        }
+    }
+
+    #[test]
+    fn sticks_are_still_until_past_the_deadzone() {
+        let deadzone = 0.2;
+
+        assert_eq!(without_deadzone(Vec2::ZERO, deadzone), Vec2::ZERO);
+        assert_eq!(without_deadzone(Vec2::new(0.0, 0.2), deadzone), Vec2::ZERO);
+
+        let past = without_deadzone(Vec2::new(0.0, 0.21), deadzone);
+        assert!(past.x == 0.0 && past.y > 0.0 && past.y < 0.02, "{past}");
+    }
+
+    #[test]
+    fn full_tilt_is_full_speed() {
+        let deadzone = 0.2;
+
+        for stick in [Vec2::X, Vec2::NEG_Y, Vec2::new(1.0, 1.0).normalize()] {
+            let moved = without_deadzone(stick, deadzone);
+            assert!((moved - stick).length() < 1e-6, "{stick} gave {moved}");
+        }
+
+        // the corners of square gates read past 1.0
+        let corner = without_deadzone(Vec2::ONE, deadzone);
+        assert!((corner.length() - 1.0).abs() < 1e-6, "{corner}");
+    }
+}
@@ -1,3 +1,131 @@
+use std::fmt;
+use std::str::FromStr;
+
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+
+// How likely generators are to carve along each axis compared to the others. `x` is left and
+// right, `y` is up and down and `z` is in and out, which 2D mazes don't have. `y` is vertical in
+// 3D mazes, so turning it down makes mostly flat mazes with the odd shaft between floors.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Bias {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Bias {
+    pub const UNIFORM: Bias = Bias {
+        x: 1.0,
+        y: 1.0,
+        z: 1.0,
+    };
+
+    // long corridors across 2D mazes, flat floors in 3D ones
+    pub const HORIZONTAL: Bias = Bias {
+        x: 1.0,
+        y: 0.2,
+        z: 1.0,
+    };
+
+    // long corridors down 2D mazes, tall shafts in 3D ones
+    pub const VERTICAL: Bias = Bias {
+        x: 0.2,
+        y: 1.0,
+        z: 0.2,
+    };
+
+    pub const PRESETS: [Bias; 3] = [Bias::UNIFORM, Bias::HORIZONTAL, Bias::VERTICAL];
+
+    // Shuffles `directions` so ones with a higher weight tend to come first, a weight of 0 puts
+    // them last. Equal weights are a plain shuffle so unbiased mazes match their seeds from before
+    // there was a bias.
+    fn order<D: Copy>(rng: &Rng, directions: &mut [D], weight: impl Fn(&D) -> f32) {
+        let first = weight(&directions[0]);
+
+        if directions
+            .iter()
+            .all(|direction| weight(direction) == first)
+        {
+            rng.shuffle(directions);
+            return;
+        }
+
+        let mut keyed: Vec<_> = directions
+            .iter()
+            .map(|direction| match weight(direction) {
+                w if w > 0.0 => rng.f32().powf(1.0 / w),
+                _ => -rng.f32(),
+            })
+            .zip(0..)
+            .collect();
+
+        keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let shuffled: Vec<_> = keyed.iter().map(|&(_, i)| i).collect();
+        let original = directions.to_vec();
+
+        for (direction, i) in directions.iter_mut().zip(shuffled) {
+            *direction = original[i];
+        }
+    }
+
+    // one of `directions` at random with chances proportional to their weights, or all the same
+    // chance if they're all 0
+    fn choose<D: Copy>(rng: &Rng, directions: &[D], weight: impl Fn(&D) -> f32) -> D {
+        let total: f32 = directions.iter().map(&weight).sum();
+
+        if total <= 0.0 {
+            return directions[rng.usize(..directions.len())];
+        }
+
+        let mut remaining = rng.f32() * total;
+
+        for direction in directions {
+            remaining -= weight(direction);
+
+            if remaining < 0.0 {
+                return *direction;
+            }
+        }
+
+        directions[directions.len() - 1]
+    }
+}
+
+impl Default for Bias {
+    fn default() -> Self {
+        Bias::UNIFORM
+    }
+}
+
+impl fmt::Display for Bias {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{}", self.x, self.y, self.z)
+    }
+}
+
+// "x,y,z", or "x,y" for 2D mazes
+impl FromStr for Bias {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let weights = s
+            .split(',')
+            .map(|weight| match weight.trim().parse::<f32>() {
+                Ok(weight) if weight >= 0.0 && weight.is_finite() => Ok(weight),
+                _ => Err(format!("invalid bias {s}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match weights[..] {
+            [x, y] => Ok(Bias { x, y, z: 1.0 }),
+            [x, y, z] => Ok(Bias { x, y, z }),
+            _ => Err(format!("invalid bias {s}, expected x,y or x,y,z")),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Direction2D {
     Up = 0,
@@ -7,7 +135,7 @@ pub enum Direction2D {
 }
 
 impl Direction2D {
-    pub fn random_order() -> [Direction2D; 4] {
+    pub fn random_order(rng: &Rng) -> [Direction2D; 4] {
         let mut directions = [
             Direction2D::Up,
             Direction2D::Down,
@@ -15,10 +143,36 @@ impl Direction2D {
             Direction2D::Right,
         ];
 
-        fastrand::shuffle(&mut directions);
+        rng.shuffle(&mut directions);
 
         directions
     }
+
+    // like `random_order`, but weighted towards the axes `bias` prefers
+    pub fn biased_order(rng: &Rng, bias: &Bias) -> [Direction2D; 4] {
+        let mut directions = [
+            Direction2D::Up,
+            Direction2D::Down,
+            Direction2D::Left,
+            Direction2D::Right,
+        ];
+
+        Bias::order(rng, &mut directions, |direction| direction.weight(bias));
+
+        directions
+    }
+
+    // one of `directions`, which can't be empty, weighted the same way as `biased_order`
+    pub fn biased_choice(rng: &Rng, bias: &Bias, directions: &[Direction2D]) -> Direction2D {
+        Bias::choose(rng, directions, |direction| direction.weight(bias))
+    }
+
+    pub fn weight(self, bias: &Bias) -> f32 {
+        match self {
+            Direction2D::Up | Direction2D::Down => bias.y,
+            Direction2D::Left | Direction2D::Right => bias.x,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -32,7 +186,22 @@ pub enum Direction3D {
 }
 
 impl Direction3D {
-    pub fn random_order() -> [Direction3D; 6] {
+    pub fn random_order(rng: &Rng) -> [Direction3D; 6] {
+        let mut directions = [
+            Direction3D::Up,
+            Direction3D::Down,
+            Direction3D::Left,
+            Direction3D::Right,
+            Direction3D::In,
+            Direction3D::Out,
+        ];
+
+        rng.shuffle(&mut directions);
+
+        directions
+    }
+
+    pub fn biased_order(rng: &Rng, bias: &Bias) -> [Direction3D; 6] {
         let mut directions = [
             Direction3D::Up,
             Direction3D::Down,
@@ -42,8 +211,52 @@ impl Direction3D {
             Direction3D::Out,
         ];
 
-        fastrand::shuffle(&mut directions);
+        Bias::order(rng, &mut directions, |direction| direction.weight(bias));
 
         directions
     }
+
+    pub fn biased_choice(rng: &Rng, bias: &Bias, directions: &[Direction3D]) -> Direction3D {
+        Bias::choose(rng, directions, |direction| direction.weight(bias))
+    }
+
+    pub fn weight(self, bias: &Bias) -> f32 {
+        match self {
+            Direction3D::Up | Direction3D::Down => bias.y,
+            Direction3D::Left | Direction3D::Right => bias.x,
+            Direction3D::In | Direction3D::Out => bias.z,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_weights_are_a_plain_shuffle() {
+        let even = Bias {
+            x: 0.5,
+            y: 0.5,
+            z: 0.5,
+        };
+
+        for bias in [Bias::UNIFORM, even] {
+            for seed in 0..16 {
+                let (plain, biased) = (Rng::with_seed(seed), Rng::with_seed(seed));
+
+                assert_eq!(
+                    Direction2D::random_order(&plain).map(|direction| direction as u8),
+                    Direction2D::biased_order(&biased, &bias).map(|direction| direction as u8)
+                );
+                assert_eq!(
+                    Direction3D::random_order(&plain).map(|direction| direction as u8),
+                    Direction3D::biased_order(&biased, &bias).map(|direction| direction as u8)
+                );
+
+                // as many numbers are used up, so whatever's generated next matches as well
+                assert_eq!(plain.u64(..), biased.u64(..));
+            }
+        }
+    }
 }
@@ -0,0 +1,368 @@
+use std::ops::RangeInclusive;
+
+use fastrand::Rng;
+
+use crate::direction::{Bias, Direction2D};
+use crate::maze2d::Maze2D;
+
+// Rectangular rooms with hunt and kill corridors filling the space between them. Every room gets
+// at least one door, and sizes are in logical cells.
+#[derive(Clone, Debug)]
+pub struct Dungeon {
+    // rooms that get placed at random, ones that would overlap another room are skipped
+    pub room_attempts: usize,
+    pub room_size: RangeInclusive<usize>,
+
+    // chance of each room getting a second door, which makes loops
+    pub extra_door_chance: f32,
+
+    // see `Maze2D::prune_dead_ends`
+    pub prune_dead_ends: bool,
+
+    // only affects the corridors
+    pub bias: Bias,
+}
+
+impl Default for Dungeon {
+    fn default() -> Self {
+        Dungeon {
+            room_attempts: 20,
+            room_size: 2..=4,
+            extra_door_chance: 0.5,
+            prune_dead_ends: false,
+            bias: Bias::UNIFORM,
+        }
+    }
+}
+
+// a logical cell one step away in `direction`, if it's inside a maze of `width` by `height`
+fn step(
+    (x, y): (usize, usize),
+    direction: Direction2D,
+    width: usize,
+    height: usize,
+) -> Option<(usize, usize)> {
+    match direction {
+        Direction2D::Up if y > 0 => Some((x, y - 1)),
+        Direction2D::Down if y + 1 < height => Some((x, y + 1)),
+        Direction2D::Left if x > 0 => Some((x - 1, y)),
+        Direction2D::Right if x + 1 < width => Some((x + 1, y)),
+        _ => None,
+    }
+}
+
+// opens two logical cells and the wall between them, they have to be next to each other
+fn connect(maze: &mut Maze2D, (ax, ay): (usize, usize), (bx, by): (usize, usize)) {
+    maze.cells[ay * 2 + 1][ax * 2 + 1] = false;
+    maze.cells[by * 2 + 1][bx * 2 + 1] = false;
+    maze.cells[ay + by + 1][ax + bx + 1] = false;
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+
+    i
+}
+
+impl Dungeon {
+    pub fn generate(&self, width: usize, height: usize, seed: u64) -> Maze2D {
+        self.generate_with_rooms(width, height, seed).0
+    }
+
+    // along with which room each logical cell is in
+    fn generate_with_rooms(
+        &self,
+        width: usize,
+        height: usize,
+        seed: u64,
+    ) -> (Maze2D, Vec<Vec<Option<usize>>>) {
+        let rng = Rng::with_seed(seed);
+
+        let mut maze = Maze2D {
+            cells: vec![vec![true; width * 2 + 1]; height * 2 + 1],
+            width,
+            height,
+            seed,
+            generator: "dungeon".to_string(),
+            bias: self.bias,
+            start: (1, 1),
+            goal: (width * 2 - 1, height * 2 - 1),
+            portals: vec![],
+        };
+
+        let mut rooms = vec![vec![None; width]; height];
+        let mut room_count = 0;
+
+        for _ in 0..self.room_attempts {
+            let size = (
+                rng.usize(self.room_size.clone()).min(width),
+                rng.usize(self.room_size.clone()).min(height),
+            );
+            let from = (rng.usize(..=width - size.0), rng.usize(..=height - size.1));
+
+            let overlaps = (from.1..from.1 + size.1)
+                .any(|y| (from.0..from.0 + size.0).any(|x| rooms[y][x].is_some()));
+
+            if overlaps {
+                continue;
+            }
+
+            for (y, row) in rooms.iter_mut().enumerate().skip(from.1).take(size.1) {
+                for (x, room) in row.iter_mut().enumerate().skip(from.0).take(size.0) {
+                    *room = Some(room_count);
+
+                    for neighbour in [(x + 1, y), (x, y + 1)] {
+                        if neighbour.0 < from.0 + size.0 && neighbour.1 < from.1 + size.1 {
+                            connect(&mut maze, (x, y), neighbour);
+                        }
+                    }
+                }
+            }
+
+            // pillars in the middle of the room
+            for y in from.1 * 2 + 2..(from.1 + size.1) * 2 {
+                for x in from.0 * 2 + 2..(from.0 + size.0) * 2 {
+                    maze.cells[y][x] = false;
+                }
+            }
+
+            room_count += 1;
+        }
+
+        self.carve_corridors(&mut maze, &rooms, &rng);
+        self.add_doors(&mut maze, &rooms, room_count, &rng);
+
+        if self.prune_dead_ends {
+            maze.prune_dead_ends();
+        }
+
+        (maze, rooms)
+    }
+
+    // Hunt and kill through every logical cell outside of the rooms. Rooms can wall corridors off
+    // from each other, those get a walk of their own and are joined up by doors later.
+    fn carve_corridors(&self, maze: &mut Maze2D, rooms: &[Vec<Option<usize>>], rng: &Rng) {
+        let (width, height, bias) = (maze.width, maze.height, maze.bias);
+        let mut visited = vec![vec![false; width]; height];
+
+        let free =
+            |visited: &[Vec<bool>], (x, y): (usize, usize)| rooms[y][x].is_none() && !visited[y][x];
+
+        loop {
+            // somewhere unvisited next to a corridor, or anywhere unvisited if there isn't one
+            let mut hunted = None;
+
+            'hunt: for y in 0..height {
+                for x in 0..width {
+                    if !free(&visited, (x, y)) {
+                        continue;
+                    }
+
+                    let from = Direction2D::biased_order(rng, &bias)
+                        .into_iter()
+                        .filter_map(|direction| step((x, y), direction, width, height))
+                        .find(|&(x, y)| visited[y][x]);
+
+                    if from.is_some() || hunted.is_none() {
+                        hunted = Some(((x, y), from));
+                    }
+
+                    if from.is_some() {
+                        break 'hunt;
+                    }
+                }
+            }
+
+            let Some((mut cell, from)) = hunted else {
+                break;
+            };
+
+            match from {
+                Some(from) => connect(maze, from, cell),
+                None => connect(maze, cell, cell),
+            }
+
+            visited[cell.1][cell.0] = true;
+
+            // walk until there's nowhere left to go
+            while let Some(next) = Direction2D::biased_order(rng, &bias)
+                .into_iter()
+                .filter_map(|direction| step(cell, direction, width, height))
+                .find(|&next| free(&visited, next))
+            {
+                connect(maze, cell, next);
+                visited[next.1][next.0] = true;
+                cell = next;
+            }
+        }
+    }
+
+    // Opens walls around rooms until everything is connected, then gives some rooms a second door.
+    fn add_doors(
+        &self,
+        maze: &mut Maze2D,
+        rooms: &[Vec<Option<usize>>],
+        room_count: usize,
+        rng: &Rng,
+    ) {
+        let (width, height) = (maze.width, maze.height);
+
+        // logical cells are joined up as they're connected, starting with what's already open
+        let mut parents: Vec<usize> = (0..width * height).collect();
+
+        for y in 0..height {
+            for x in 0..width {
+                for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                    if nx < width && ny < height && !maze.cells[y + ny + 1][x + nx + 1] {
+                        let (a, b) = (
+                            find(&mut parents, y * width + x),
+                            find(&mut parents, ny * width + nx),
+                        );
+                        parents[a] = b;
+                    }
+                }
+            }
+        }
+
+        // closed walls with a room on at least one side
+        let mut doors = vec![];
+
+        for y in 0..height {
+            for x in 0..width {
+                for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                    if nx < width
+                        && ny < height
+                        && maze.cells[y + ny + 1][x + nx + 1]
+                        && (rooms[y][x].is_some() || rooms[ny][nx].is_some())
+                    {
+                        doors.push(((x, y), (nx, ny)));
+                    }
+                }
+            }
+        }
+
+        rng.shuffle(&mut doors);
+
+        for &(a, b) in doors.iter() {
+            let (root_a, root_b) = (
+                find(&mut parents, a.1 * width + a.0),
+                find(&mut parents, b.1 * width + b.0),
+            );
+
+            if root_a != root_b {
+                connect(maze, a, b);
+                parents[root_a] = root_b;
+            }
+        }
+
+        for room in 0..room_count {
+            if rng.f32() >= self.extra_door_chance {
+                continue;
+            }
+
+            let extra = doors.iter().find(|&&(a, b)| {
+                (rooms[a.1][a.0] == Some(room) || rooms[b.1][b.0] == Some(room))
+                    && maze.cells[a.1 + b.1 + 1][a.0 + b.0 + 1]
+            });
+
+            if let Some(&(a, b)) = extra {
+                connect(maze, a, b);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: [(usize, usize); 3] = [(10, 10), (7, 13), (3, 2)];
+
+    fn dungeons() -> impl Iterator<Item = (Maze2D, Vec<Vec<Option<usize>>>)> {
+        [false, true].into_iter().flat_map(|prune_dead_ends| {
+            let dungeon = Dungeon {
+                prune_dead_ends,
+                ..Default::default()
+            };
+
+            SIZES.into_iter().flat_map(move |(width, height)| {
+                let dungeon = dungeon.clone();
+                (0..20).map(move |seed| dungeon.generate_with_rooms(width, height, seed))
+            })
+        })
+    }
+
+    #[test]
+    fn dungeons_are_connected() {
+        for (maze, _) in dungeons() {
+            assert_eq!(maze.unreachable_regions(), Vec::<Vec<_>>::new(), "{maze}");
+        }
+    }
+
+    #[test]
+    fn every_room_has_a_door() {
+        for (maze, rooms) in dungeons() {
+            let (width, height) = (maze.width, maze.height);
+            let room_count = rooms
+                .iter()
+                .flatten()
+                .flatten()
+                .max()
+                .map_or(0, |&room| room + 1);
+
+            for room in 0..room_count {
+                let door = (0..height).any(|y| {
+                    (0..width).any(|x| {
+                        rooms[y][x] == Some(room)
+                            && [
+                                Direction2D::Up,
+                                Direction2D::Down,
+                                Direction2D::Left,
+                                Direction2D::Right,
+                            ]
+                            .into_iter()
+                            .any(|direction| {
+                                step((x, y), direction, width, height).is_some_and(|(nx, ny)| {
+                                    rooms[ny][nx] != Some(room)
+                                        && !maze.cells[y + ny + 1][x + nx + 1]
+                                })
+                            })
+                    })
+                });
+
+                // unless it fills the whole maze
+                let everywhere = rooms.iter().flatten().all(|&cell| cell == Some(room));
+
+                assert!(door || everywhere, "room {room} has no door\n{maze}");
+            }
+        }
+    }
+
+    #[test]
+    fn pruned_dungeons_have_no_dead_ends() {
+        let dungeon = Dungeon {
+            prune_dead_ends: true,
+            ..Default::default()
+        };
+
+        for (width, height) in SIZES {
+            for seed in 0..20 {
+                let maze = dungeon.generate(width, height, seed);
+
+                for y in (1..height * 2).step_by(2) {
+                    for x in (1..width * 2).step_by(2) {
+                        let dead_end = !maze.cells[y][x]
+                            && (x, y) != maze.start
+                            && (x, y) != maze.goal
+                            && maze.neighbours((x, y)).count() < 2;
+
+                        assert!(!dead_end, "dead end at {:?}\n{maze}", (x, y));
+                    }
+                }
+            }
+        }
+    }
+}
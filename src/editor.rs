@@ -0,0 +1,677 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use bevy::{prelude::*, render::camera::ScalingMode};
+use mazemazemaze::maze2d::{Maze2D, MAZE_SCALE};
+use mazemazemaze::maze_file::MazeFile;
+
+use crate::{filter::FilterCamera, maze2d_plugin::Maze2DBundle, state::AppState, Settings};
+
+// where ctrl+s saves the maze and ctrl+o loads it from, unless a file was given in `Settings`
+const EDITOR_FILE: &str = "editor.maze";
+
+// the oldest edits are forgotten past this
+const UNDO_LIMIT: usize = 100;
+
+// The maze being edited, kept between visits to the editor. While `playtest` is set it's played
+// instead of a generated maze. `path` is the file it's saved to and loaded from.
+#[derive(Resource)]
+pub struct EditedMaze2D {
+    pub maze: Maze2D,
+    pub markers: Vec<(usize, usize)>,
+    pub playtest: bool,
+    pub path: String,
+}
+
+// A file given in `Settings` is edited here if it doesn't exist yet or holds a 2D maze, 3D mazes
+// go to the 3D editor instead.
+impl FromWorld for EditedMaze2D {
+    fn from_world(world: &mut World) -> Self {
+        let mut edited = EditedMaze2D {
+            maze: Maze2D::hunt_and_kill(10, 10, fastrand::u64(..)),
+            markers: vec![],
+            playtest: false,
+            path: EDITOR_FILE.into(),
+        };
+
+        if let Some(path) = world.resource::<Settings>().maze_file.clone() {
+            if !Path::new(&path).exists() {
+                edited.path = path;
+            } else if let Some(file) = load_maze_file(&path) {
+                if let Ok(maze) = file.to_maze2d() {
+                    (edited.maze, edited.markers) = (maze, file.markers2d());
+                    edited.path = path;
+                }
+            }
+        }
+
+        edited
+    }
+}
+
+// Copies of whatever is being edited from before each edit, and from before each undo so it can
+// be redone.
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        History {
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+}
+
+impl<T> History<T> {
+    pub fn record(&mut self, before: T) {
+        self.undo.push(before);
+        self.redo.clear();
+
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+
+        Some(next)
+    }
+}
+
+pub fn save_maze_file(path: &str, file: &MazeFile) {
+    let saved = file
+        .to_binary()
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| fs::write(path, bytes).map_err(|e| e.to_string()));
+
+    match saved {
+        Ok(()) => info!("saved maze to {path}"),
+        Err(e) => error!("failed to save maze to {path}: {e}"),
+    }
+}
+
+pub fn load_maze_file(path: &str) -> Option<MazeFile> {
+    let loaded = fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| MazeFile::from_bytes(&bytes).map_err(|e| e.to_string()));
+
+    match loaded {
+        Ok(file) => {
+            info!("loaded maze from {path}");
+            Some(file)
+        }
+        Err(e) => {
+            error!("failed to load maze from {path}: {e}");
+            None
+        }
+    }
+}
+
+// what clicking on a cell does, picked with the number keys
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Tool {
+    Wall,
+    Start,
+    Goal,
+    Portal,
+    Marker,
+}
+
+// the maze and its markers before or after an edit
+type Snapshot = (Maze2D, Vec<(usize, usize)>);
+
+#[derive(Resource)]
+struct EditorData {
+    root: Entity,
+    tool: Tool,
+
+    // first end of a portal that's being placed
+    portal_start: Option<(usize, usize)>,
+
+    history: History<Snapshot>,
+}
+
+#[derive(Resource)]
+struct EditorAssets {
+    tile: Handle<Mesh>,
+    sphere: Handle<Mesh>,
+    unreachable: Handle<StandardMaterial>,
+    start: Handle<StandardMaterial>,
+    goal: Handle<StandardMaterial>,
+    portal: Handle<StandardMaterial>,
+    marker: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct EditorCamera;
+
+#[derive(Component)]
+struct EditorMaze;
+
+// everything drawn on top of the maze, redrawn after every edit
+#[derive(Component)]
+struct EditorOverlay;
+
+// straight down at the whole maze, with rows going down the screen
+fn editor_camera(maze: &Maze2D) -> (Transform, Projection) {
+    let size = Vec2::new(maze.cells[0].len() as f32, maze.cells.len() as f32) * MAZE_SCALE;
+
+    let corner = Vec3::from(maze.cell_translation((0, 0)));
+    let center = Vec3::new(
+        corner.x + (size.x - MAZE_SCALE) / 2.0,
+        0.0,
+        corner.z + (size.y - MAZE_SCALE) / 2.0,
+    );
+
+    (
+        Transform::from_translation(center + Vec3::Y * 100.0).looking_at(center, Vec3::NEG_Z),
+        Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::Auto {
+                min_width: size.x + MAZE_SCALE * 2.0,
+                min_height: size.y + MAZE_SCALE * 2.0,
+            },
+            ..default()
+        }),
+    )
+}
+
+// e opens the 2D editor, shift+e the 3D one
+fn enter_editor(keys: Res<Input<KeyCode>>, mut state: ResMut<State<AppState>>) {
+    if keys.just_pressed(KeyCode::E) {
+        if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+            state.set(AppState::Editor3D).unwrap();
+        } else {
+            state.set(AppState::Editor).unwrap();
+        }
+    }
+}
+
+fn setup_editor(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    edited: Res<EditedMaze2D>,
+) {
+    let mut unlit = |color: &str| {
+        materials.add(StandardMaterial {
+            base_color: Color::hex(color).unwrap(),
+            unlit: true,
+            ..default()
+        })
+    };
+
+    commands.insert_resource(EditorAssets {
+        tile: meshes.add(shape::Plane { size: MAZE_SCALE }.into()),
+        sphere: meshes.add(
+            shape::UVSphere {
+                radius: MAZE_SCALE / 4.0,
+                ..default()
+            }
+            .into(),
+        ),
+        unreachable: unlit("ff0000"),
+        start: unlit("00ff00"),
+        goal: unlit("ffaa00"),
+        portal: unlit("aa00ff"),
+        marker: unlit("ffff00"),
+    });
+
+    let (transform, projection) = editor_camera(&edited.maze);
+
+    let root = commands
+        .spawn(TransformBundle::default())
+        .insert(VisibilityBundle::default())
+        .with_children(|parent| {
+            parent
+                .spawn(Camera3dBundle {
+                    transform,
+                    projection,
+                    ..default()
+                })
+                .insert(EditorCamera)
+                .insert(FilterCamera);
+
+            parent.spawn(DirectionalLightBundle {
+                transform: Transform::from_rotation(Quat::from_rotation_x(-PI / 3.0)),
+                ..default()
+            });
+
+            parent
+                .spawn(Maze2DBundle {
+                    maze: edited.maze.clone(),
+                    ..default()
+                })
+                .insert(EditorMaze);
+        })
+        .id();
+
+    commands.insert_resource(EditorData {
+        root,
+        tool: Tool::Wall,
+        portal_start: None,
+        history: History::default(),
+    });
+}
+
+// Applies `tool` to a cell of a copy of the maze. Walls can't be put over anything placed in the
+// maze, and the outer walls stay put so there's no way out.
+fn apply_tool(
+    tool: Tool,
+    portal_start: &mut Option<(usize, usize)>,
+    (maze, markers): &mut Snapshot,
+    (x, y): (usize, usize),
+) {
+    let open = !maze.cells[y][x];
+    let occupied = (x, y) == maze.start
+        || (x, y) == maze.goal
+        || *portal_start == Some((x, y))
+        || maze.portal_destination((x, y)).is_some()
+        || markers.contains(&(x, y));
+
+    match tool {
+        Tool::Wall => {
+            let border =
+                x == 0 || y == 0 || y == maze.cells.len() - 1 || x == maze.cells[y].len() - 1;
+
+            if !border && !occupied {
+                maze.cells[y][x] = open;
+            }
+        }
+        Tool::Start if open => maze.start = (x, y),
+        Tool::Goal if open => maze.goal = (x, y),
+        Tool::Portal if maze.portal_destination((x, y)).is_some() => {
+            maze.portals.retain(|&[a, b]| a != (x, y) && b != (x, y));
+        }
+        Tool::Portal if open => match portal_start.take() {
+            Some(start) if start != (x, y) => maze.portals.push([start, (x, y)]),
+            Some(_) => (),
+            None => *portal_start = Some((x, y)),
+        },
+        Tool::Marker if markers.contains(&(x, y)) => markers.retain(|&marker| marker != (x, y)),
+        Tool::Marker if open => markers.push((x, y)),
+        _ => (),
+    }
+}
+
+// left click uses the current tool on the cell under the cursor
+fn editor_click(
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    mut edited: ResMut<EditedMaze2D>,
+    mut data: ResMut<EditorData>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let window = windows.get_primary().unwrap();
+
+    if let Some(mouse_position) = window.cursor_position() {
+        #[cfg(not(target_family = "wasm"))]
+        let mouse_position = mouse_position * window.scale_factor() as f32;
+
+        for (camera, camera_transform) in camera_query.iter() {
+            if let Some(ray) = camera.viewport_to_world(camera_transform, mouse_position) {
+                // the camera looks straight down so any height will do
+                let t = -ray.origin.y / ray.direction.y;
+                let cursor_position_world = ray.origin + ray.direction * t;
+
+                if let Some(cell) = edited.maze.cell_at(cursor_position_world.into()) {
+                    let before = (edited.maze.clone(), edited.markers.clone());
+                    let mut after = before.clone();
+
+                    apply_tool(data.tool, &mut data.portal_start, &mut after, cell);
+
+                    if after != before {
+                        data.history.record(before);
+                        (edited.maze, edited.markers) = after;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Number keys pick a tool, ctrl+z and ctrl+y undo and redo, ctrl+s and ctrl+o save and load,
+// return plays the maze and escape goes back to the menu.
+fn editor(
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut edited: ResMut<EditedMaze2D>,
+    mut data: ResMut<EditorData>,
+) {
+    let tools = [
+        (KeyCode::Key1, Tool::Wall),
+        (KeyCode::Key2, Tool::Start),
+        (KeyCode::Key3, Tool::Goal),
+        (KeyCode::Key4, Tool::Portal),
+        (KeyCode::Key5, Tool::Marker),
+    ];
+
+    for (key, tool) in tools {
+        if keys.just_pressed(key) {
+            info!("editor tool: {tool:?}");
+            data.tool = tool;
+            data.portal_start = None;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu).unwrap();
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        edited.playtest = true;
+        state.set(AppState::Maze2D).unwrap();
+    }
+
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+
+    let current = (edited.maze.clone(), edited.markers.clone());
+
+    if keys.just_pressed(KeyCode::Z) {
+        if let Some(previous) = data.history.undo(current) {
+            (edited.maze, edited.markers) = previous;
+        }
+    } else if keys.just_pressed(KeyCode::Y) {
+        if let Some(next) = data.history.redo(current) {
+            (edited.maze, edited.markers) = next;
+        }
+    } else if keys.just_pressed(KeyCode::S) {
+        save_maze_file(
+            &edited.path,
+            &MazeFile::from_maze2d(&edited.maze, &edited.markers),
+        );
+    } else if keys.just_pressed(KeyCode::O) {
+        let loaded = load_maze_file(&edited.path).and_then(|file| match file.to_maze2d() {
+            Ok(maze) => Some((maze, file.markers2d())),
+            Err(e) => {
+                error!("failed to load maze from {}: {e}", edited.path);
+                None
+            }
+        });
+
+        if let Some(loaded) = loaded {
+            data.history.record(current);
+            data.portal_start = None;
+            (edited.maze, edited.markers) = loaded;
+        }
+    }
+}
+
+// copies edits over to the maze in the world, which then rebuilds its own mesh
+fn update_editor_maze(
+    edited: Res<EditedMaze2D>,
+    mut maze_query: Query<&mut Maze2D, With<EditorMaze>>,
+    mut camera_query: Query<(&mut Transform, &mut Projection), With<EditorCamera>>,
+) {
+    if !edited.is_changed() {
+        return;
+    }
+
+    for mut maze in maze_query.iter_mut() {
+        if *maze != edited.maze {
+            *maze = edited.maze.clone();
+        }
+    }
+
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        (*transform, *projection) = editor_camera(&edited.maze);
+    }
+}
+
+// start, goal, portals, markers and every cell that can't be reached from the start
+fn draw_overlays(
+    mut commands: Commands,
+    edited: Res<EditedMaze2D>,
+    data: Res<EditorData>,
+    assets: Res<EditorAssets>,
+    overlay_query: Query<Entity, With<EditorOverlay>>,
+) {
+    if !edited.is_changed() && !data.is_changed() {
+        return;
+    }
+
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let maze = &edited.maze;
+
+    let tiles = maze
+        .unreachable_regions()
+        .into_iter()
+        .flatten()
+        .map(|cell| (cell, &assets.unreachable))
+        .chain([(maze.start, &assets.start), (maze.goal, &assets.goal)])
+        .chain(
+            maze.portals
+                .iter()
+                .flatten()
+                .chain(data.portal_start.iter())
+                .map(|&cell| (cell, &assets.portal)),
+        );
+
+    let spheres = edited.markers.iter().map(|&cell| (cell, &assets.marker));
+
+    let overlays = tiles
+        .map(|(cell, material)| (cell, &assets.tile, material))
+        .chain(spheres.map(|(cell, material)| (cell, &assets.sphere, material)));
+
+    commands.entity(data.root).with_children(|parent| {
+        for (cell, mesh, material) in overlays {
+            // just above the floor, under the tops of the walls
+            let translation =
+                Vec3::from(maze.cell_translation(cell)) * Vec3::new(1.0, 0.0, 1.0) + Vec3::Y * 0.1;
+
+            parent
+                .spawn(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                })
+                .insert(EditorOverlay);
+        }
+    });
+}
+
+fn cleanup_editor(mut commands: Commands, data: Res<EditorData>) {
+    commands.entity(data.root).despawn_recursive();
+    commands.remove_resource::<EditorData>();
+    commands.remove_resource::<EditorAssets>();
+}
+
+pub struct EditorPlugin;
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditedMaze2D>()
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(enter_editor))
+            .add_system_set(SystemSet::on_enter(AppState::Editor).with_system(setup_editor))
+            .add_system_set(
+                SystemSet::on_update(AppState::Editor)
+                    .with_system(editor)
+                    .with_system(editor_click)
+                    .with_system(update_editor_maze.after(editor).after(editor_click))
+                    .with_system(draw_overlays.after(editor).after(editor_click)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Editor).with_system(cleanup_editor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 4x4 maze starting at (1, 1), every odd cell is open and every even one is a wall
+    fn snapshot() -> Snapshot {
+        let maze = Maze2D::hunt_and_kill(4, 4, 3);
+        assert_eq!(maze.start, (1, 1));
+
+        (maze, vec![])
+    }
+
+    fn applied(
+        tool: Tool,
+        portal_start: &mut Option<(usize, usize)>,
+        snapshot: &Snapshot,
+        cell: (usize, usize),
+    ) -> Snapshot {
+        let mut after = snapshot.clone();
+        apply_tool(tool, portal_start, &mut after, cell);
+
+        after
+    }
+
+    #[test]
+    fn only_the_last_hundred_edits_can_be_undone() {
+        let mut history = History::default();
+
+        for edit in 0..UNDO_LIMIT + 5 {
+            history.record(edit);
+        }
+
+        for edit in (5..UNDO_LIMIT + 5).rev() {
+            assert_eq!(history.undo(edit + 1), Some(edit));
+        }
+        assert_eq!(history.undo(5), None);
+    }
+
+    #[test]
+    fn undone_edits_are_redone_until_something_else_is_edited() {
+        let mut history = History::default();
+        history.record(1);
+        history.record(2);
+
+        assert_eq!(history.undo(3), Some(2));
+        assert_eq!(history.undo(2), Some(1));
+        assert_eq!(history.redo(1), Some(2));
+
+        history.record(2);
+        assert_eq!(history.redo(4), None);
+        assert_eq!(history.undo(4), Some(2));
+        assert_eq!(history.undo(2), Some(1));
+    }
+
+    #[test]
+    fn walls_stay_off_the_border_and_anything_placed() {
+        let mut portal_start = None;
+        let (mut maze, mut markers) = snapshot();
+        maze.goal = (7, 7);
+        maze.portals = vec![[(3, 3), (5, 5)]];
+        markers.push((7, 1));
+        let snapshot = (maze, markers);
+
+        for cell in [
+            (0, 2),
+            (2, 0),
+            (8, 4),
+            (4, 8),
+            (1, 1),
+            (7, 7),
+            (3, 3),
+            (5, 5),
+            (7, 1),
+        ] {
+            assert_eq!(
+                applied(Tool::Wall, &mut portal_start, &snapshot, cell),
+                snapshot
+            );
+        }
+
+        // including the end of a portal that's still being placed
+        portal_start = Some((1, 3));
+        assert_eq!(
+            applied(Tool::Wall, &mut portal_start, &snapshot, (1, 3)),
+            snapshot
+        );
+        portal_start = None;
+
+        let opened = applied(Tool::Wall, &mut portal_start, &snapshot, (2, 2));
+        assert!(!opened.0.cells[2][2]);
+        assert!(
+            applied(Tool::Wall, &mut portal_start, &opened, (2, 2))
+                .0
+                .cells[2][2]
+        );
+    }
+
+    #[test]
+    fn starts_goals_and_markers_only_go_in_open_cells() {
+        let mut portal_start = None;
+        let snapshot = snapshot();
+
+        for tool in [Tool::Start, Tool::Goal, Tool::Marker, Tool::Portal] {
+            assert_eq!(
+                applied(tool, &mut portal_start, &snapshot, (2, 2)),
+                snapshot
+            );
+        }
+        assert_eq!(portal_start, None);
+
+        assert_eq!(
+            applied(Tool::Start, &mut portal_start, &snapshot, (3, 5))
+                .0
+                .start,
+            (3, 5)
+        );
+        assert_eq!(
+            applied(Tool::Goal, &mut portal_start, &snapshot, (3, 5))
+                .0
+                .goal,
+            (3, 5)
+        );
+
+        let marked = applied(Tool::Marker, &mut portal_start, &snapshot, (3, 5));
+        assert_eq!(marked.1, [(3, 5)]);
+        assert_eq!(
+            applied(Tool::Marker, &mut portal_start, &marked, (3, 5)),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn portals_are_placed_in_pairs_and_removed_together() {
+        let mut portal_start = None;
+        let snapshot = snapshot();
+
+        let half = applied(Tool::Portal, &mut portal_start, &snapshot, (3, 3));
+        assert_eq!(half, snapshot);
+        assert_eq!(portal_start, Some((3, 3)));
+
+        // clicking the same cell again starts over
+        assert_eq!(
+            applied(Tool::Portal, &mut portal_start, &half, (3, 3)),
+            snapshot
+        );
+        assert_eq!(portal_start, None);
+
+        portal_start = Some((3, 3));
+        let paired = applied(Tool::Portal, &mut portal_start, &half, (5, 7));
+        assert_eq!(paired.0.portals, [[(3, 3), (5, 7)]]);
+        assert_eq!(portal_start, None);
+
+        // either end takes the whole portal away
+        for end in [(3, 3), (5, 7)] {
+            let removed = applied(Tool::Portal, &mut portal_start, &paired, end);
+            assert!(removed.0.portals.is_empty());
+            assert_eq!(portal_start, None);
+        }
+    }
+}
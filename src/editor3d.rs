@@ -0,0 +1,523 @@
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::path::Path;
+
+use bevy::{prelude::*, render::camera::ScalingMode};
+use mazemazemaze::maze3d::Maze3D;
+use mazemazemaze::maze_file::MazeFile;
+
+use crate::editor::{load_maze_file, save_maze_file, History};
+use crate::{filter::FilterCamera, state::AppState, Settings};
+
+// where ctrl+s saves the maze and ctrl+o loads it from, unless a 3D maze was given in `Settings`
+const EDITOR3D_FILE: &str = "editor3d.maze";
+
+// The maze being edited, kept between visits to the editor. While `playtest` is set it's played
+// instead of a generated maze. `path` is the file it's saved to and loaded from. There's no tool
+// for markers, but any the file had are kept and saved back.
+#[derive(Resource)]
+pub struct EditedMaze3D {
+    pub maze: Maze3D,
+    pub markers: Vec<(usize, usize, usize)>,
+    pub playtest: bool,
+    pub path: String,
+}
+
+// see `EditedMaze2D`, new files are left to the 2D editor
+impl FromWorld for EditedMaze3D {
+    fn from_world(world: &mut World) -> Self {
+        let mut edited = EditedMaze3D {
+            maze: Maze3D::hunt_and_kill(4, 4, 4, fastrand::u64(..)),
+            markers: vec![],
+            playtest: false,
+            path: EDITOR3D_FILE.into(),
+        };
+
+        if let Some(path) = world.resource::<Settings>().maze_file.clone() {
+            if Path::new(&path).exists() {
+                if let Some(file) = load_maze_file(&path) {
+                    if let Ok(maze) = file.to_maze3d() {
+                        (edited.maze, edited.markers) = (maze, file.markers3d());
+                        edited.path = path;
+                    }
+                }
+            }
+        }
+
+        edited
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Tool {
+    Wall,
+    Start,
+    Goal,
+}
+
+// the maze and its markers before or after an edit
+type Snapshot = (Maze3D, Vec<(usize, usize, usize)>);
+
+#[derive(Resource)]
+struct Editor3DData {
+    root: Entity,
+    tool: Tool,
+
+    // the z of the cells being shown
+    layer: usize,
+
+    history: History<Snapshot>,
+}
+
+#[derive(Resource)]
+struct Editor3DAssets {
+    tile: Handle<Mesh>,
+    wall: Handle<StandardMaterial>,
+    above: Handle<StandardMaterial>,
+    below: Handle<StandardMaterial>,
+    unreachable: Handle<StandardMaterial>,
+    start: Handle<StandardMaterial>,
+    goal: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct Editor3DCamera;
+
+// one cell of the grid view, redrawn after every edit
+#[derive(Component)]
+struct Editor3DTile;
+
+// Where a cell of a layer is drawn. Layers are laid out with x going right and y going up the
+// screen, like they would look from the start of the maze.
+fn tile_translation((x, y): (usize, usize)) -> Vec3 {
+    Vec3::new(x as f32, 0.0, -(y as f32))
+}
+
+// straight down at the whole layer
+fn editor3d_camera(maze: &Maze3D) -> (Transform, Projection) {
+    let size = Vec2::new(maze.cells[0][0].len() as f32, maze.cells[0].len() as f32);
+    let center = (tile_translation((0, 0)) + Vec3::new(size.x - 1.0, 0.0, 1.0 - size.y)) / 2.0;
+
+    (
+        Transform::from_translation(center + Vec3::Y * 100.0).looking_at(center, Vec3::NEG_Z),
+        Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::Auto {
+                min_width: size.x + 2.0,
+                min_height: size.y + 2.0,
+            },
+            ..default()
+        }),
+    )
+}
+
+fn setup_editor3d(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    edited: Res<EditedMaze3D>,
+) {
+    let mut unlit = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        })
+    };
+
+    commands.insert_resource(Editor3DAssets {
+        tile: meshes.add(shape::Plane { size: 0.95 }.into()),
+        wall: unlit(Color::hex("0000ff").unwrap()),
+        above: unlit(Color::rgba(1.0, 1.0, 1.0, 0.4)),
+        below: unlit(Color::rgba(0.0, 0.67, 1.0, 0.3)),
+        unreachable: unlit(Color::hex("ff0000").unwrap()),
+        start: unlit(Color::hex("00ff00").unwrap()),
+        goal: unlit(Color::hex("ffaa00").unwrap()),
+    });
+
+    let (transform, projection) = editor3d_camera(&edited.maze);
+
+    let root = commands
+        .spawn(TransformBundle::default())
+        .insert(VisibilityBundle::default())
+        .with_children(|parent| {
+            parent
+                .spawn(Camera3dBundle {
+                    transform,
+                    projection,
+                    ..default()
+                })
+                .insert(Editor3DCamera)
+                .insert(FilterCamera);
+
+            parent.spawn(DirectionalLightBundle {
+                transform: Transform::from_rotation(Quat::from_rotation_x(-PI / 3.0)),
+                ..default()
+            });
+        })
+        .id();
+
+    commands.insert_resource(Editor3DData {
+        root,
+        tool: Tool::Wall,
+        layer: edited.maze.start.2,
+        history: History::default(),
+    });
+}
+
+// see `editor::apply_tool`
+fn apply_tool(tool: Tool, (maze, markers): &mut Snapshot, (x, y, z): (usize, usize, usize)) {
+    let open = !maze.cells[z][y][x];
+    let occupied =
+        (x, y, z) == maze.start || (x, y, z) == maze.goal || markers.contains(&(x, y, z));
+
+    match tool {
+        Tool::Wall => {
+            let border = [x, y, z].contains(&0)
+                || z == maze.cells.len() - 1
+                || y == maze.cells[z].len() - 1
+                || x == maze.cells[z][y].len() - 1;
+
+            if !border && !occupied {
+                maze.cells[z][y][x] = open;
+            }
+        }
+        Tool::Start if open => maze.start = (x, y, z),
+        Tool::Goal if open => maze.goal = (x, y, z),
+        _ => (),
+    }
+}
+
+// left click uses the current tool on the cell under the cursor, in the layer being shown
+fn editor3d_click(
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Editor3DCamera>>,
+    mut edited: ResMut<EditedMaze3D>,
+    mut data: ResMut<Editor3DData>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let window = windows.get_primary().unwrap();
+
+    if let Some(mouse_position) = window.cursor_position() {
+        #[cfg(not(target_family = "wasm"))]
+        let mouse_position = mouse_position * window.scale_factor() as f32;
+
+        for (camera, camera_transform) in camera_query.iter() {
+            if let Some(ray) = camera.viewport_to_world(camera_transform, mouse_position) {
+                // inverse of `tile_translation`
+                let x = ray.origin.x.round();
+                let y = -ray.origin.z.round();
+
+                let layer = &edited.maze.cells[data.layer];
+
+                if x < 0.0 || y < 0.0 || y as usize >= layer.len() || x as usize >= layer[0].len() {
+                    continue;
+                }
+
+                let before = (edited.maze.clone(), edited.markers.clone());
+                let mut after = before.clone();
+
+                apply_tool(data.tool, &mut after, (x as usize, y as usize, data.layer));
+
+                if after != before {
+                    data.history.record(before);
+                    (edited.maze, edited.markers) = after;
+                }
+            }
+        }
+    }
+}
+
+// Number keys pick a tool, up and down step through layers, ctrl+z and ctrl+y undo and redo,
+// ctrl+s and ctrl+o save and load, return plays the maze and escape goes back to the menu.
+fn editor3d(
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut edited: ResMut<EditedMaze3D>,
+    mut data: ResMut<Editor3DData>,
+) {
+    let tools = [
+        (KeyCode::Key1, Tool::Wall),
+        (KeyCode::Key2, Tool::Start),
+        (KeyCode::Key3, Tool::Goal),
+    ];
+
+    for (key, tool) in tools {
+        if keys.just_pressed(key) {
+            info!("editor tool: {tool:?}");
+            data.tool = tool;
+        }
+    }
+
+    if keys.any_just_pressed([KeyCode::Up, KeyCode::PageUp])
+        && data.layer + 1 < edited.maze.cells.len()
+    {
+        data.layer += 1;
+        info!("editing layer {}", data.layer);
+    }
+
+    if keys.any_just_pressed([KeyCode::Down, KeyCode::PageDown]) && data.layer > 0 {
+        data.layer -= 1;
+        info!("editing layer {}", data.layer);
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu).unwrap();
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        if edited.maze.solve().is_some() {
+            edited.playtest = true;
+            state.set(AppState::Maze3D).unwrap();
+        } else {
+            warn!("the goal can't be reached from the start");
+        }
+    }
+
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+
+    let current = (edited.maze.clone(), edited.markers.clone());
+
+    if keys.just_pressed(KeyCode::Z) {
+        if let Some(previous) = data.history.undo(current) {
+            (edited.maze, edited.markers) = previous;
+        }
+    } else if keys.just_pressed(KeyCode::Y) {
+        if let Some(next) = data.history.redo(current) {
+            (edited.maze, edited.markers) = next;
+        }
+    } else if keys.just_pressed(KeyCode::S) {
+        save_maze_file(
+            &edited.path,
+            &MazeFile::from_maze3d(&edited.maze, &edited.markers),
+        );
+    } else if keys.just_pressed(KeyCode::O) {
+        let loaded = load_maze_file(&edited.path).and_then(|file| match file.to_maze3d() {
+            Ok(maze) => Some((maze, file.markers3d())),
+            Err(e) => {
+                error!("failed to load maze from {}: {e}", edited.path);
+                None
+            }
+        });
+
+        if let Some(loaded) = loaded {
+            load(&mut edited, &mut data, loaded);
+        }
+    }
+}
+
+// Swaps in a loaded maze so that loading can be undone, keeping the layer being shown inside it
+// in case it has fewer.
+fn load(edited: &mut EditedMaze3D, data: &mut Editor3DData, loaded: Snapshot) {
+    let (maze, markers) = loaded;
+    let current = (
+        std::mem::replace(&mut edited.maze, maze),
+        std::mem::replace(&mut edited.markers, markers),
+    );
+
+    data.history.record(current);
+    data.layer = data.layer.min(edited.maze.cells.len() - 1);
+}
+
+// logs whenever the goal goes from reachable to unreachable or back
+fn validate_maze3d(edited: Res<EditedMaze3D>, mut reachable: Local<Option<bool>>) {
+    if !edited.is_changed() {
+        return;
+    }
+
+    let solvable = edited.maze.solve().is_some();
+
+    if *reachable != Some(solvable) {
+        if solvable {
+            info!("the goal can be reached from the start");
+        } else {
+            warn!("the goal can't be reached from the start");
+        }
+
+        *reachable = Some(solvable);
+    }
+}
+
+// The layer being edited with its walls, start, goal and the cells that can't be reached from
+// the start. Walls in the layers above and below are ghosted over and under it.
+fn draw_layers(
+    mut commands: Commands,
+    edited: Res<EditedMaze3D>,
+    data: Res<Editor3DData>,
+    assets: Res<Editor3DAssets>,
+    tile_query: Query<Entity, With<Editor3DTile>>,
+    mut camera_query: Query<(&mut Transform, &mut Projection), With<Editor3DCamera>>,
+) {
+    if !edited.is_changed() && !data.is_changed() {
+        return;
+    }
+
+    for entity in tile_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        (*transform, *projection) = editor3d_camera(&edited.maze);
+    }
+
+    let maze = &edited.maze;
+    let z = data.layer;
+
+    let unreachable: HashSet<_> = maze.unreachable_regions().into_iter().flatten().collect();
+
+    let mut tiles = vec![];
+
+    for y in 0..maze.cells[z].len() {
+        for x in 0..maze.cells[z][y].len() {
+            let current = match (x, y, z) {
+                cell if cell == maze.start => Some(&assets.start),
+                cell if cell == maze.goal => Some(&assets.goal),
+                _ if maze.cells[z][y][x] => Some(&assets.wall),
+                cell if unreachable.contains(&cell) => Some(&assets.unreachable),
+                _ => None,
+            };
+
+            if let Some(material) = current {
+                tiles.push(((x, y), 0.0, 1.0, material));
+            }
+
+            if z > 0 && maze.cells[z - 1][y][x] {
+                tiles.push(((x, y), -0.5, 1.0, &assets.below));
+            }
+
+            if z + 1 < maze.cells.len() && maze.cells[z + 1][y][x] {
+                tiles.push(((x, y), 0.5, 0.3, &assets.above));
+            }
+        }
+    }
+
+    commands.entity(data.root).with_children(|parent| {
+        for (cell, height, scale, material) in tiles {
+            parent
+                .spawn(PbrBundle {
+                    mesh: assets.tile.clone(),
+                    material: material.clone(),
+                    transform: Transform {
+                        translation: tile_translation(cell) + Vec3::Y * height,
+                        scale: Vec3::splat(scale),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(Editor3DTile);
+        }
+    });
+}
+
+fn cleanup_editor3d(mut commands: Commands, data: Res<Editor3DData>) {
+    commands.entity(data.root).despawn_recursive();
+    commands.remove_resource::<Editor3DData>();
+    commands.remove_resource::<Editor3DAssets>();
+}
+
+pub struct Editor3DPlugin;
+impl Plugin for Editor3DPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditedMaze3D>()
+            .add_system_set(SystemSet::on_enter(AppState::Editor3D).with_system(setup_editor3d))
+            .add_system_set(
+                SystemSet::on_update(AppState::Editor3D)
+                    .with_system(editor3d)
+                    .with_system(editor3d_click)
+                    .with_system(validate_maze3d.after(editor3d).after(editor3d_click))
+                    .with_system(draw_layers.after(editor3d).after(editor3d_click)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Editor3D).with_system(cleanup_editor3d));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x2x2 maze starting at (1, 1, 1), every odd cell is open and every even one is a wall
+    fn snapshot() -> Snapshot {
+        let maze = Maze3D::hunt_and_kill(2, 2, 2, 3);
+        assert_eq!(maze.start, (1, 1, 1));
+
+        (maze, vec![])
+    }
+
+    fn applied(tool: Tool, snapshot: &Snapshot, cell: (usize, usize, usize)) -> Snapshot {
+        let mut after = snapshot.clone();
+        apply_tool(tool, &mut after, cell);
+
+        after
+    }
+
+    #[test]
+    fn walls_stay_off_the_border_and_anything_placed() {
+        let (mut maze, mut markers) = snapshot();
+        maze.goal = (3, 3, 3);
+        markers.push((3, 1, 3));
+        let snapshot = (maze, markers);
+
+        for cell in [
+            (0, 2, 2),
+            (2, 4, 2),
+            (2, 2, 0),
+            (1, 1, 1),
+            (3, 3, 3),
+            (3, 1, 3),
+        ] {
+            assert_eq!(applied(Tool::Wall, &snapshot, cell), snapshot);
+        }
+
+        let opened = applied(Tool::Wall, &snapshot, (2, 2, 2));
+        assert!(!opened.0.cells[2][2][2]);
+        assert!(applied(Tool::Wall, &opened, (2, 2, 2)).0.cells[2][2][2]);
+    }
+
+    #[test]
+    fn starts_and_goals_only_go_in_open_cells() {
+        let snapshot = snapshot();
+
+        assert_eq!(applied(Tool::Start, &snapshot, (2, 2, 2)), snapshot);
+        assert_eq!(applied(Tool::Goal, &snapshot, (2, 2, 2)), snapshot);
+
+        assert_eq!(
+            applied(Tool::Start, &snapshot, (1, 3, 3)).0.start,
+            (1, 3, 3)
+        );
+        assert_eq!(applied(Tool::Goal, &snapshot, (1, 3, 3)).0.goal, (1, 3, 3));
+    }
+
+    #[test]
+    fn loading_keeps_the_layer_inside_the_maze_and_can_be_undone() {
+        let (maze, _) = snapshot();
+        let mut edited = EditedMaze3D {
+            maze: Maze3D::hunt_and_kill(4, 4, 4, 3),
+            markers: vec![(1, 1, 7)],
+            playtest: false,
+            path: String::new(),
+        };
+        let mut data = Editor3DData {
+            root: Entity::from_raw(0),
+            tool: Tool::Wall,
+            layer: 7,
+            history: History::default(),
+        };
+        let before = (edited.maze.clone(), edited.markers.clone());
+
+        load(&mut edited, &mut data, (maze.clone(), vec![(3, 3, 1)]));
+        assert_eq!(data.layer, 4);
+        assert_eq!(
+            (&edited.maze, &edited.markers[..]),
+            (&maze, &[(3, 3, 1)][..])
+        );
+
+        let loaded = (edited.maze.clone(), edited.markers.clone());
+        assert_eq!(data.history.undo(loaded), Some(before));
+    }
+}
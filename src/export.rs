@@ -0,0 +1,338 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::geometry::MazeGeometry;
+use crate::maze2d::{self, Maze2D};
+use crate::maze3d::{self, Maze3D};
+use crate::maze_image::MazeImage;
+
+// pixels per cell of exported hint sheets
+pub const IMAGE_CELL_SIZE: u32 = 8;
+
+// world space geometry of a maze spawned at the origin
+pub fn maze2d_geometry(maze: &Maze2D) -> MazeGeometry {
+    maze.geometry()
+        .transformed(maze2d::MAZE_SCALE, maze2d::MAZE_OFFSET)
+}
+
+pub fn maze3d_geometry(maze: &Maze3D) -> MazeGeometry {
+    maze.geometry()
+        .transformed(maze3d::MAZE_SCALE, maze3d::MAZE_OFFSET)
+}
+
+pub fn to_obj(geometry: &MazeGeometry) -> String {
+    let mut obj = String::from("# mazemazemaze\no maze\n");
+
+    for [x, y, z] in geometry.positions.iter() {
+        writeln!(obj, "v {x} {y} {z}").unwrap();
+    }
+
+    for [x, y, z] in geometry.normals.iter() {
+        writeln!(obj, "vn {x} {y} {z}").unwrap();
+    }
+
+    // obj indices start at 1, and every vertex has its own normal
+    for triangle in geometry.indices.chunks(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}").unwrap();
+    }
+
+    obj
+}
+
+pub fn to_glb(geometry: &MazeGeometry) -> Vec<u8> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let mut buffer: Vec<u8> = vec![];
+    buffer.extend(
+        geometry
+            .positions
+            .iter()
+            .flatten()
+            .flat_map(|f| f.to_le_bytes()),
+    );
+    let normals_offset = buffer.len();
+    buffer.extend(
+        geometry
+            .normals
+            .iter()
+            .flatten()
+            .flat_map(|f| f.to_le_bytes()),
+    );
+    let indices_offset = buffer.len();
+    buffer.extend(geometry.indices.iter().flat_map(|i| i.to_le_bytes()));
+
+    // positions require bounds
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for position in geometry.positions.iter() {
+        for i in 0..3 {
+            min[i] = min[i].min(position[i]);
+            max[i] = max[i].max(position[i]);
+        }
+    }
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "mazemazemaze" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": "maze" }],
+        "meshes": [{
+            "name": "maze",
+            "primitives": [{
+                "attributes": { "POSITION": 0, "NORMAL": 1 },
+                "indices": 2,
+                "material": 0,
+            }],
+        }],
+        "materials": [{
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [0.0, 0.0, 1.0, 1.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.9,
+            },
+        }],
+        "buffers": [{ "byteLength": buffer.len() }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": normals_offset, "target": ARRAY_BUFFER },
+            {
+                "buffer": 0,
+                "byteOffset": normals_offset,
+                "byteLength": indices_offset - normals_offset,
+                "target": ARRAY_BUFFER,
+            },
+            {
+                "buffer": 0,
+                "byteOffset": indices_offset,
+                "byteLength": buffer.len() - indices_offset,
+                "target": ELEMENT_ARRAY_BUFFER,
+            },
+        ],
+        "accessors": [
+            {
+                "bufferView": 0,
+                "componentType": FLOAT,
+                "count": geometry.positions.len(),
+                "type": "VEC3",
+                "min": min,
+                "max": max,
+            },
+            {
+                "bufferView": 1,
+                "componentType": FLOAT,
+                "count": geometry.normals.len(),
+                "type": "VEC3",
+            },
+            {
+                "bufferView": 2,
+                "componentType": UNSIGNED_INT,
+                "count": geometry.indices.len(),
+                "type": "SCALAR",
+            },
+        ],
+    });
+
+    // chunks must be 4 byte aligned, json is padded with spaces and binary with zeroes
+    let mut json_chunk = document.to_string().into_bytes();
+    while !json_chunk.len().is_multiple_of(4) {
+        json_chunk.push(b' ');
+    }
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+
+    let total_length = 12 + 8 + json_chunk.len() + 8 + buffer.len();
+
+    let mut glb = Vec::with_capacity(total_length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+
+    glb.extend_from_slice(&(json_chunk.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json_chunk);
+
+    glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"BIN\0");
+    glb.extend_from_slice(&buffer);
+
+    glb
+}
+
+// picks the format from the extension of `path`
+pub fn write_geometry(geometry: &MazeGeometry, path: &Path) -> io::Result<()> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("obj") => std::fs::write(path, to_obj(geometry)),
+        Some("glb") => std::fs::write(path, to_glb(geometry)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a .obj or .glb file", path.display()),
+        )),
+    }
+}
+
+// hint sheet with the solution drawn in, picks the format from the extension of `path`
+pub fn write_image(maze: &Maze2D, path: &Path) -> io::Result<()> {
+    let solution = maze.solve().unwrap_or_default();
+    let image = MazeImage::new(maze).with_solution(&solution);
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => std::fs::write(path, image.to_svg(IMAGE_CELL_SIZE)),
+        Some("png") => std::fs::write(path, image.to_png(IMAGE_CELL_SIZE)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a .svg or .png file", path.display()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometries() -> [MazeGeometry; 2] {
+        [
+            maze2d_geometry(&Maze2D::hunt_and_kill(5, 4, 3)),
+            maze3d_geometry(&Maze3D::hunt_and_kill(3, 2, 4, 3)),
+        ]
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    // Every vertex is on the edge of a cell, as placed by `cell_translation`, and the walls
+    // reach exactly as far as the outermost cells do.
+    fn assert_walls_line_up_with_cells(
+        geometry: &MazeGeometry,
+        scale: f32,
+        first: [f32; 3],
+        last: [f32; 3],
+    ) {
+        for axis in 0..3 {
+            let min = first[axis] - scale / 2.0;
+            let max = last[axis] + scale / 2.0;
+
+            let along = geometry.positions.iter().map(|p| p[axis]);
+            assert_eq!(along.clone().fold(f32::MAX, f32::min), min);
+            assert_eq!(along.clone().fold(f32::MIN, f32::max), max);
+
+            for p in along {
+                let edges = (p - min) / scale;
+                assert_eq!(edges, edges.round(), "{p} isn't on the edge of a cell");
+            }
+        }
+    }
+
+    #[test]
+    fn vertices_land_on_the_edges_of_cells() {
+        let maze = Maze2D::hunt_and_kill(5, 4, 3);
+        let mut first = maze.cell_translation((0, 0));
+        let mut last = maze.cell_translation((10, 8));
+
+        // 2D walls are a single cell high, from the floor up
+        (first[1], last[1]) = (maze2d::MAZE_SCALE / 2.0, maze2d::MAZE_SCALE / 2.0);
+        assert_walls_line_up_with_cells(&maze2d_geometry(&maze), maze2d::MAZE_SCALE, first, last);
+
+        let maze = Maze3D::hunt_and_kill(3, 2, 4, 3);
+        let first = maze.cell_translation((0, 0, 0));
+        let last = maze.cell_translation((6, 4, 8));
+        assert_walls_line_up_with_cells(&maze3d_geometry(&maze), maze3d::MAZE_SCALE, first, last);
+    }
+
+    #[test]
+    fn transformed_faces_still_face_outwards() {
+        let geometry = maze3d_geometry(&Maze3D::hunt_and_kill(2, 2, 2, 3));
+
+        // a quarter turn around y, stretched along x, then moved
+        let turned = [
+            [0.0, 0.0, -2.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [3.0, 4.0, 5.0, 1.0],
+        ];
+        // turned and mirrored across x
+        let mut mirrored = turned;
+        mirrored[0][2] = 2.0;
+
+        for matrix in [turned, mirrored] {
+            let transformed = geometry.transformed_by(matrix);
+
+            let [x, y, z] = geometry.positions[0];
+            assert_eq!(
+                transformed.positions[0],
+                [z + 3.0, y + 4.0, matrix[0][2] * x + 5.0]
+            );
+
+            for triangle in transformed.indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| transformed.positions[triangle[i] as usize]);
+                let normal = transformed.normals[triangle[0] as usize];
+
+                let ab = [0, 1, 2].map(|i| b[i] - a[i]);
+                let ac = [0, 1, 2].map(|i| c[i] - a[i]);
+                let facing = [
+                    ab[1] * ac[2] - ab[2] * ac[1],
+                    ab[2] * ac[0] - ab[0] * ac[2],
+                    ab[0] * ac[1] - ab[1] * ac[0],
+                ];
+                let area = facing.iter().map(|f| f * f).sum::<f32>().sqrt();
+
+                let length = normal.iter().map(|n| n * n).sum::<f32>().sqrt();
+                assert!((length - 1.0).abs() < 1e-5);
+
+                let alignment = (0..3).map(|i| facing[i] * normal[i]).sum::<f32>() / area;
+                assert!(
+                    (alignment - 1.0).abs() < 1e-5,
+                    "{normal:?} doesn't face {facing:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn obj_has_every_vertex_and_face() {
+        for geometry in geometries() {
+            let obj = to_obj(&geometry);
+            let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+
+            assert_eq!(count("v "), geometry.positions.len());
+            assert_eq!(count("vn "), geometry.normals.len());
+            assert_eq!(count("f "), geometry.indices.len() / 3);
+        }
+    }
+
+    #[test]
+    fn glb_chunks_are_aligned_and_add_up() {
+        for geometry in geometries() {
+            let glb = to_glb(&geometry);
+
+            assert_eq!(&glb[0..4], b"glTF");
+            assert_eq!(u32_at(&glb, 4), 2);
+            assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+            let json_length = u32_at(&glb, 12) as usize;
+            assert_eq!(&glb[16..20], b"JSON");
+            assert_eq!(json_length % 4, 0);
+
+            let bin_start = 20 + json_length;
+            let bin_length = u32_at(&glb, bin_start) as usize;
+            assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+            assert_eq!(bin_length % 4, 0);
+            assert_eq!(bin_start + 8 + bin_length, glb.len());
+
+            let document: serde_json::Value = serde_json::from_slice(&glb[20..bin_start]).unwrap();
+            let accessors = &document["accessors"];
+
+            assert_eq!(accessors[0]["count"], geometry.positions.len());
+            assert_eq!(accessors[1]["count"], geometry.normals.len());
+            assert_eq!(accessors[2]["count"], geometry.indices.len());
+            assert!(document["buffers"][0]["byteLength"].as_u64().unwrap() as usize <= bin_length);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use mazemazemaze::export::{maze2d_geometry, maze3d_geometry, write_geometry, write_image};
+use mazemazemaze::maze2d::Maze2D;
+use mazemazemaze::maze3d::Maze3D;
+
+// F2 writes every maze in the world to the working directory, in world space, along with hint
+// sheets for 2D mazes. Files are named after the kind of maze, its generator and its seed.
+fn export_maze(
+    keys: Res<Input<KeyCode>>,
+    maze2d_query: Query<(&Maze2D, &GlobalTransform)>,
+    maze3d_query: Query<(&Maze3D, &GlobalTransform)>,
+) {
+    if !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    for (maze, _) in maze2d_query.iter() {
+        for extension in ["svg", "png"] {
+            let path = format!("maze-2d-{}-{}.{extension}", maze.generator, maze.seed);
+
+            match write_image(maze, Path::new(&path)) {
+                Ok(()) => info!("exported maze to {path}"),
+                Err(e) => error!("failed to export maze to {path}: {e}"),
+            }
+        }
+    }
+
+    let mazes = maze2d_query
+        .iter()
+        .map(|(maze, transform)| {
+            let name = format!("maze-2d-{}-{}", maze.generator, maze.seed);
+            (name, maze2d_geometry(maze), transform)
+        })
+        .chain(maze3d_query.iter().map(|(maze, transform)| {
+            let name = format!("maze-3d-{}-{}", maze.generator, maze.seed);
+            (name, maze3d_geometry(maze), transform)
+        }));
+
+    for (name, geometry, transform) in mazes {
+        // the same as the meshes, which are already scaled and offset around the entity
+        let geometry = geometry.transformed_by(transform.compute_matrix().to_cols_array_2d());
+
+        for extension in ["obj", "glb"] {
+            let path = format!("{name}.{extension}");
+
+            match write_geometry(&geometry, Path::new(&path)) {
+                Ok(()) => info!("exported maze to {path}"),
+                Err(e) => error!("failed to export maze to {path}: {e}"),
+            }
+        }
+    }
+}
+
+pub struct ExportPlugin;
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        // there is no filesystem to write to on the web
+        if cfg!(not(target_family = "wasm")) {
+            app.add_system(export_maze);
+        }
+    }
+}
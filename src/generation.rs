@@ -0,0 +1,252 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use mazemazemaze::direction::Bias;
+use mazemazemaze::hunt_and_kill::{GenerationStep, HuntAndKill2D};
+use mazemazemaze::maze2d::{Maze2D, MAZE_OFFSET, MAZE_SCALE};
+
+use crate::{
+    chunks::MazeChunks, filter::FilterCamera, maze2d_plugin::Maze2DBundle, state::AppState,
+    Settings,
+};
+
+// Grows the `Maze2D` on the same entity a few steps every frame, and is removed once the maze is
+// finished.
+#[derive(Component)]
+pub struct Generating2D {
+    generator: HuntAndKill2D,
+    pub steps_per_second: f32,
+
+    // fraction of a step left over from earlier frames
+    pending: f32,
+}
+
+impl Generating2D {
+    pub fn new(generator: HuntAndKill2D, steps_per_second: f32) -> Self {
+        Generating2D {
+            generator,
+            steps_per_second,
+            pending: 0.0,
+        }
+    }
+
+    // the maze to spawn alongside this
+    pub fn maze(&self) -> Maze2D {
+        self.generator.maze().clone()
+    }
+}
+
+// sent for every step a `Generating2D` takes
+pub struct GenerationStepEvent {
+    pub maze: Entity,
+    pub step: GenerationStep<(usize, usize)>,
+}
+
+// Follows the latest step of the `Generating2D` on its parent, full size while carving and half
+// size while hunting.
+#[derive(Component)]
+struct GenerationHead;
+
+#[derive(Resource)]
+struct GenerationData {
+    root: Entity,
+}
+
+fn step_generators(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut events: EventWriter<GenerationStepEvent>,
+    mut query: Query<(Entity, &mut Generating2D, &mut Maze2D, &mut MazeChunks)>,
+) {
+    for (entity, mut generating, mut maze, mut chunks) in query.iter_mut() {
+        generating.pending += time.delta_seconds() * generating.steps_per_second;
+
+        while generating.pending >= 1.0 {
+            generating.pending -= 1.0;
+
+            match generating.generator.next() {
+                Some(step) => {
+                    if let GenerationStep::Carve { cell, passage } = step {
+                        for (x, y) in passage.into_iter().chain([cell]) {
+                            chunks.dirty.push(vec![x..=x, y..=y]);
+                        }
+                    }
+
+                    events.send(GenerationStepEvent { maze: entity, step });
+                }
+                None => {
+                    commands.entity(entity).remove::<Generating2D>();
+                    break;
+                }
+            }
+        }
+
+        // only carving changes cells, and only the chunks around them get rebuilt
+        if !chunks.dirty.is_empty() {
+            maze.cells.clone_from(&generating.generator.maze().cells);
+        }
+    }
+}
+
+fn move_heads(
+    mut events: EventReader<GenerationStepEvent>,
+    generating_query: Query<(), With<Generating2D>>,
+    mut head_query: Query<(&Parent, &mut Transform, &mut Visibility), With<GenerationHead>>,
+) {
+    for event in events.iter() {
+        for (parent, mut transform, _) in head_query.iter_mut() {
+            if parent.get() != event.maze {
+                continue;
+            }
+
+            let ((x, y), scale) = match event.step {
+                GenerationStep::Carve { cell, .. } => (cell, 1.0),
+                GenerationStep::Scan(cell) => (cell, 0.5),
+            };
+
+            // same layout as the walls of `maze2d_geometry`
+            transform.translation = Vec3::from(MAZE_OFFSET)
+                + Vec3::new(x as f32 + 1.5, 1.5, y as f32 + 1.5) * MAZE_SCALE;
+            transform.scale = Vec3::splat(scale * MAZE_SCALE);
+        }
+    }
+
+    for (parent, _, mut visibility) in head_query.iter_mut() {
+        visibility.is_visible = generating_query.get(parent.get()).is_ok();
+    }
+}
+
+fn enter_generation(keys: Res<Input<KeyCode>>, mut state: ResMut<State<AppState>>) {
+    if keys.just_pressed(KeyCode::G) {
+        state.set(AppState::Generation).unwrap();
+    }
+}
+
+fn spawn_generating_maze(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    bias: Bias,
+) -> Entity {
+    let generating = Generating2D::new(HuntAndKill2D::new(10, 10, fastrand::u64(..), bias), 20.0);
+
+    commands
+        .spawn(Maze2DBundle {
+            maze: generating.maze(),
+            ..default()
+        })
+        .insert(generating)
+        .with_children(|parent| {
+            parent
+                .spawn(PbrBundle {
+                    mesh: meshes.add(shape::Cube::new(1.0).into()),
+                    material: materials.add(Color::hex("ffff00").unwrap().into()),
+                    visibility: Visibility::INVISIBLE,
+                    ..default()
+                })
+                .insert(GenerationHead);
+        })
+        .id()
+}
+
+fn setup_generation(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<Settings>,
+) {
+    // middle of a 10x10 maze in world space
+    let center = Vec3::new(
+        MAZE_OFFSET[0] + MAZE_SCALE * 11.5,
+        0.0,
+        MAZE_OFFSET[2] + MAZE_SCALE * 11.5,
+    );
+
+    let maze = spawn_generating_maze(&mut commands, &mut meshes, &mut materials, settings.bias);
+
+    let root = commands
+        .spawn(TransformBundle::default())
+        .insert(VisibilityBundle::default())
+        .with_children(|parent| {
+            parent
+                .spawn(Camera3dBundle {
+                    transform: Transform::from_translation(center + Vec3::Y * 140.0)
+                        .looking_at(center, Vec3::NEG_Z),
+                    ..default()
+                })
+                .insert(FilterCamera);
+
+            parent.spawn(DirectionalLightBundle {
+                transform: Transform::from_rotation(Quat::from_rotation_x(-PI / 3.0)),
+                ..default()
+            });
+        })
+        .add_child(maze)
+        .id();
+
+    commands.insert_resource(GenerationData { root });
+}
+
+// escape goes back to the menu and the arrow keys change the speed
+fn generation(
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut generating_query: Query<&mut Generating2D>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu).unwrap();
+    }
+
+    for mut generating in generating_query.iter_mut() {
+        if keys.just_pressed(KeyCode::Up) {
+            generating.steps_per_second *= 2.0;
+        }
+
+        if keys.just_pressed(KeyCode::Down) {
+            generating.steps_per_second /= 2.0;
+        }
+    }
+}
+
+// return throws the maze away and starts over
+fn restart_generation(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    generation_data: Res<GenerationData>,
+    settings: Res<Settings>,
+    maze_query: Query<Entity, With<Maze2D>>,
+) {
+    if keys.just_pressed(KeyCode::Return) {
+        for entity in maze_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        let maze = spawn_generating_maze(&mut commands, &mut meshes, &mut materials, settings.bias);
+        commands.entity(generation_data.root).add_child(maze);
+    }
+}
+
+fn cleanup_generation(mut commands: Commands, generation_data: Res<GenerationData>) {
+    commands.entity(generation_data.root).despawn_recursive();
+}
+
+pub struct GenerationPlugin;
+impl Plugin for GenerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerationStepEvent>()
+            .add_system(step_generators)
+            .add_system(move_heads.after(step_generators))
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(enter_generation))
+            .add_system_set(SystemSet::on_enter(AppState::Generation).with_system(setup_generation))
+            .add_system_set(
+                SystemSet::on_update(AppState::Generation)
+                    .with_system(generation)
+                    .with_system(restart_generation),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Generation).with_system(cleanup_generation),
+            );
+    }
+}
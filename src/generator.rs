@@ -0,0 +1,523 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use fastrand::Rng;
+use instant::Instant;
+
+use crate::analysis::{Difficulty, DifficultyBand, MazeStats};
+use crate::binary_tree;
+use crate::direction::Bias;
+use crate::dungeon::Dungeon;
+use crate::hunt_and_kill::{HuntAndKill2D, HuntAndKill3D};
+use crate::maze2d::Maze2D;
+use crate::maze3d::Maze3D;
+use crate::sidewinder;
+
+// seeds tried by `Generator::rated_maze2d` and `Generator::rated_maze3d`
+const RATING_ATTEMPTS: u64 = 16;
+
+// largest block of logical cells recarved at once when searching for a maze within a band
+const MUTATION_SIZE: usize = 4;
+
+// recarves in a row that don't get closer to the band before starting over with a new maze
+const MUTATION_PATIENCE: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Generator {
+    HuntAndKill,
+    Dungeon,
+
+    // no hunting or backtracking, so they stay fast for huge mazes but look more regular
+    BinaryTree,
+    Sidewinder,
+}
+
+impl Generator {
+    pub const ALL: [Generator; 4] = [
+        Generator::HuntAndKill,
+        Generator::Dungeon,
+        Generator::BinaryTree,
+        Generator::Sidewinder,
+    ];
+
+    // matches `Maze2D::generator` and `Maze3D::generator`
+    pub fn name(self) -> &'static str {
+        match self {
+            Generator::HuntAndKill => "hunt_and_kill",
+            Generator::Dungeon => "dungeon",
+            Generator::BinaryTree => "binary_tree",
+            Generator::Sidewinder => "sidewinder",
+        }
+    }
+
+    // the same seed and bias always give the same maze
+    pub fn maze2d(self, width: usize, height: usize, seed: u64, bias: Bias) -> Maze2D {
+        match self {
+            Generator::HuntAndKill => HuntAndKill2D::new(width, height, seed, bias).finish(),
+            Generator::Dungeon => Dungeon {
+                bias,
+                ..Default::default()
+            }
+            .generate(width, height, seed),
+            Generator::BinaryTree => binary_tree::maze2d(width, height, seed, bias),
+            Generator::Sidewinder => sidewinder::maze2d(width, height, seed, bias),
+        }
+    }
+
+    // dungeons only come in 2D, so 3D ones are plain hunt and kill mazes
+    pub fn maze3d(
+        self,
+        width: usize,
+        height: usize,
+        depth: usize,
+        seed: u64,
+        bias: Bias,
+    ) -> Maze3D {
+        match self {
+            Generator::HuntAndKill | Generator::Dungeon => {
+                HuntAndKill3D::new(width, height, depth, seed, bias).finish()
+            }
+            Generator::BinaryTree => binary_tree::maze3d(width, height, depth, seed, bias),
+            Generator::Sidewinder => sidewinder::maze3d(width, height, depth, seed, bias),
+        }
+    }
+
+    // generates a maze and then removes `braid` of its dead ends, see `Maze2D::braid`
+    pub fn braided_maze2d(
+        self,
+        width: usize,
+        height: usize,
+        seed: u64,
+        bias: Bias,
+        braid: f32,
+    ) -> Maze2D {
+        let mut maze = self.maze2d(width, height, seed, bias);
+        maze.braid(braid, &Rng::with_seed(seed));
+
+        maze
+    }
+
+    pub fn braided_maze3d(
+        self,
+        width: usize,
+        height: usize,
+        depth: usize,
+        seed: u64,
+        bias: Bias,
+        braid: f32,
+    ) -> Maze3D {
+        let mut maze = self.maze3d(width, height, depth, seed, bias);
+        maze.braid(braid, &Rng::with_seed(seed));
+
+        maze
+    }
+
+    // Tries seeds counting up from `seed` at the size `difficulty` suggests, and keeps the first
+    // maze that gets rated `difficulty`. Falls back to the closest one.
+    pub fn rated_maze2d(self, difficulty: Difficulty, seed: u64, bias: Bias) -> Maze2D {
+        let (width, height) = difficulty.maze2d_size();
+
+        closest_rated(difficulty, seed, |seed| {
+            let maze = self.maze2d(width, height, seed, bias);
+            (MazeStats::of_maze2d(&maze).score(), maze)
+        })
+    }
+
+    pub fn rated_maze3d(self, difficulty: Difficulty, seed: u64, bias: Bias) -> Maze3D {
+        let (width, height, depth) = difficulty.maze3d_size();
+
+        closest_rated(difficulty, seed, |seed| {
+            let maze = self.maze3d(width, height, depth, seed, bias);
+            (MazeStats::of_maze3d(&maze).score(), maze)
+        })
+    }
+
+    // Generates mazes and recarves parts of them until the statistics fall within `band`, giving
+    // up with the closest one found once `budget` runs out. Recarved mazes can't be regenerated
+    // from their seed, save them with `MazeFile` instead.
+    //
+    // The game doesn't search at runtime, a search can take its whole budget which would freeze
+    // a frame. Levels that need to be within a band are found ahead of time with `mazegen` and
+    // shipped as `.maze` files, like `maze2d_plugin::FIRST_MAZE`.
+    pub fn maze2d_in_band(
+        self,
+        width: usize,
+        height: usize,
+        band: &DifficultyBand,
+        seed: u64,
+        bias: Bias,
+        budget: Duration,
+    ) -> Result<Maze2D, OutOfBand<Maze2D>> {
+        search_band(
+            band,
+            seed,
+            budget,
+            |seed| self.maze2d(width, height, seed, bias),
+            |maze, rng| {
+                let size = (
+                    rng.usize(1..=MUTATION_SIZE.min(width)),
+                    rng.usize(1..=MUTATION_SIZE.min(height)),
+                );
+                let from = (rng.usize(..=width - size.0), rng.usize(..=height - size.1));
+
+                maze.recarve(from, size, rng);
+            },
+            MazeStats::of_maze2d,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn maze3d_in_band(
+        self,
+        width: usize,
+        height: usize,
+        depth: usize,
+        band: &DifficultyBand,
+        seed: u64,
+        bias: Bias,
+        budget: Duration,
+    ) -> Result<Maze3D, OutOfBand<Maze3D>> {
+        search_band(
+            band,
+            seed,
+            budget,
+            |seed| self.maze3d(width, height, depth, seed, bias),
+            |maze, rng| {
+                let size = (
+                    rng.usize(1..=MUTATION_SIZE.min(width)),
+                    rng.usize(1..=MUTATION_SIZE.min(height)),
+                    rng.usize(1..=MUTATION_SIZE.min(depth)),
+                );
+                let from = (
+                    rng.usize(..=width - size.0),
+                    rng.usize(..=height - size.1),
+                    rng.usize(..=depth - size.2),
+                );
+
+                maze.recarve(from, size, rng);
+            },
+            MazeStats::of_maze3d,
+        )
+    }
+}
+
+// returned when no maze within a band was found in time
+#[derive(Debug)]
+pub struct OutOfBand<M> {
+    pub closest: Box<M>,
+    pub stats: MazeStats,
+}
+
+impl<M> fmt::Display for OutOfBand<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ran out of time, the closest maze had a solution length of {} and {} dead ends",
+            self.stats.solution_length, self.stats.dead_ends
+        )
+    }
+}
+
+impl<M: fmt::Debug> std::error::Error for OutOfBand<M> {}
+
+// hill climbs towards `band` by recarving, starting over whenever it gets stuck
+fn search_band<M: Clone>(
+    band: &DifficultyBand,
+    seed: u64,
+    budget: Duration,
+    generate: impl Fn(u64) -> M,
+    mutate: impl Fn(&mut M, &Rng),
+    measure: impl Fn(&M) -> MazeStats,
+) -> Result<M, OutOfBand<M>> {
+    let deadline = Instant::now() + budget;
+    let rng = Rng::with_seed(seed);
+
+    let mut current = generate(seed);
+    let mut stats = measure(&current);
+    let mut closest = (current.clone(), stats);
+    let mut stuck = 0;
+
+    while !band.contains(&stats) {
+        if Instant::now() >= deadline {
+            return Err(OutOfBand {
+                closest: Box::new(closest.0),
+                stats: closest.1,
+            });
+        }
+
+        if stuck == MUTATION_PATIENCE {
+            current = generate(rng.u64(..));
+            stats = measure(&current);
+            stuck = 0;
+        } else {
+            let mut candidate = current.clone();
+            mutate(&mut candidate, &rng);
+            let candidate_stats = measure(&candidate);
+
+            let (before, after) = (band.distance(&stats), band.distance(&candidate_stats));
+            stuck = if after < before { 0 } else { stuck + 1 };
+
+            // sideways moves are fine, they help get off plateaus
+            if after <= before {
+                current = candidate;
+                stats = candidate_stats;
+            }
+        }
+
+        if band.distance(&stats) < band.distance(&closest.1) {
+            closest = (current.clone(), stats);
+        }
+    }
+
+    Ok(current)
+}
+
+fn closest_rated<M>(difficulty: Difficulty, seed: u64, generate: impl Fn(u64) -> (f32, M)) -> M {
+    let mut closest = generate(seed);
+
+    for attempt in 1..RATING_ATTEMPTS {
+        if difficulty.distance(closest.0) == 0.0 {
+            break;
+        }
+
+        let candidate = generate(seed.wrapping_add(attempt));
+        if difficulty.distance(candidate.0) < difficulty.distance(closest.0) {
+            closest = candidate;
+        }
+    }
+
+    closest.1
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Generator::ALL
+            .into_iter()
+            .find(|generator| generator.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Generator::ALL.iter().map(|g| g.name()).collect();
+                format!(
+                    "unknown generator {s}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a weight of 0 only gets picked when there's nothing else
+    const BIASES: [Bias; 4] = [
+        Bias::UNIFORM,
+        Bias::HORIZONTAL,
+        Bias::VERTICAL,
+        Bias {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        },
+    ];
+
+    // what seed 3 gave before there was a bias
+    const DUNGEON_SEED_3: [&str; 9] = [
+        "#############",
+        "#S#     #   #",
+        "# #     # ###",
+        "#           #",
+        "# #     #   #",
+        "# #     #   #",
+        "# #     #   #",
+        "# #     #  G#",
+        "#############",
+    ];
+
+    // what seed 5 gave when these generators were added
+    const BINARY_TREE_SEED_5: [&str; 9] = [
+        "#############",
+        "#S          #",
+        "# # ##### # #",
+        "# #     # # #",
+        "# ###########",
+        "#           #",
+        "# ### #######",
+        "#   #      G#",
+        "#############",
+    ];
+
+    #[rustfmt::skip]
+    const BINARY_TREE_SEED_5_3D: [&str; 29] = [
+        "#######", "#######", "#######", "#######", "#######", "",
+        "#######", "#S    #", "# #####", "#     #", "#######", "",
+        "#######", "# ### #", "#######", "# #####", "#######", "",
+        "#######", "#   # #", "### ###", "# #  G#", "#######", "",
+        "#######", "#######", "#######", "#######", "#######",
+    ];
+
+    const SIDEWINDER_SEED_5: [&str; 9] = [
+        "#############",
+        "#S          #",
+        "##### ##### #",
+        "#     #     #",
+        "# # # ### # #",
+        "# # #   # # #",
+        "# ####### # #",
+        "#   #     #G#",
+        "#############",
+    ];
+
+    #[rustfmt::skip]
+    const SIDEWINDER_SEED_5_3D: [&str; 29] = [
+        "#######", "#######", "#######", "#######", "#######", "",
+        "#######", "#S    #", "### # #", "#   # #", "#######", "",
+        "#######", "# # ###", "#######", "# ### #", "#######", "",
+        "#######", "# #   #", "### ###", "# # #G#", "#######", "",
+        "#######", "#######", "#######", "#######", "#######",
+    ];
+
+    fn lines(maze: impl ToString) -> Vec<String> {
+        maze.to_string().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn generators_make_perfect_mazes() {
+        for generator in Generator::ALL {
+            for bias in BIASES {
+                for seed in 0..6 {
+                    // rooms have loops in them, so dungeons are only connected
+                    let maze2d = generator.maze2d(9, 6, seed, bias);
+
+                    match generator {
+                        Generator::Dungeon => assert!(maze2d.unreachable_regions().is_empty()),
+                        _ => assert!(maze2d.is_perfect(), "{generator:?} {bias}\n{maze2d}"),
+                    }
+
+                    let maze3d = generator.maze3d(4, 3, 5, seed, bias);
+                    assert!(maze3d.is_perfect(), "{generator:?} {bias}\n{maze3d}");
+                }
+            }
+        }
+    }
+
+    // neither is what seed 0 gives, 72 steps with 8 dead ends and 18 steps with 9 dead ends
+    fn bands() -> [DifficultyBand; 2] {
+        [
+            DifficultyBand {
+                solution_length: 50..=60,
+                dead_ends: 4..=6,
+            },
+            DifficultyBand {
+                solution_length: 30..=40,
+                dead_ends: 4..=6,
+            },
+        ]
+    }
+
+    #[test]
+    fn reachable_bands_are_recarved_into() {
+        let [band2d, band3d] = bands();
+        let budget = Duration::from_secs(10);
+
+        for generator in [Generator::HuntAndKill, Generator::Sidewinder] {
+            let initial = MazeStats::of_maze2d(&generator.maze2d(8, 8, 0, Bias::UNIFORM));
+            assert!(!band2d.contains(&initial));
+
+            let maze = generator
+                .maze2d_in_band(8, 8, &band2d, 0, Bias::UNIFORM, budget)
+                .unwrap();
+            assert!(band2d.contains(&MazeStats::of_maze2d(&maze)));
+            assert!(maze.is_perfect(), "{maze}");
+
+            let initial = MazeStats::of_maze3d(&generator.maze3d(4, 4, 4, 0, Bias::UNIFORM));
+            assert!(!band3d.contains(&initial));
+
+            let maze = generator
+                .maze3d_in_band(4, 4, 4, &band3d, 0, Bias::UNIFORM, budget)
+                .unwrap();
+            assert!(band3d.contains(&MazeStats::of_maze3d(&maze)));
+            assert!(maze.is_perfect(), "{maze}");
+        }
+    }
+
+    #[test]
+    fn impossible_bands_give_back_the_closest_maze_found() {
+        // longer than a 5x5 maze could ever be
+        let band = DifficultyBand {
+            solution_length: 1000..=1000,
+            ..Default::default()
+        };
+
+        // without any time there's only the first maze
+        let Err(out_of_band) =
+            Generator::HuntAndKill.maze2d_in_band(5, 5, &band, 7, Bias::UNIFORM, Duration::ZERO)
+        else {
+            panic!("found a maze within an impossible band");
+        };
+        assert_eq!(
+            *out_of_band.closest,
+            Generator::HuntAndKill.maze2d(5, 5, 7, Bias::UNIFORM)
+        );
+
+        // every maze that gets measured is looked at, whether it's kept or not
+        let measured = std::cell::RefCell::new(vec![]);
+        let result = search_band(
+            &band,
+            7,
+            Duration::from_millis(50),
+            |seed| Maze2D::hunt_and_kill(5, 5, seed),
+            |maze, rng| {
+                maze.recarve((rng.usize(..=3), rng.usize(..=3)), (2, 2), rng);
+            },
+            |maze| {
+                let stats = MazeStats::of_maze2d(maze);
+                measured.borrow_mut().push(band.distance(&stats));
+                stats
+            },
+        );
+
+        let Err(out_of_band) = result else {
+            panic!("found a maze within an impossible band");
+        };
+        let closest = measured.into_inner().into_iter().fold(f32::MAX, f32::min);
+
+        assert_eq!(
+            MazeStats::of_maze2d(&out_of_band.closest),
+            out_of_band.stats
+        );
+        assert_eq!(band.distance(&out_of_band.stats), closest);
+        assert!(out_of_band.closest.is_perfect());
+    }
+
+    // hunt and kill has its own in `hunt_and_kill`
+    #[test]
+    fn uniform_bias_gives_the_same_mazes_as_before() {
+        let generate2d = |generator: Generator, seed| generator.maze2d(6, 4, seed, Bias::UNIFORM);
+        let generate3d =
+            |generator: Generator, seed| generator.maze3d(3, 2, 2, seed, Bias::UNIFORM);
+
+        assert_eq!(lines(generate2d(Generator::Dungeon, 3)), DUNGEON_SEED_3);
+        assert_eq!(lines(Maze2D::dungeon(6, 4, 3)), DUNGEON_SEED_3);
+
+        assert_eq!(
+            lines(generate2d(Generator::BinaryTree, 5)),
+            BINARY_TREE_SEED_5
+        );
+        assert_eq!(
+            lines(generate3d(Generator::BinaryTree, 5)),
+            BINARY_TREE_SEED_5_3D
+        );
+
+        assert_eq!(
+            lines(generate2d(Generator::Sidewinder, 5)),
+            SIDEWINDER_SEED_5
+        );
+        assert_eq!(
+            lines(generate3d(Generator::Sidewinder, 5)),
+            SIDEWINDER_SEED_5_3D
+        );
+    }
+}
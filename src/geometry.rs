@@ -0,0 +1,162 @@
+#[cfg(feature = "game")]
+use bevy::render::mesh::{Indices, Mesh, VertexAttributeValues};
+#[cfg(feature = "game")]
+use bevy::render::render_resource::PrimitiveTopology;
+use block_mesh::ndshape::RuntimeShape;
+use block_mesh::{
+    greedy_quads, GreedyQuadsBuffer, MergeVoxel, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
+};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct BoolVoxel(bool);
+
+pub const EMPTY: BoolVoxel = BoolVoxel(false);
+pub const FULL: BoolVoxel = BoolVoxel(true);
+
+impl Voxel for BoolVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        if *self == EMPTY {
+            VoxelVisibility::Empty
+        } else {
+            VoxelVisibility::Opaque
+        }
+    }
+}
+
+impl MergeVoxel for BoolVoxel {
+    type MergeValue = Self;
+
+    fn merge_value(&self) -> Self::MergeValue {
+        *self
+    }
+}
+
+// Triangle list of the walls of a maze, one unit per cell. Shared by the render mesh and the
+// exporters so they always agree.
+#[derive(Clone, Default, Debug)]
+pub struct MazeGeometry {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+impl MazeGeometry {
+    // greedy meshes the voxels in `shape` between the origin and `max` inclusive
+    pub fn from_voxels(voxels: &[BoolVoxel], shape: &RuntimeShape<u32, 3>, max: [u32; 3]) -> Self {
+        let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
+
+        let mut buffer = GreedyQuadsBuffer::new(voxels.len());
+        greedy_quads(voxels, shape, [0, 0, 0], max, &faces, &mut buffer);
+
+        let num_indices = buffer.quads.num_quads() * 6;
+        let num_vertices = buffer.quads.num_quads() * 4;
+        let mut indices = Vec::with_capacity(num_indices);
+        let mut positions = Vec::with_capacity(num_vertices);
+        let mut normals = Vec::with_capacity(num_vertices);
+        for (group, face) in buffer.quads.groups.into_iter().zip(faces) {
+            for quad in group.into_iter() {
+                indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
+                positions.extend_from_slice(&face.quad_mesh_positions(&quad, 1.0));
+                normals.extend_from_slice(&face.quad_mesh_normals());
+            }
+        }
+
+        MazeGeometry {
+            positions,
+            normals,
+            indices,
+        }
+    }
+
+    // scales and then offsets every vertex, e.g. to get world space geometry
+    pub fn transformed(&self, scale: f32, offset: [f32; 3]) -> MazeGeometry {
+        MazeGeometry {
+            positions: self
+                .positions
+                .iter()
+                .map(|p| [0, 1, 2].map(|i| p[i] * scale + offset[i]))
+                .collect(),
+            normals: self.normals.clone(),
+            indices: self.indices.clone(),
+        }
+    }
+
+    // Applies a column major affine matrix, like bevy's `Mat4::to_cols_array_2d`. Normals go
+    // through the inverse transpose so they stay square to their faces, and mirrored geometry
+    // has its triangles flipped so they still face outwards.
+    pub fn transformed_by(&self, matrix: [[f32; 4]; 4]) -> MazeGeometry {
+        // the bottom row of an affine matrix is always 0, 0, 0, 1
+        let [x, y, z, w] = matrix.map(|column| [column[0], column[1], column[2]]);
+
+        // the columns of the inverse transpose, scaled by the determinant
+        let cofactors = [cross(y, z), cross(z, x), cross(x, y)];
+        let determinant = dot(x, cofactors[0]);
+
+        let positions = self
+            .positions
+            .iter()
+            .map(|p| [0, 1, 2].map(|i| x[i] * p[0] + y[i] * p[1] + z[i] * p[2] + w[i]))
+            .collect();
+
+        let normals = self
+            .normals
+            .iter()
+            .map(|n| {
+                let normal = [0, 1, 2].map(|i| {
+                    (cofactors[0][i] * n[0] + cofactors[1][i] * n[1] + cofactors[2][i] * n[2])
+                        * determinant.signum()
+                });
+                let length = dot(normal, normal).sqrt();
+
+                normal.map(|n| n / length)
+            })
+            .collect();
+
+        let indices = if determinant < 0.0 {
+            self.indices
+                .chunks(3)
+                .flat_map(|triangle| [triangle[0], triangle[2], triangle[1]])
+                .collect()
+        } else {
+            self.indices.clone()
+        };
+
+        MazeGeometry {
+            positions,
+            normals,
+            indices,
+        }
+    }
+
+    #[cfg(feature = "game")]
+    pub fn to_mesh(&self) -> Mesh {
+        let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            VertexAttributeValues::Float32x3(self.positions.clone()),
+        );
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            VertexAttributeValues::Float32x3(self.normals.clone()),
+        );
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            VertexAttributeValues::Float32x2(vec![[0.0; 2]; self.positions.len()]),
+        );
+        render_mesh.set_indices(Some(Indices::U32(self.indices.clone())));
+
+        render_mesh
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
@@ -0,0 +1,383 @@
+use bevy::prelude::*;
+use fastrand::Rng;
+use mazemazemaze::maze3d::{Maze3D, MAZE_SCALE};
+
+use crate::controls::Action;
+use crate::physics::PhysicsPosition;
+use crate::player2d::{Player2D, PlayerCamera};
+use crate::state::AppState;
+
+// how quickly the player turns to a new up, per second
+const ALIGN_SPEED: f32 = 6.0;
+
+// roughly how many open cells there are for each tile
+const CELLS_PER_TILE: usize = 12;
+
+// what tiles can pull towards, in the order they're tried when one has to move
+const SIDES: [Vec3; 6] = [
+    Vec3::X,
+    Vec3::NEG_X,
+    Vec3::Y,
+    Vec3::NEG_Y,
+    Vec3::Z,
+    Vec3::NEG_Z,
+];
+
+// 3D mazes are walked through like 2D ones instead of flown through, with gravity that can pull
+// towards any of the six sides
+#[derive(Resource, Default)]
+pub struct GravityMode {
+    pub enabled: bool,
+}
+
+// Walking into this tile's cell makes gravity pull towards `down`, which is where the tile is
+// drawn.
+#[derive(Component)]
+pub struct GravityTile {
+    pub cell: (usize, usize, usize),
+    pub down: Vec3,
+}
+
+// The cell the player was in last frame, so standing on a tile only turns them once. It goes
+// away with the player, so every maze starts without one.
+#[derive(Component, Default)]
+struct LastCell(Option<(usize, usize, usize)>);
+
+impl LastCell {
+    // the cell the player has just walked into, none while they stay in the same one
+    fn enter(&mut self, cell: Option<(usize, usize, usize)>) -> Option<(usize, usize, usize)> {
+        if cell == self.0 {
+            return None;
+        }
+
+        self.0 = cell;
+        cell
+    }
+}
+
+// the axis closest to `direction`
+fn nearest_axis(direction: Vec3) -> Vec3 {
+    let abs = direction.abs();
+
+    if abs.x >= abs.y && abs.x >= abs.z {
+        Vec3::X * direction.x.signum()
+    } else if abs.y >= abs.z {
+        Vec3::Y * direction.y.signum()
+    } else {
+        Vec3::Z * direction.z.signum()
+    }
+}
+
+// whether the cell next to `cell` towards `direction`, which has to be an axis, is a wall
+fn wall_towards(maze: &Maze3D, (x, y, z): (usize, usize, usize), direction: Vec3) -> bool {
+    let step = |i: usize, d: f32| (i as isize + d as isize) as usize;
+
+    maze.cells[step(z, direction.z)][step(y, direction.y)][step(x, direction.x)]
+}
+
+// on the wall of `cell` towards `down`
+fn tile_transform(maze: &Maze3D, cell: (usize, usize, usize), down: Vec3) -> Transform {
+    let centre = Vec3::from(maze.cell_translation(cell));
+
+    Transform {
+        translation: centre + down * (MAZE_SCALE / 2.0 - 0.05),
+        rotation: Quat::from_rotation_arc(Vec3::NEG_Y, down),
+        ..default()
+    }
+}
+
+// A few open cells get a tile on one of their walls, the same ones every time for each seed. Gives
+// each tile's cell and the wall it's on.
+fn place_tiles(maze: &Maze3D) -> Vec<((usize, usize, usize), Vec3)> {
+    let rng = Rng::with_seed(maze.seed);
+    let tiles = maze.width * maze.height * maze.depth / CELLS_PER_TILE;
+    let mut placed = Vec::new();
+
+    for _ in 0..tiles {
+        let cell = (
+            rng.usize(..maze.width) * 2 + 1,
+            rng.usize(..maze.height) * 2 + 1,
+            rng.usize(..maze.depth) * 2 + 1,
+        );
+
+        if cell == maze.start || cell == maze.goal {
+            continue;
+        }
+
+        let walls: Vec<Vec3> = SIDES
+            .into_iter()
+            .filter(|&side| wall_towards(maze, cell, side))
+            .collect();
+
+        if walls.is_empty() {
+            continue;
+        }
+
+        placed.push((cell, walls[rng.usize(..walls.len())]));
+    }
+
+    placed
+}
+
+// The wall a tile in `cell` on `down` should be on after the maze shifts. It stays put while that's
+// still a wall, otherwise it moves to the first wall of its cell, or there's nowhere left for it.
+fn tile_wall(maze: &Maze3D, cell: (usize, usize, usize), down: Vec3) -> Option<Vec3> {
+    if wall_towards(maze, cell, down) {
+        return Some(down);
+    }
+
+    SIDES
+        .into_iter()
+        .find(|&side| wall_towards(maze, cell, side))
+}
+
+pub fn toggle_gravity_mode(actions: Res<Input<Action>>, mut gravity: ResMut<GravityMode>) {
+    if actions.just_pressed(Action::ToggleGravity) {
+        gravity.enabled = !gravity.enabled;
+    }
+}
+
+fn spawn_tiles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    gravity: Res<GravityMode>,
+    maze_query: Query<&Maze3D, Added<Maze3D>>,
+) {
+    if !gravity.enabled {
+        return;
+    }
+
+    let mesh = meshes.add(shape::Box::new(MAZE_SCALE * 0.6, 0.1, MAZE_SCALE * 0.6).into());
+    let material = materials.add(StandardMaterial {
+        base_color: Color::hex("00ffaa").unwrap(),
+        emissive: Color::hex("00ffaa").unwrap(),
+        ..default()
+    });
+
+    for maze in maze_query.iter() {
+        for (cell, down) in place_tiles(maze) {
+            commands
+                .spawn(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: tile_transform(maze, cell, down),
+                    ..default()
+                })
+                .insert(GravityTile { cell, down });
+        }
+    }
+}
+
+// Shifting mazes can knock out the wall a tile is on, those tiles move to another wall of their
+// cell or go away if there isn't one.
+fn move_tiles(
+    mut commands: Commands,
+    maze_query: Query<&Maze3D, Changed<Maze3D>>,
+    mut tile_query: Query<(Entity, &mut GravityTile, &mut Transform)>,
+) {
+    for maze in maze_query.iter() {
+        for (entity, mut tile, mut transform) in tile_query.iter_mut() {
+            match tile_wall(maze, tile.cell, tile.down) {
+                Some(down) if down == tile.down => {}
+                Some(down) => {
+                    tile.down = down;
+                    *transform = tile_transform(maze, tile.cell, down);
+                }
+                None => commands.entity(entity).despawn_recursive(),
+            }
+        }
+    }
+}
+
+fn cleanup_tiles(mut commands: Commands, query: Query<Entity, With<GravityTile>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn track_players(mut commands: Commands, query: Query<Entity, Added<Player2D>>) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(LastCell::default());
+    }
+}
+
+// see `portal::use_portals`
+fn use_tiles(
+    maze_query: Query<&Maze3D>,
+    tile_query: Query<&GravityTile>,
+    mut player_query: Query<(&PhysicsPosition, &mut Player2D, &mut LastCell)>,
+) {
+    for maze in maze_query.iter() {
+        for (position, mut player, mut last_cell) in player_query.iter_mut() {
+            let Some(cell) = last_cell.enter(maze.cell_at(position.current.into())) else {
+                continue;
+            };
+
+            for tile in tile_query.iter() {
+                if tile.cell == cell {
+                    player.set_up(-tile.down);
+                }
+            }
+        }
+    }
+}
+
+// gravity pulls towards whichever side the player is looking at most
+fn flip_gravity(
+    actions: Res<Input<Action>>,
+    mut player_query: Query<&mut Player2D>,
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    if !actions.just_pressed(Action::FlipGravity) {
+        return;
+    }
+
+    for mut player in player_query.iter_mut() {
+        for transform in camera_query.iter() {
+            let down = nearest_axis(transform.forward());
+
+            player.set_up(-down);
+            info!("gravity: {down}");
+        }
+    }
+}
+
+// turns the player a bit of the way to their up each frame, the camera goes along with them
+fn align_to_gravity(time: Res<Time>, mut query: Query<(&mut Transform, &Player2D)>) {
+    let amount = (ALIGN_SPEED * time.delta_seconds()).min(1.0);
+
+    for (mut transform, player) in query.iter_mut() {
+        let arc = Quat::from_rotation_arc(transform.up(), player.up());
+
+        transform.rotation = Quat::IDENTITY.slerp(arc, amount) * transform.rotation;
+    }
+}
+
+pub struct GravityPlugin;
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GravityMode>()
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu).with_system(toggle_gravity_mode),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Maze3D).with_system(cleanup_tiles))
+            .add_system_set(
+                SystemSet::on_update(AppState::Maze3D)
+                    .with_system(spawn_tiles)
+                    .with_system(move_tiles)
+                    .with_system(track_players)
+                    .with_system(use_tiles)
+                    .with_system(flip_gravity)
+                    .with_system(align_to_gravity),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mazemazemaze::direction::Bias;
+
+    use super::*;
+
+    #[test]
+    fn nearest_axis_picks_the_biggest_component() {
+        assert_eq!(nearest_axis(Vec3::new(0.2, -0.9, 0.1)), Vec3::NEG_Y);
+        assert_eq!(nearest_axis(Vec3::new(-0.3, 0.1, -0.8)), Vec3::NEG_Z);
+        assert_eq!(nearest_axis(Vec3::new(-1.0, 0.0, 0.0)), Vec3::NEG_X);
+
+        // ties go to x, then y
+        assert_eq!(nearest_axis(Vec3::new(0.5, 0.5, 0.5)), Vec3::X);
+        assert_eq!(nearest_axis(Vec3::new(0.1, -0.5, 0.5)), Vec3::NEG_Y);
+    }
+
+    // two logical cells along x with a passage between them
+    fn corridor() -> Maze3D {
+        let mut cells = vec![vec![vec![true; 5]; 3]; 3];
+        cells[1][1][1..=3].fill(false);
+
+        Maze3D {
+            cells,
+            width: 2,
+            height: 1,
+            depth: 1,
+            seed: 0,
+            generator: "test".to_string(),
+            bias: Bias::UNIFORM,
+            start: (1, 1, 1),
+            goal: (3, 1, 1),
+        }
+    }
+
+    #[test]
+    fn wall_towards_looks_at_the_neighbouring_cell() {
+        let maze = corridor();
+
+        let walls = |cell| {
+            SIDES
+                .into_iter()
+                .filter(|&side| wall_towards(&maze, cell, side))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            walls((1, 1, 1)),
+            [Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z]
+        );
+        assert_eq!(
+            walls((3, 1, 1)),
+            [Vec3::X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z]
+        );
+    }
+
+    #[test]
+    fn tiles_go_on_walls_of_open_cells() {
+        let maze = Maze3D::hunt_and_kill(6, 6, 6, 7);
+        let tiles = place_tiles(&maze);
+
+        assert!(!tiles.is_empty());
+        assert!(tiles.len() <= 6 * 6 * 6 / CELLS_PER_TILE);
+        assert_eq!(place_tiles(&maze), tiles);
+
+        for (cell @ (x, y, z), down) in tiles {
+            assert!(x % 2 == 1 && y % 2 == 1 && z % 2 == 1, "{cell:?}");
+            assert!(cell != maze.start && cell != maze.goal, "{cell:?}");
+            assert!(SIDES.contains(&down));
+            assert!(wall_towards(&maze, cell, down), "{cell:?} {down}");
+        }
+    }
+
+    #[test]
+    fn tiles_move_off_knocked_out_walls() {
+        let mut maze = corridor();
+
+        // still on a wall
+        assert_eq!(tile_wall(&maze, (1, 1, 1), Vec3::NEG_Y), Some(Vec3::NEG_Y));
+
+        // the passage along x was never a wall, the first side that is gets picked instead
+        assert_eq!(tile_wall(&maze, (1, 1, 1), Vec3::X), Some(Vec3::NEG_X));
+        assert_eq!(tile_wall(&maze, (3, 1, 1), Vec3::NEG_X), Some(Vec3::X));
+
+        // with every wall knocked out there's nowhere left
+        for (x, y, z) in [(0, 1, 1), (1, 0, 1), (1, 2, 1), (1, 1, 0), (1, 1, 2)] {
+            maze.cells[z][y][x] = false;
+        }
+        assert_eq!(tile_wall(&maze, (1, 1, 1), Vec3::NEG_Y), None);
+    }
+
+    #[test]
+    fn tiles_only_turn_players_as_they_walk_in() {
+        let mut last_cell = LastCell::default();
+
+        assert_eq!(last_cell.enter(Some((1, 1, 1))), Some((1, 1, 1)));
+        assert_eq!(last_cell.enter(Some((1, 1, 1))), None);
+
+        assert_eq!(last_cell.enter(Some((3, 1, 1))), Some((3, 1, 1)));
+        assert_eq!(last_cell.enter(Some((1, 1, 1))), Some((1, 1, 1)));
+
+        // leaving the maze and coming back counts as walking in again
+        assert_eq!(last_cell.enter(None), None);
+        assert_eq!(last_cell.enter(None), None);
+        assert_eq!(last_cell.enter(Some((1, 1, 1))), Some((1, 1, 1)));
+    }
+}
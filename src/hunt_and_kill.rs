@@ -0,0 +1,392 @@
+use fastrand::Rng;
+
+use crate::direction::{Bias, Direction2D, Direction3D};
+use crate::maze2d::Maze2D;
+use crate::maze3d::Maze3D;
+
+// something a generator did to the maze, in cell coordinates
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GenerationStep<C> {
+    // `cell` was opened, along with the passage from the cell it was reached from
+    Carve { cell: C, passage: Option<C> },
+
+    // the hunt phase looked at `cell` for somewhere to carve from next
+    Scan(C),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Phase<C> {
+    Start,
+    Walk(C),
+    Hunt,
+    Done,
+}
+
+// Hunt and kill one step at a time, `Maze2D::hunt_and_kill` just runs this to the end. The
+// random state is kept as a seed so this can live in a component.
+#[derive(Clone, Debug)]
+pub struct HuntAndKill2D {
+    maze: Maze2D,
+    rng: u64,
+    phase: Phase<(usize, usize)>,
+
+    // where the hunt picks up again, it only ever passes over the maze once
+    scan: (usize, usize),
+}
+
+impl HuntAndKill2D {
+    pub fn new(width: usize, height: usize, seed: u64, bias: Bias) -> Self {
+        HuntAndKill2D {
+            maze: Maze2D {
+                cells: vec![vec![true; width * 2 + 1]; height * 2 + 1],
+                width,
+                height,
+                seed,
+                generator: "hunt_and_kill".to_string(),
+                bias,
+                start: (1, 1),
+                goal: (width * 2 - 1, height * 2 - 1),
+                portals: vec![],
+            },
+            rng: seed,
+            phase: Phase::Start,
+            scan: (1, 1),
+        }
+    }
+
+    // the maze as far as it has been generated
+    pub fn maze(&self) -> &Maze2D {
+        &self.maze
+    }
+
+    pub fn finish(mut self) -> Maze2D {
+        for _ in self.by_ref() {}
+
+        self.maze
+    }
+
+    // the passage and the cell two steps away in `direction`, if they're inside the outer walls
+    fn towards(
+        &self,
+        (x, y): (usize, usize),
+        direction: Direction2D,
+    ) -> Option<[(usize, usize); 2]> {
+        match direction {
+            Direction2D::Up if y > 1 => Some([(x, y - 1), (x, y - 2)]),
+            Direction2D::Down if y < self.maze.height * 2 - 1 => Some([(x, y + 1), (x, y + 2)]),
+            Direction2D::Left if x > 1 => Some([(x - 1, y), (x - 2, y)]),
+            Direction2D::Right if x < self.maze.width * 2 - 1 => Some([(x + 1, y), (x + 2, y)]),
+            _ => None,
+        }
+    }
+
+    fn carve(
+        &mut self,
+        cell: (usize, usize),
+        passage: Option<(usize, usize)>,
+    ) -> GenerationStep<(usize, usize)> {
+        for (x, y) in passage.into_iter().chain([cell]) {
+            self.maze.cells[y][x] = false;
+        }
+
+        self.phase = Phase::Walk(cell);
+
+        GenerationStep::Carve { cell, passage }
+    }
+
+    fn step(&mut self, rng: &Rng) -> Option<GenerationStep<(usize, usize)>> {
+        loop {
+            match self.phase {
+                Phase::Start => return Some(self.carve((1, 1), None)),
+
+                // carve towards a random unvisited cell until there are none
+                Phase::Walk(cell) => {
+                    let next = Direction2D::biased_order(rng, &self.maze.bias)
+                        .into_iter()
+                        .filter_map(|direction| self.towards(cell, direction))
+                        .find(|&[_, (x, y)]| self.maze.cells[y][x]);
+
+                    match next {
+                        Some([passage, cell]) => return Some(self.carve(cell, Some(passage))),
+                        None => self.phase = Phase::Hunt,
+                    }
+                }
+
+                // scan for an unvisited cell next to a visited one, and walk from there
+                Phase::Hunt => {
+                    let (x, y) = self.scan;
+
+                    if y >= self.maze.height * 2 {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+
+                    self.scan = if x + 2 < self.maze.width * 2 {
+                        (x + 2, y)
+                    } else {
+                        (1, y + 2)
+                    };
+
+                    if !self.maze.cells[y][x] {
+                        return Some(GenerationStep::Scan((x, y)));
+                    }
+
+                    let passage = Direction2D::biased_order(rng, &self.maze.bias)
+                        .into_iter()
+                        .filter_map(|direction| self.towards((x, y), direction))
+                        .find(|&[_, (x, y)]| !self.maze.cells[y][x])
+                        .map(|[passage, _]| passage);
+
+                    return Some(self.carve((x, y), passage));
+                }
+
+                Phase::Done => return None,
+            }
+        }
+    }
+}
+
+impl Iterator for HuntAndKill2D {
+    type Item = GenerationStep<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rng = Rng::with_seed(self.rng);
+        let step = self.step(&rng);
+        self.rng = rng.get_seed();
+
+        step
+    }
+}
+
+// see `HuntAndKill2D`
+#[derive(Clone, Debug)]
+pub struct HuntAndKill3D {
+    maze: Maze3D,
+    rng: u64,
+    phase: Phase<(usize, usize, usize)>,
+    scan: (usize, usize, usize),
+}
+
+impl HuntAndKill3D {
+    pub fn new(width: usize, height: usize, depth: usize, seed: u64, bias: Bias) -> Self {
+        HuntAndKill3D {
+            maze: Maze3D {
+                cells: vec![vec![vec![true; width * 2 + 1]; height * 2 + 1]; depth * 2 + 1],
+                width,
+                height,
+                depth,
+                seed,
+                generator: "hunt_and_kill".to_string(),
+                bias,
+                start: (1, 1, 1),
+                goal: (width * 2 - 1, height * 2 - 1, depth * 2 - 1),
+            },
+            rng: seed,
+            phase: Phase::Start,
+            scan: (1, 1, 1),
+        }
+    }
+
+    pub fn maze(&self) -> &Maze3D {
+        &self.maze
+    }
+
+    pub fn finish(mut self) -> Maze3D {
+        for _ in self.by_ref() {}
+
+        self.maze
+    }
+
+    fn towards(
+        &self,
+        (x, y, z): (usize, usize, usize),
+        direction: Direction3D,
+    ) -> Option<[(usize, usize, usize); 2]> {
+        match direction {
+            Direction3D::Up if y > 1 => Some([(x, y - 1, z), (x, y - 2, z)]),
+            Direction3D::Down if y < self.maze.height * 2 - 1 => {
+                Some([(x, y + 1, z), (x, y + 2, z)])
+            }
+            Direction3D::Left if x > 1 => Some([(x - 1, y, z), (x - 2, y, z)]),
+            Direction3D::Right if x < self.maze.width * 2 - 1 => {
+                Some([(x + 1, y, z), (x + 2, y, z)])
+            }
+            Direction3D::In if z > 1 => Some([(x, y, z - 1), (x, y, z - 2)]),
+            Direction3D::Out if z < self.maze.depth * 2 - 1 => Some([(x, y, z + 1), (x, y, z + 2)]),
+            _ => None,
+        }
+    }
+
+    fn carve(
+        &mut self,
+        cell: (usize, usize, usize),
+        passage: Option<(usize, usize, usize)>,
+    ) -> GenerationStep<(usize, usize, usize)> {
+        for (x, y, z) in passage.into_iter().chain([cell]) {
+            self.maze.cells[z][y][x] = false;
+        }
+
+        self.phase = Phase::Walk(cell);
+
+        GenerationStep::Carve { cell, passage }
+    }
+
+    fn step(&mut self, rng: &Rng) -> Option<GenerationStep<(usize, usize, usize)>> {
+        loop {
+            match self.phase {
+                Phase::Start => return Some(self.carve((1, 1, 1), None)),
+
+                Phase::Walk(cell) => {
+                    let next = Direction3D::biased_order(rng, &self.maze.bias)
+                        .into_iter()
+                        .filter_map(|direction| self.towards(cell, direction))
+                        .find(|&[_, (x, y, z)]| self.maze.cells[z][y][x]);
+
+                    match next {
+                        Some([passage, cell]) => return Some(self.carve(cell, Some(passage))),
+                        None => self.phase = Phase::Hunt,
+                    }
+                }
+
+                Phase::Hunt => {
+                    let (x, y, z) = self.scan;
+
+                    if z >= self.maze.depth * 2 {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+
+                    self.scan = if x + 2 < self.maze.width * 2 {
+                        (x + 2, y, z)
+                    } else if y + 2 < self.maze.height * 2 {
+                        (1, y + 2, z)
+                    } else {
+                        (1, 1, z + 2)
+                    };
+
+                    if !self.maze.cells[z][y][x] {
+                        return Some(GenerationStep::Scan((x, y, z)));
+                    }
+
+                    let passage = Direction3D::biased_order(rng, &self.maze.bias)
+                        .into_iter()
+                        .filter_map(|direction| self.towards((x, y, z), direction))
+                        .find(|&[_, (x, y, z)]| !self.maze.cells[z][y][x])
+                        .map(|[passage, _]| passage);
+
+                    return Some(self.carve((x, y, z), passage));
+                }
+
+                Phase::Done => return None,
+            }
+        }
+    }
+}
+
+impl Iterator for HuntAndKill3D {
+    type Item = GenerationStep<(usize, usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rng = Rng::with_seed(self.rng);
+        let step = self.step(&rng);
+        self.rng = rng.get_seed();
+
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::Generator;
+
+    // what these seeds gave before generation could be stepped through, saved seeds have to keep
+    // giving the same mazes
+    const SEED_1: [&str; 9] = [
+        "#############",
+        "#S    #     #",
+        "### # ##### #",
+        "#   #   #   #",
+        "# ##### ### #",
+        "#   #     # #",
+        "### # ### # #",
+        "#   #   #  G#",
+        "#############",
+    ];
+
+    const SEED_42: [&str; 9] = [
+        "#############",
+        "#S#     #   #",
+        "# # ##### # #",
+        "# # #     # #",
+        "# # # ##### #",
+        "# #   #   # #",
+        "# ##### # # #",
+        "#       #  G#",
+        "#############",
+    ];
+
+    #[rustfmt::skip]
+    const SEED_7_3D: [&str; 29] = [
+        "#######", "#######", "#######", "#######", "#######", "",
+        "#######", "#S#   #", "# #####", "#     #", "#######", "",
+        "#######", "##### #", "#######", "##### #", "#######", "",
+        "#######", "#     #", "# # ###", "# #  G#", "#######", "",
+        "#######", "#######", "#######", "#######", "#######",
+    ];
+
+    #[test]
+    fn seeds_give_the_same_mazes_as_before() {
+        for (seed, expected) in [(1, SEED_1), (42, SEED_42)] {
+            let maze = HuntAndKill2D::new(6, 4, seed, Bias::UNIFORM).finish();
+            assert_eq!(maze.to_string().lines().collect::<Vec<_>>(), expected);
+        }
+
+        let maze = HuntAndKill3D::new(3, 2, 2, 7, Bias::UNIFORM).finish();
+        assert_eq!(maze.to_string().lines().collect::<Vec<_>>(), SEED_7_3D);
+    }
+
+    #[test]
+    fn stepping_to_the_end_matches_generating_at_once() {
+        for seed in 0..8 {
+            for bias in Bias::PRESETS {
+                let mut generator = HuntAndKill2D::new(7, 5, seed, bias);
+                let mut carved = 0;
+
+                for step in generator.by_ref() {
+                    if let GenerationStep::Carve { .. } = step {
+                        carved += 1;
+                    }
+                }
+
+                assert_eq!(carved, 7 * 5);
+                assert_eq!(
+                    generator.maze(),
+                    &Generator::HuntAndKill.maze2d(7, 5, seed, bias)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stepping_to_the_end_matches_generating_at_once_in_3d() {
+        for seed in 0..8 {
+            for bias in Bias::PRESETS {
+                let mut generator = HuntAndKill3D::new(4, 3, 3, seed, bias);
+                let mut carved = 0;
+
+                for step in generator.by_ref() {
+                    if let GenerationStep::Carve { .. } = step {
+                        carved += 1;
+                    }
+                }
+
+                assert_eq!(carved, 4 * 3 * 3);
+                assert_eq!(
+                    generator.maze(),
+                    &Generator::HuntAndKill.maze3d(4, 3, 3, seed, bias)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// Maze generation, solving and file formats. Nothing in here needs a window, so it is shared by
+// the game and the `mazegen` command line tool.
+pub mod analysis;
+pub mod binary_tree;
+pub mod direction;
+pub mod dungeon;
+pub mod export;
+pub mod generator;
+pub mod geometry;
+pub mod hunt_and_kill;
+pub mod maze2d;
+pub mod maze3d;
+pub mod maze_file;
+pub mod maze_image;
+pub mod sidewinder;
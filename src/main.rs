@@ -1,34 +1,74 @@
 use bevy::{prelude::*, window::CursorGrabMode};
 use bevy_embedded_assets::EmbeddedAssetPlugin;
 use bevy_rapier3d::prelude::*;
+use mazemazemaze::direction::Bias;
+use mazemazemaze::generator::Generator;
 
+use controls::ControlsPlugin;
+use editor::EditorPlugin;
+use editor3d::Editor3DPlugin;
+use export_plugin::ExportPlugin;
 use filter::FilterPlugin;
+use generation::GenerationPlugin;
+use gravity::GravityPlugin;
 use marker::MarkerPlugin;
-use maze2d::Maze2DPlugin;
-use maze3d::Maze3DPlugin;
+use maze2d_plugin::Maze2DPlugin;
+use maze3d_plugin::Maze3DPlugin;
+use maze_loader::MazeFilePlugin;
 use menu::MenuPlugin;
+use physics::PhysicsPlugin;
 use player2d::Player2DPlugin;
 use player3d::Player3DPlugin;
+use portal::PortalPlugin;
+use shifting::ShiftingPlugin;
 use state::AppState;
+use touch::{TouchControls, TouchPlugin};
+use win::WinPlugin;
 
-mod direction;
+mod chunks;
+mod controls;
+mod editor;
+mod editor3d;
+mod export_plugin;
 mod filter;
+mod generation;
+mod gravity;
 mod marker;
-mod maze2d;
-mod maze3d;
+mod maze2d_plugin;
+mod maze3d_plugin;
+mod maze_loader;
 mod menu;
+mod physics;
 mod player2d;
 mod player3d;
+mod portal;
+mod shifting;
 mod state;
+mod touch;
+mod win;
 
 #[derive(Resource)]
 pub struct Settings {
     sensitivity: f32,
+
+    // what new mazes are generated with, 3D mazes fall back to hunt and kill for dungeons
+    pub generator: Generator,
+
+    // which way corridors tend to run, see `Bias`
+    pub bias: Bias,
+
+    // the file the editors save to and load from, given as the first command line argument
+    pub maze_file: Option<String>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        Settings { sensitivity: 0.08 }
+        Settings {
+            sensitivity: 0.08,
+            generator: Generator::HuntAndKill,
+            bias: Bias::UNIFORM,
+            maze_file: std::env::args().nth(1),
+        }
     }
 }
 
@@ -44,12 +84,24 @@ fn main() {
         )
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         .add_plugin(MenuPlugin)
+        .add_plugin(ControlsPlugin)
+        .add_plugin(TouchPlugin)
+        .add_plugin(PhysicsPlugin)
         .add_plugin(Player2DPlugin)
         .add_plugin(Player3DPlugin)
+        .add_plugin(GravityPlugin)
         .add_plugin(Maze2DPlugin)
         .add_plugin(Maze3DPlugin)
         .add_plugin(FilterPlugin)
         .add_plugin(MarkerPlugin)
+        .add_plugin(MazeFilePlugin)
+        .add_plugin(ExportPlugin)
+        .add_plugin(WinPlugin)
+        .add_plugin(GenerationPlugin)
+        .add_plugin(ShiftingPlugin)
+        .add_plugin(PortalPlugin)
+        .add_plugin(EditorPlugin)
+        .add_plugin(Editor3DPlugin)
         .add_startup_system(play_music)
         .add_system(animate_spin)
         .add_system(cursor_grab)
@@ -59,10 +111,26 @@ fn main() {
 fn cursor_grab(
     keys: Res<Input<KeyCode>>,
     buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    touch: Res<TouchControls>,
     mut windows: ResMut<Windows>,
     state: Res<State<AppState>>,
 ) {
-    if *state.current() != AppState::MainMenu {
+    // taps come through as clicks too, but the touch controls don't need the cursor
+    if touch.active {
+        return;
+    }
+
+    if !matches!(
+        state.current(),
+        AppState::MainMenu
+            | AppState::Won
+            | AppState::Generation
+            | AppState::Editor
+            | AppState::Editor3D
+            | AppState::Controls
+    ) {
         let window = windows.get_primary_mut().unwrap();
 
         if keys.just_pressed(KeyCode::Escape) {
@@ -74,6 +142,18 @@ fn cursor_grab(
             window.set_cursor_grab_mode(CursorGrabMode::Locked);
             window.set_cursor_visibility(false);
         }
+
+        // start toggles it, gamepads only move the player while it's grabbed
+        if controls::gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::Start) {
+            let grabbed = window.cursor_grab_mode() != CursorGrabMode::None;
+
+            window.set_cursor_grab_mode(if grabbed {
+                CursorGrabMode::None
+            } else {
+                CursorGrabMode::Locked
+            });
+            window.set_cursor_visibility(grabbed);
+        }
     }
 }
 
@@ -83,6 +163,24 @@ pub struct Goal;
 #[derive(Component)]
 pub struct SpinBouncing;
 
+pub fn spawn_goal(commands: &mut Commands, server: &AssetServer, translation: Vec3) {
+    let scene = server.load("goal.glb#Scene0");
+
+    commands
+        .spawn(SceneBundle {
+            scene,
+            transform: Transform {
+                translation,
+                scale: Vec3::new(0.3, 0.3, 0.3),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Collider::ball(1.0))
+        .insert(SpinBouncing)
+        .insert(Goal);
+}
+
 fn animate_spin(mut query: Query<&mut Transform, With<SpinBouncing>>, time: Res<Time>) {
     for mut goal in query.iter_mut() {
         goal.rotate_y(time.delta_seconds());
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::{player2d::Player2D, player3d::Player3D, SpinBouncing};
+use crate::{controls::Action, player2d::Player2D, player3d::Player3D, SpinBouncing};
 
 #[derive(Component)]
 pub struct Marker;
@@ -9,34 +9,48 @@ fn spawn_marker(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    keys: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
     query: Query<&Transform, Or<(With<Player3D>, With<Player2D>)>>,
 ) {
     for transform in query.iter() {
-        if keys.just_pressed(KeyCode::E) {
-            commands
-                .spawn(PbrBundle {
-                    mesh: meshes.add(
-                        shape::UVSphere {
-                            radius: 0.5,
-                            ..default()
-                        }
-                        .into(),
-                    ),
-                    transform: Transform {
-                        translation: transform.translation.clone(),
-                        ..default()
-                    },
-                    material: materials.add(Color::hex("ffff00").unwrap().into()),
-                    visibility: Visibility::INVISIBLE,
-                    ..default()
-                })
-                .insert(SpinBouncing)
-                .insert(Marker);
+        if actions.just_pressed(Action::PlaceMarker) {
+            spawn_marker_at(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                transform.translation,
+            );
         }
     }
 }
 
+pub fn spawn_marker_at(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    translation: Vec3,
+) {
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(
+                shape::UVSphere {
+                    radius: 0.5,
+                    ..default()
+                }
+                .into(),
+            ),
+            transform: Transform {
+                translation,
+                ..default()
+            },
+            material: materials.add(Color::hex("ffff00").unwrap().into()),
+            visibility: Visibility::INVISIBLE,
+            ..default()
+        })
+        .insert(SpinBouncing)
+        .insert(Marker);
+}
+
 fn marker_visibility(
     mut marker_query: Query<(&GlobalTransform, &mut Visibility), With<Marker>>,
     player_query: Query<&GlobalTransform, Or<(With<Player3D>, With<Player2D>)>>,
@@ -1,318 +1,531 @@
-use bevy::prelude::*;
-use bevy::render::mesh::{Indices, VertexAttributeValues};
-use bevy::render::render_resource::PrimitiveTopology;
-use bevy_rapier3d::prelude::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::RangeInclusive;
+
 use block_mesh::ndshape::{RuntimeShape, Shape};
-use block_mesh::{
-    greedy_quads, GreedyQuadsBuffer, MergeVoxel, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
-};
+use fastrand::Rng;
 
-use crate::direction::Direction2D;
-use crate::marker::Marker;
-use crate::state::AppState;
-use crate::{Goal, SpinBouncing};
+use crate::direction::{Bias, Direction2D};
+use crate::dungeon::Dungeon;
+use crate::geometry::{MazeGeometry, EMPTY, FULL};
+use crate::hunt_and_kill::HuntAndKill2D;
 
-const MAZE_SCALE: f32 = 5.0;
+pub const MAZE_SCALE: f32 = 5.0;
+pub const MAZE_OFFSET: [f32; 3] = [-MAZE_SCALE * 2.5, -MAZE_SCALE, -MAZE_SCALE * 2.5];
 
-#[derive(Component, Default)]
+#[cfg_attr(feature = "game", derive(bevy::prelude::Component))]
+#[derive(Default, Clone, PartialEq, Debug)]
 pub struct Maze2D {
     // true == filled in/not walkable
-    cells: Vec<Vec<bool>>,
+    pub cells: Vec<Vec<bool>>,
 
     // logical size
-    width: usize,
-    height: usize,
-    // bevy shit
+    pub width: usize,
+    pub height: usize,
+
+    // what produced this maze, kept so it can be saved and regenerated
+    pub seed: u64,
+    pub generator: String,
+    pub bias: Bias,
+
+    // cell coordinates of the spawn point and the goal
+    pub start: (usize, usize),
+    pub goal: (usize, usize),
+
+    // pairs of cells that lead to each other, both ways
+    pub portals: Vec<[(usize, usize); 2]>,
 }
 
 impl Maze2D {
     // generates a maze using hunt and kill
-    pub fn hunt_and_kill(width: usize, height: usize) -> Maze2D {
-        let mut maze = Maze2D {
-            cells: vec![vec![true; width * 2 + 1]; height * 2 + 1],
-            width,
-            height,
-        };
+    pub fn hunt_and_kill(width: usize, height: usize, seed: u64) -> Maze2D {
+        HuntAndKill2D::new(width, height, seed, Bias::UNIFORM).finish()
+    }
 
-        maze.random_walk(1, 1);
-
-        for y in (1..(maze.height * 2)).step_by(2) {
-            for x in (1..(maze.width * 2)).step_by(2) {
-                if maze.cells[y][x] {
-                    for direction in Direction2D::random_order() {
-                        match direction {
-                            Direction2D::Up => {
-                                if y > 1 && !maze.cells[y - 2][x] {
-                                    maze.cells[y - 1][x] = false;
-                                    break;
-                                }
-                            }
-
-                            Direction2D::Down => {
-                                if y < maze.height * 2 - 1 && !maze.cells[y + 2][x] {
-                                    maze.cells[y + 1][x] = false;
-                                    break;
-                                }
-                            }
-
-                            Direction2D::Left => {
-                                if x > 1 && !maze.cells[y][x - 2] {
-                                    maze.cells[y][x - 1] = false;
-                                    break;
-                                }
-                            }
-
-                            Direction2D::Right => {
-                                if x < maze.width * 2 - 1 && !maze.cells[y][x + 2] {
-                                    maze.cells[y][x + 1] = false;
-                                    break;
-                                }
-                            }
-                        }
-                    }
+    // generates rooms joined up by corridors, see `Dungeon` for more control over them
+    pub fn dungeon(width: usize, height: usize, seed: u64) -> Maze2D {
+        Dungeon::default().generate(width, height, seed)
+    }
+
+    // knocks a wall out of each dead end with probability `factor`, so 0.0 keeps the maze perfect
+    // and 1.0 leaves no dead ends at all
+    pub fn braid(&mut self, factor: f32, rng: &Rng) {
+        for y in (1..(self.height * 2)).step_by(2) {
+            for x in (1..(self.width * 2)).step_by(2) {
+                if self.neighbours((x, y)).count() != 1 || rng.f32() >= factor {
+                    continue;
+                }
 
-                    maze.random_walk(x, y);
+                for direction in Direction2D::random_order(rng) {
+                    let (wall_x, wall_y) = match direction {
+                        Direction2D::Up if y > 1 => (x, y - 1),
+                        Direction2D::Down if y < self.height * 2 - 1 => (x, y + 1),
+                        Direction2D::Left if x > 1 => (x - 1, y),
+                        Direction2D::Right if x < self.width * 2 - 1 => (x + 1, y),
+                        _ => continue,
+                    };
+
+                    if self.cells[wall_y][wall_x] {
+                        self.cells[wall_y][wall_x] = false;
+                        break;
+                    }
                 }
             }
         }
-
-        maze
     }
 
-    fn random_walk(&mut self, x: usize, y: usize) {
-        let mut next = Some((x, y));
+    // Fills in dead ends until there are none left, so only the passages that lead somewhere are
+    // kept. The start and the goal are never filled in, and portals don't count as a way out.
+    // Open cells between walls, like the pillars in a dungeon's rooms, go with the cells around
+    // them.
+    pub fn prune_dead_ends(&mut self) {
+        loop {
+            let mut pruned = false;
+
+            for y in (1..(self.height * 2)).step_by(2) {
+                for x in (1..(self.width * 2)).step_by(2) {
+                    if self.cells[y][x] || (x, y) == self.start || (x, y) == self.goal {
+                        continue;
+                    }
 
-        while let Some((x, y)) = next.take() {
-            self.cells[y][x] = false;
+                    let open = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                        .into_iter()
+                        .filter(|&(x, y)| !self.cells[y][x])
+                        .collect::<Vec<_>>();
 
-            for direction in Direction2D::random_order() {
-                match direction {
-                    Direction2D::Up => {
-                        if y > 1 && self.cells[y - 2][x] {
-                            self.cells[y - 1][x] = false;
-                            next = Some((x, y - 2));
-                        }
+                    if open.len() > 1 {
+                        continue;
                     }
 
-                    Direction2D::Down => {
-                        if y < self.height * 2 - 1 && self.cells[y + 2][x] {
-                            self.cells[y + 1][x] = false;
-                            next = Some((x, y + 2));
+                    self.cells[y][x] = true;
+                    for (x, y) in open {
+                        self.cells[y][x] = true;
+                    }
+
+                    // pillars left open in a room once the cells around them are filled in
+                    for (x, y) in [
+                        (x - 1, y - 1),
+                        (x + 1, y - 1),
+                        (x - 1, y + 1),
+                        (x + 1, y + 1),
+                    ] {
+                        if !self.cells[y][x] && self.neighbours((x, y)).next().is_none() {
+                            self.cells[y][x] = true;
                         }
                     }
 
-                    Direction2D::Left => {
-                        if x > 1 && self.cells[y][x - 2] {
-                            self.cells[y][x - 1] = false;
-                            next = Some((x - 2, y));
+                    pruned = true;
+                }
+            }
+
+            if !pruned {
+                break;
+            }
+        }
+    }
+
+    // Replaces a block of `size` logical cells starting at logical cell `from` with a new random
+    // maze, then reconnects it with one passage to each part of the maze it was cut off from, so
+    // perfect mazes stay perfect. Returns the cells that might have changed, x first, or nothing if
+    // the block is empty.
+    pub fn recarve(
+        &mut self,
+        from: (usize, usize),
+        size: (usize, usize),
+        rng: &Rng,
+    ) -> Option<[RangeInclusive<usize>; 2]> {
+        let to = (
+            (from.0 + size.0).min(self.width),
+            (from.1 + size.1).min(self.height),
+        );
+
+        if to.0 <= from.0 || to.1 <= from.1 {
+            return None;
+        }
+
+        let inside = |(x, y): (usize, usize)| {
+            x > from.0 * 2 && x < to.0 * 2 && y > from.1 * 2 && y < to.1 * 2
+        };
+
+        // wall in the block, including the passages leading into it
+        for y in from.1 * 2..=to.1 * 2 {
+            for x in from.0 * 2..=to.0 * 2 {
+                self.cells[y][x] = true;
+            }
+        }
+
+        let first = (
+            (from.0 + rng.usize(..to.0 - from.0)) * 2 + 1,
+            (from.1 + rng.usize(..to.1 - from.1)) * 2 + 1,
+        );
+
+        self.cells[first.1][first.0] = false;
+        let mut stack = vec![first];
+
+        while let Some(&(x, y)) = stack.last() {
+            let next = Direction2D::biased_order(rng, &self.bias)
+                .into_iter()
+                .map(|direction| match direction {
+                    Direction2D::Up => (x, y.wrapping_sub(2)),
+                    Direction2D::Down => (x, y + 2),
+                    Direction2D::Left => (x.wrapping_sub(2), y),
+                    Direction2D::Right => (x + 2, y),
+                })
+                .find(|&(x, y)| inside((x, y)) && self.cells[y][x]);
+
+            match next {
+                Some((next_x, next_y)) => {
+                    self.cells[(y + next_y) / 2][(x + next_x) / 2] = false;
+                    self.cells[next_y][next_x] = false;
+                    stack.push((next_x, next_y));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        // every part of the rest of the maze gets one random door into the block
+        let mut visited = vec![vec![false; self.cells[0].len()]; self.cells.len()];
+
+        for y in 0..self.cells.len() {
+            for x in 0..self.cells[y].len() {
+                if self.cells[y][x] || visited[y][x] || inside((x, y)) {
+                    continue;
+                }
+
+                let mut doors = vec![];
+                let mut stack = vec![(x, y)];
+                visited[y][x] = true;
+
+                while let Some((x, y)) = stack.pop() {
+                    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                        let door = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy));
+                        let beyond = (
+                            door.0.wrapping_add_signed(dx),
+                            door.1.wrapping_add_signed(dy),
+                        );
+
+                        // doors only ever lead from one logical cell to another
+                        if x % 2 == 1 && y % 2 == 1 && inside(beyond) && self.cells[door.1][door.0]
+                        {
+                            doors.push(door);
                         }
                     }
 
-                    Direction2D::Right => {
-                        if x < self.width * 2 - 1 && self.cells[y][x + 2] {
-                            self.cells[y][x + 1] = false;
-                            next = Some((x + 2, y));
+                    for (x, y) in self.neighbours((x, y)) {
+                        if !visited[y][x] && !inside((x, y)) {
+                            visited[y][x] = true;
+                            stack.push((x, y));
                         }
                     }
                 }
 
-                if next.is_some() {
-                    break;
+                if !doors.is_empty() {
+                    let (x, y) = doors[rng.usize(..doors.len())];
+                    self.cells[y][x] = false;
                 }
             }
         }
+
+        // doors are always in the walls around the block
+        Some([from.0 * 2..=to.0 * 2, from.1 * 2..=to.1 * 2])
     }
-}
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
-struct BoolVoxel(bool);
+    // world space center of a cell, assuming the maze is spawned at the origin
+    pub fn cell_translation(&self, (x, y): (usize, usize)) -> [f32; 3] {
+        [
+            MAZE_SCALE * (x as f32 - 1.0),
+            2.0,
+            MAZE_SCALE * (y as f32 - 1.0),
+        ]
+    }
 
-const EMPTY: BoolVoxel = BoolVoxel(false);
-const FULL: BoolVoxel = BoolVoxel(true);
+    // the cell a world space position is in, the inverse of `cell_translation`
+    pub fn cell_at(&self, translation: [f32; 3]) -> Option<(usize, usize)> {
+        let x = (translation[0] / MAZE_SCALE + 1.0).round();
+        let y = (translation[2] / MAZE_SCALE + 1.0).round();
 
-impl Voxel for BoolVoxel {
-    fn get_visibility(&self) -> VoxelVisibility {
-        if *self == EMPTY {
-            VoxelVisibility::Empty
-        } else {
-            VoxelVisibility::Opaque
-        }
+        (x >= 0.0 && y >= 0.0)
+            .then_some((x as usize, y as usize))
+            .filter(|&(x, y)| y < self.cells.len() && x < self.cells[y].len())
     }
-}
 
-impl MergeVoxel for BoolVoxel {
-    type MergeValue = Self;
+    // where the portal in a cell leads, if there is one
+    pub fn portal_destination(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.portals.iter().find_map(|&[a, b]| match cell {
+            _ if cell == a => Some(b),
+            _ if cell == b => Some(a),
+            _ => None,
+        })
+    }
 
-    fn merge_value(&self) -> Self::MergeValue {
-        *self
+    // open cells next to a cell, or on the other side of a portal in it
+    pub fn neighbours(&self, (x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .chain(self.portal_destination((x, y)))
+        .filter(|&(x, y)| y < self.cells.len() && x < self.cells[y].len() && !self.cells[y][x])
     }
-}
 
-#[derive(Bundle, Default)]
-pub struct Maze2DBundle {
-    pub maze: Maze2D,
-    pub collider: Collider,
-    pub mesh: Handle<Mesh>,
-    pub material: Handle<StandardMaterial>,
-    pub transform: Transform,
-    pub global_transform: GlobalTransform,
-    pub visibility: Visibility,
-    pub computed_visibility: ComputedVisibility,
-}
+    // shortest path of cells from `start` to `goal`, both included
+    pub fn solve(&self) -> Option<Vec<(usize, usize)>> {
+        let mut previous = vec![vec![None; self.cells[0].len()]; self.cells.len()];
+        let mut queue = VecDeque::from([self.start]);
 
-fn generate_maze2d_mesh(
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut query: Query<
-        (
-            &Maze2D,
-            &Handle<Mesh>,
-            &Handle<StandardMaterial>,
-            &mut Transform,
-            &mut Collider,
-        ),
-        Added<Maze2D>,
-    >,
-) {
-    for (maze, mesh_handle, material_handle, mut transform, mut collider) in query.iter_mut() {
-        let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
-        let shape =
-            RuntimeShape::<u32, 3>::new([maze.width as u32 * 2 + 3, 3, maze.height as u32 * 2 + 3]);
-
-        // This chunk will cover just a single octant of a sphere SDF (radius 15).
-        let mut voxels = vec![EMPTY; shape.size() as usize];
-        let mut cubes = vec![];
-
-        for y in 0..maze.cells.len() {
-            for x in 0..maze.cells[0].len() {
-                if maze.cells[y][x] {
-                    voxels[shape.linearize([x as u32 + 1, 1, y as u32 + 1]) as usize] = FULL;
-
-                    cubes.push((
-                        Vec3::new(x as f32 + 1.5, 1.5, y as f32 + 1.5),
-                        Rot::default(),
-                        Collider::cuboid(0.5, 0.5, 0.5),
-                    ));
+        previous[self.start.1][self.start.0] = Some(self.start);
+
+        while let Some(cell) = queue.pop_front() {
+            if cell == self.goal {
+                let mut path = vec![cell];
+
+                while let Some(&(x, y)) = path.last() {
+                    match previous[y][x] {
+                        Some(prev) if prev != (x, y) => path.push(prev),
+                        _ => break,
+                    }
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            for (x, y) in self.neighbours(cell) {
+                if previous[y][x].is_none() {
+                    previous[y][x] = Some(cell);
+                    queue.push_back((x, y));
                 }
             }
         }
 
-        let mut buffer = GreedyQuadsBuffer::new(voxels.len());
-        greedy_quads(
-            &voxels,
-            &shape,
-            [0, 0, 0],
-            [maze.width as u32 * 2 + 2, 2, maze.height as u32 * 2 + 2],
-            &faces,
-            &mut buffer,
-        );
+        None
+    }
+
+    // connected groups of open cells that can't be reached from `start`
+    pub fn unreachable_regions(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut visited = vec![vec![false; self.cells[0].len()]; self.cells.len()];
+        let mut regions = vec![];
+
+        let flood = |visited: &mut Vec<Vec<bool>>, from: (usize, usize)| {
+            let mut region = vec![from];
+            let mut stack = vec![from];
+            visited[from.1][from.0] = true;
+
+            while let Some(cell) = stack.pop() {
+                for (x, y) in self.neighbours(cell) {
+                    if !visited[y][x] {
+                        visited[y][x] = true;
+                        region.push((x, y));
+                        stack.push((x, y));
+                    }
+                }
+            }
+
+            region
+        };
+
+        flood(&mut visited, self.start);
 
-        let num_indices = buffer.quads.num_quads() * 6;
-        let num_vertices = buffer.quads.num_quads() * 4;
-        let mut indices = Vec::with_capacity(num_indices);
-        let mut positions = Vec::with_capacity(num_vertices);
-        let mut normals = Vec::with_capacity(num_vertices);
-        for (group, face) in buffer.quads.groups.into_iter().zip(faces.into_iter()) {
-            for quad in group.into_iter() {
-                indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-                positions.extend_from_slice(&face.quad_mesh_positions(&quad, 1.0));
-                normals.extend_from_slice(&face.quad_mesh_normals());
+        for y in 0..self.cells.len() {
+            for x in 0..self.cells[y].len() {
+                if !self.cells[y][x] && !visited[y][x] {
+                    regions.push(flood(&mut visited, (x, y)));
+                }
             }
         }
 
-        let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        render_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            VertexAttributeValues::Float32x3(positions.clone()),
-        );
-        render_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            VertexAttributeValues::Float32x3(normals),
-        );
-        render_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            VertexAttributeValues::Float32x2(vec![[0.0; 2]; num_vertices]),
-        );
-        render_mesh.set_indices(Some(Indices::U32(indices.clone())));
-
-        meshes.set_untracked(mesh_handle, render_mesh);
-        materials.set_untracked(
-            material_handle,
-            StandardMaterial {
-                base_color: Color::hex("0000ff").unwrap(),
-                perceptual_roughness: 0.9,
-                metallic: 0.0,
-                ..default()
-            },
-        );
+        regions
+    }
+
+    // greedy meshed walls in maze space, see `MAZE_OFFSET` and `MAZE_SCALE` for world space
+    pub fn geometry(&self) -> MazeGeometry {
+        self.chunk_geometry((0, 0), (self.cells[0].len(), self.cells.len()))
+    }
 
-        transform.translation += Vec3::new(-MAZE_SCALE * 2.5, -MAZE_SCALE, -MAZE_SCALE * 2.5);
-        transform.scale *= Vec3::from([MAZE_SCALE; 3]);
+    // The walls of the cells from `from` up to but not including `to`, in the same place as they
+    // are in `geometry`. Faces against walls just outside the chunk are left out too, so chunks
+    // fit together without any faces in between.
+    pub fn chunk_geometry(&self, from: (usize, usize), to: (usize, usize)) -> MazeGeometry {
+        let size = [to.0 - from.0, to.1 - from.1].map(|size| size as u32);
+        let shape = RuntimeShape::<u32, 3>::new([size[0] + 2, 3, size[1] + 2]);
 
-        *collider = Collider::compound(cubes);
+        let mut voxels = vec![EMPTY; shape.size() as usize];
+
+        // including a border of cells around the chunk, which is only used to hide faces
+        for y in from.1.saturating_sub(1)..(to.1 + 1).min(self.cells.len()) {
+            for x in from.0.saturating_sub(1)..(to.0 + 1).min(self.cells[y].len()) {
+                if self.cells[y][x] {
+                    let voxel = [x + 1 - from.0, 1, y + 1 - from.1].map(|i| i as u32);
+                    voxels[shape.linearize(voxel) as usize] = FULL;
+                }
+            }
+        }
+
+        MazeGeometry::from_voxels(&voxels, &shape, [size[0] + 1, 2, size[1] + 1])
+            .transformed(1.0, [from.0 as f32, 0.0, from.1 as f32])
     }
 }
 
-fn setup_maze2d(
-    mut commands: Commands,
-    server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    commands
-        .spawn(Maze2DBundle {
-            maze: Maze2D::hunt_and_kill(10, 10),
+// walls are `#`, the start is `S` and the goal is `G`
+impl fmt::Display for Maze2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, &wall) in row.iter().enumerate() {
+                let c = match (x, y) {
+                    cell if cell == self.start => 'S',
+                    cell if cell == self.goal => 'G',
+                    _ if wall => '#',
+                    _ => ' ',
+                };
+
+                write!(f, "{c}")?;
+            }
 
-            // We need to preset a mesh here don't ask
-            mesh: meshes.add(shape::Cube::new(50.0).into()),
+            writeln!(f)?;
+        }
 
-            ..default()
-        })
-        .with_children(|parent| {
-            parent
-                .spawn(PbrBundle {
-                    mesh: meshes.add(shape::Plane { size: 100.0 }.into()),
-                    material: materials.add(StandardMaterial {
-                        base_color: Color::hex("00aaff").unwrap(),
-                        ..default()
-                    }),
-                    transform: Transform::from_xyz(0.0, 1.0, 0.0),
-                    ..default()
-                })
-                .insert(Collider::cuboid(100.0, 0.01, 100.0));
-        });
-
-    let scene = server.load("goal.glb#Scene0");
-
-    commands
-        .spawn(SceneBundle {
-            scene,
-            transform: Transform {
-                translation: Vec3::new(90.0, 2.0, 90.0),
-                scale: Vec3::new(0.3, 0.3, 0.3),
-                ..default()
-            },
-            ..default()
-        })
-        .insert(Collider::ball(1.0))
-        .insert(SpinBouncing)
-        .insert(Goal);
+        Ok(())
+    }
 }
 
-fn cleanup_maze2d(
-    mut commands: Commands,
-    query: Query<Entity, Or<(With<Goal>, With<Maze2D>, With<Marker>)>>,
-) {
-    for entity in query.iter() {
-        commands.entity(entity).despawn_recursive();
+#[cfg(test)]
+impl Maze2D {
+    // every open cell can be reached from the start in exactly one way
+    pub fn is_perfect(&self) -> bool {
+        let open: Vec<_> = (0..self.cells.len())
+            .flat_map(|y| (0..self.cells[y].len()).map(move |x| (x, y)))
+            .filter(|&(x, y)| !self.cells[y][x])
+            .collect();
+
+        let passages: usize = open.iter().map(|&cell| self.neighbours(cell).count()).sum();
+
+        self.unreachable_regions().is_empty() && passages / 2 == open.len() - 1
     }
 }
 
-pub struct Maze2DPlugin;
-impl Plugin for Maze2DPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_system(generate_maze2d_mesh)
-            .add_system_set(SystemSet::on_enter(AppState::Maze2D).with_system(setup_maze2d))
-            .add_system_set(SystemSet::on_exit(AppState::Maze2D).with_system(cleanup_maze2d));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(geometry: &MazeGeometry) -> f32 {
+        geometry
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| geometry.positions[triangle[i] as usize]);
+                let ab = [0, 1, 2].map(|i| b[i] - a[i]);
+                let ac = [0, 1, 2].map(|i| c[i] - a[i]);
+                let cross = [
+                    ab[1] * ac[2] - ab[2] * ac[1],
+                    ab[2] * ac[0] - ab[0] * ac[2],
+                    ab[0] * ac[1] - ab[1] * ac[0],
+                ];
+
+                cross.iter().map(|c| c * c).sum::<f32>().sqrt() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn recarving_keeps_mazes_perfect() {
+        let rng = Rng::with_seed(3);
+
+        for seed in 0..8 {
+            let mut maze = Maze2D::hunt_and_kill(9, 7, seed);
+
+            for _ in 0..8 {
+                let size = (rng.usize(1..=4), rng.usize(1..=4));
+                let from = (rng.usize(..9), rng.usize(..7));
+
+                let before = maze.clone();
+                let [xs, ys] = maze.recarve(from, size, &rng).unwrap();
+
+                assert!(maze.is_perfect(), "{maze}");
+
+                for y in 0..maze.cells.len() {
+                    for x in 0..maze.cells[y].len() {
+                        if !xs.contains(&x) || !ys.contains(&y) {
+                            assert_eq!(maze.cells[y][x], before.cells[y][x]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn recarving_nothing_changes_nothing() {
+        let mut maze = Maze2D::hunt_and_kill(5, 5, 1);
+        let before = maze.clone();
+
+        assert!(maze.recarve((5, 0), (2, 2), &Rng::with_seed(1)).is_none());
+        assert_eq!(maze, before);
+    }
+
+    #[test]
+    fn chunks_cover_the_same_walls_as_the_whole_maze() {
+        let maze = Maze2D::hunt_and_kill(9, 7, 5);
+        let (columns, rows) = (maze.cells[0].len(), maze.cells.len());
+
+        let mut chunked = 0.0;
+
+        for y in (0..rows).step_by(4) {
+            for x in (0..columns).step_by(4) {
+                let to = ((x + 4).min(columns), (y + 4).min(rows));
+                let geometry = maze.chunk_geometry((x, y), to);
+
+                // `geometry` has the cells one unit in, to leave room for the outside of the maze
+                for &[px, _, pz] in geometry.positions.iter() {
+                    assert!(px >= x as f32 + 1.0 && px <= to.0 as f32 + 1.0);
+                    assert!(pz >= y as f32 + 1.0 && pz <= to.1 as f32 + 1.0);
+                }
+
+                chunked += area(&geometry);
+            }
+        }
+
+        assert!((chunked - area(&maze.geometry())).abs() < 0.01);
+    }
+
+    #[test]
+    fn pruning_fills_in_pillars_left_on_their_own() {
+        // a dead end running down the middle, with an open pillar beside its bottom half
+        let rows = [
+            "#######", //
+            "#     #", //
+            "### ###", //
+            "### ###", //
+            "###  ##", //
+            "### ###", //
+            "#######", //
+        ];
+
+        let mut maze = Maze2D {
+            cells: rows
+                .iter()
+                .map(|row| row.chars().map(|c| c == '#').collect())
+                .collect(),
+            width: 3,
+            height: 3,
+            seed: 0,
+            generator: "test".to_string(),
+            bias: Bias::UNIFORM,
+            start: (1, 1),
+            goal: (5, 1),
+            portals: vec![],
+        };
+
+        maze.prune_dead_ends();
+
+        assert!(maze.unreachable_regions().is_empty());
+        assert_eq!(
+            maze.cells.iter().flatten().filter(|&&wall| !wall).count(),
+            5
+        );
     }
 }
@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use mazemazemaze::analysis::MazeStats;
+use mazemazemaze::maze2d::{Maze2D, MAZE_OFFSET, MAZE_SCALE};
+
+use crate::chunks::{update_chunks, MazeChunks};
+use crate::editor::EditedMaze2D;
+use crate::marker::{spawn_marker_at, Marker};
+use crate::maze_loader::PendingMaze;
+use crate::portal::{spawn_portal, Portal};
+use crate::state::AppState;
+use crate::win::{CurrentMazeStats, TargetDifficulty};
+use crate::{spawn_goal, Goal, Settings};
+
+// The first maze of each run, made ahead of time with
+// `mazegen --size 6x6 --seed 1 --solution-length 24-30 --dead-ends 4-6 --format json`
+const FIRST_MAZE: &str = "first.maze";
+
+#[derive(Bundle, Default)]
+pub struct Maze2DBundle {
+    pub maze: Maze2D,
+    pub chunks: MazeChunks,
+    pub material: Handle<StandardMaterial>,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub computed_visibility: ComputedVisibility,
+}
+
+fn paint_maze2d(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<&Handle<StandardMaterial>, Added<Maze2D>>,
+) {
+    for material_handle in query.iter() {
+        materials.set_untracked(
+            material_handle,
+            StandardMaterial {
+                base_color: Color::hex("0000ff").unwrap(),
+                perceptual_roughness: 0.9,
+                metallic: 0.0,
+                ..default()
+            },
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_maze2d(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    target: Res<TargetDifficulty>,
+    settings: Res<Settings>,
+    mut edited: Option<ResMut<EditedMaze2D>>,
+    mut played_first: Local<bool>,
+) {
+    let playtest = edited.as_ref().is_some_and(|edited| edited.playtest);
+
+    // the goal, markers and portals are spawned along with the maze once the file has loaded
+    if !*played_first && !playtest {
+        *played_first = true;
+
+        commands
+            .spawn(PendingMaze(server.load(FIRST_MAZE)))
+            .with_children(|parent| spawn_floor(parent, &mut meshes, &mut materials));
+
+        return;
+    }
+
+    // play the maze from the editor if it asked for it, otherwise generate one
+    let (maze, markers) = match edited.as_deref_mut() {
+        Some(edited) if edited.playtest => {
+            edited.playtest = false;
+            (edited.maze.clone(), edited.markers.clone())
+        }
+        _ => (
+            settings
+                .generator
+                .rated_maze2d(target.0, fastrand::u64(..), settings.bias),
+            vec![],
+        ),
+    };
+
+    commands.insert_resource(CurrentMazeStats(MazeStats::of_maze2d(&maze)));
+
+    let goal = Vec3::from(maze.cell_translation(maze.goal));
+
+    for &[a, b] in maze.portals.iter() {
+        for cell in [a, b] {
+            let translation = Vec3::from(maze.cell_translation(cell));
+            spawn_portal(&mut commands, &mut meshes, &mut materials, translation);
+        }
+    }
+
+    for marker in markers {
+        let translation = Vec3::from(maze.cell_translation(marker));
+        spawn_marker_at(&mut commands, &mut meshes, &mut materials, translation);
+    }
+
+    commands
+        .spawn(Maze2DBundle { maze, ..default() })
+        .with_children(|parent| spawn_floor(parent, &mut meshes, &mut materials));
+
+    spawn_goal(&mut commands, &server, goal);
+}
+
+fn spawn_floor(
+    parent: &mut ChildBuilder,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    parent
+        .spawn(PbrBundle {
+            mesh: meshes.add(shape::Plane { size: 100.0 }.into()),
+            material: materials.add(StandardMaterial {
+                base_color: Color::hex("00aaff").unwrap(),
+                ..default()
+            }),
+            // the top of the bottom layer of walls, in maze space
+            transform: Transform {
+                translation: Vec3::from(MAZE_OFFSET) + Vec3::Y * MAZE_SCALE,
+                scale: Vec3::splat(MAZE_SCALE),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Collider::cuboid(100.0, 0.01, 100.0));
+}
+
+fn cleanup_maze2d(
+    mut commands: Commands,
+    query: Query<
+        Entity,
+        Or<(
+            With<Goal>,
+            With<Maze2D>,
+            With<PendingMaze>,
+            With<Marker>,
+            With<Portal>,
+        )>,
+    >,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct Maze2DPlugin;
+impl Plugin for Maze2DPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_chunks::<Maze2D>)
+            .add_system(paint_maze2d)
+            .add_system_set(SystemSet::on_enter(AppState::Maze2D).with_system(setup_maze2d))
+            .add_system_set(SystemSet::on_exit(AppState::Maze2D).with_system(cleanup_maze2d));
+    }
+}
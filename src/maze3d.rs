@@ -1,346 +1,496 @@
-use bevy::prelude::*;
-use bevy::render::mesh::{Indices, VertexAttributeValues};
-use bevy::render::render_resource::PrimitiveTopology;
-use bevy_rapier3d::prelude::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::RangeInclusive;
+
 use block_mesh::ndshape::{RuntimeShape, Shape};
-use block_mesh::{
-    greedy_quads, GreedyQuadsBuffer, MergeVoxel, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
-};
+use fastrand::Rng;
 
-use crate::direction::Direction3D;
-use crate::marker::Marker;
-use crate::state::AppState;
-use crate::{Goal, SpinBouncing};
+use crate::direction::{Bias, Direction3D};
+use crate::geometry::{MazeGeometry, EMPTY, FULL};
+use crate::hunt_and_kill::HuntAndKill3D;
 
-const MAZE_SCALE: f32 = 5.0;
+pub const MAZE_SCALE: f32 = 5.0;
+pub const MAZE_OFFSET: [f32; 3] = [-MAZE_SCALE * 2.5, -2.0 * MAZE_SCALE, -MAZE_SCALE * 2.5];
 
-#[derive(Component, Default)]
+#[cfg_attr(feature = "game", derive(bevy::prelude::Component))]
+#[derive(Default, Clone, PartialEq, Debug)]
 pub struct Maze3D {
     // true == filled in/not walkable
-    cells: Vec<Vec<Vec<bool>>>,
+    pub cells: Vec<Vec<Vec<bool>>>,
 
     // logical size
-    width: usize,
-    height: usize,
-    depth: usize,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+
+    // what produced this maze, kept so it can be saved and regenerated
+    pub seed: u64,
+    pub generator: String,
+    pub bias: Bias,
+
+    // cell coordinates of the spawn point and the goal
+    pub start: (usize, usize, usize),
+    pub goal: (usize, usize, usize),
 }
 
 impl Maze3D {
     // generates a maze using hunt and kill
-    pub fn hunt_and_kill(width: usize, height: usize, depth: usize) -> Maze3D {
-        let mut maze = Maze3D {
-            cells: vec![vec![vec![true; width * 2 + 1]; height * 2 + 1]; depth * 2 + 1],
-            width,
-            height,
-            depth,
-        };
+    pub fn hunt_and_kill(width: usize, height: usize, depth: usize, seed: u64) -> Maze3D {
+        HuntAndKill3D::new(width, height, depth, seed, Bias::UNIFORM).finish()
+    }
 
-        maze.random_walk(1, 1, 1);
-
-        for z in (1..(maze.depth * 2)).step_by(2) {
-            for y in (1..(maze.height * 2)).step_by(2) {
-                for x in (1..(maze.width * 2)).step_by(2) {
-                    if maze.cells[z][y][x] {
-                        for direction in Direction3D::random_order() {
-                            match direction {
-                                Direction3D::Up => {
-                                    if y > 1 && !maze.cells[z][y - 2][x] {
-                                        maze.cells[z][y - 1][x] = false;
-                                        break;
-                                    }
-                                }
-
-                                Direction3D::Down => {
-                                    if y < maze.height * 2 - 1 && !maze.cells[z][y + 2][x] {
-                                        maze.cells[z][y + 1][x] = false;
-                                        break;
-                                    }
-                                }
-
-                                Direction3D::Left => {
-                                    if x > 1 && !maze.cells[z][y][x - 2] {
-                                        maze.cells[z][y][x - 1] = false;
-                                        break;
-                                    }
-                                }
-
-                                Direction3D::Right => {
-                                    if x < maze.width * 2 - 1 && !maze.cells[z][y][x + 2] {
-                                        maze.cells[z][y][x + 1] = false;
-                                        break;
-                                    }
-                                }
-
-                                Direction3D::In => {
-                                    if z > 1 && !maze.cells[z - 2][y][x] {
-                                        maze.cells[z - 1][y][x] = false;
-                                        break;
-                                    }
-                                }
-
-                                Direction3D::Out => {
-                                    if z < maze.depth * 2 - 1 && !maze.cells[z + 2][y][x] {
-                                        maze.cells[z + 1][y][x] = false;
-                                        break;
-                                    }
-                                }
-                            }
-                        }
+    // knocks a wall out of each dead end with probability `factor`, so 0.0 keeps the maze perfect
+    // and 1.0 leaves no dead ends at all
+    pub fn braid(&mut self, factor: f32, rng: &Rng) {
+        for z in (1..(self.depth * 2)).step_by(2) {
+            for y in (1..(self.height * 2)).step_by(2) {
+                for x in (1..(self.width * 2)).step_by(2) {
+                    if self.neighbours((x, y, z)).count() != 1 || rng.f32() >= factor {
+                        continue;
+                    }
 
-                        maze.random_walk(x, y, z);
+                    for direction in Direction3D::random_order(rng) {
+                        let (wall_x, wall_y, wall_z) = match direction {
+                            Direction3D::Up if y > 1 => (x, y - 1, z),
+                            Direction3D::Down if y < self.height * 2 - 1 => (x, y + 1, z),
+                            Direction3D::Left if x > 1 => (x - 1, y, z),
+                            Direction3D::Right if x < self.width * 2 - 1 => (x + 1, y, z),
+                            Direction3D::In if z > 1 => (x, y, z - 1),
+                            Direction3D::Out if z < self.depth * 2 - 1 => (x, y, z + 1),
+                            _ => continue,
+                        };
+
+                        if self.cells[wall_z][wall_y][wall_x] {
+                            self.cells[wall_z][wall_y][wall_x] = false;
+                            break;
+                        }
                     }
                 }
             }
         }
+    }
 
-        maze
+    // open cells next to a cell
+    pub fn neighbours(
+        &self,
+        (x, y, z): (usize, usize, usize),
+    ) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        [
+            (x.wrapping_sub(1), y, z),
+            (x + 1, y, z),
+            (x, y.wrapping_sub(1), z),
+            (x, y + 1, z),
+            (x, y, z.wrapping_sub(1)),
+            (x, y, z + 1),
+        ]
+        .into_iter()
+        .filter(|&(x, y, z)| {
+            z < self.cells.len()
+                && y < self.cells[z].len()
+                && x < self.cells[z][y].len()
+                && !self.cells[z][y][x]
+        })
     }
 
-    fn random_walk(&mut self, x: usize, y: usize, z: usize) {
-        let mut next = Some((x, y, z));
+    // Replaces a block of `size` logical cells starting at logical cell `from` with a new random
+    // maze, see `Maze2D::recarve`.
+    pub fn recarve(
+        &mut self,
+        from: (usize, usize, usize),
+        size: (usize, usize, usize),
+        rng: &Rng,
+    ) -> Option<[RangeInclusive<usize>; 3]> {
+        let to = (
+            (from.0 + size.0).min(self.width),
+            (from.1 + size.1).min(self.height),
+            (from.2 + size.2).min(self.depth),
+        );
 
-        while let Some((x, y, z)) = next.take() {
-            self.cells[z][y][x] = false;
+        if to.0 <= from.0 || to.1 <= from.1 || to.2 <= from.2 {
+            return None;
+        }
 
-            for direction in Direction3D::random_order() {
-                match direction {
-                    Direction3D::Up => {
-                        if y > 1 && self.cells[z][y - 2][x] {
-                            self.cells[z][y - 1][x] = false;
-                            next = Some((x, y - 2, z));
-                        }
-                    }
+        let inside = |(x, y, z): (usize, usize, usize)| {
+            x > from.0 * 2
+                && x < to.0 * 2
+                && y > from.1 * 2
+                && y < to.1 * 2
+                && z > from.2 * 2
+                && z < to.2 * 2
+        };
 
-                    Direction3D::Down => {
-                        if y < self.height * 2 - 1 && self.cells[z][y + 2][x] {
-                            self.cells[z][y + 1][x] = false;
-                            next = Some((x, y + 2, z));
-                        }
+        // wall in the block, including the passages leading into it
+        for z in from.2 * 2..=to.2 * 2 {
+            for y in from.1 * 2..=to.1 * 2 {
+                for x in from.0 * 2..=to.0 * 2 {
+                    self.cells[z][y][x] = true;
+                }
+            }
+        }
+
+        let first = (
+            (from.0 + rng.usize(..to.0 - from.0)) * 2 + 1,
+            (from.1 + rng.usize(..to.1 - from.1)) * 2 + 1,
+            (from.2 + rng.usize(..to.2 - from.2)) * 2 + 1,
+        );
+
+        self.cells[first.2][first.1][first.0] = false;
+        let mut stack = vec![first];
+
+        while let Some(&(x, y, z)) = stack.last() {
+            let next = Direction3D::biased_order(rng, &self.bias)
+                .into_iter()
+                .map(|direction| match direction {
+                    Direction3D::Up => (x, y.wrapping_sub(2), z),
+                    Direction3D::Down => (x, y + 2, z),
+                    Direction3D::Left => (x.wrapping_sub(2), y, z),
+                    Direction3D::Right => (x + 2, y, z),
+                    Direction3D::In => (x, y, z.wrapping_sub(2)),
+                    Direction3D::Out => (x, y, z + 2),
+                })
+                .find(|&(x, y, z)| inside((x, y, z)) && self.cells[z][y][x]);
+
+            match next {
+                Some((next_x, next_y, next_z)) => {
+                    self.cells[(z + next_z) / 2][(y + next_y) / 2][(x + next_x) / 2] = false;
+                    self.cells[next_z][next_y][next_x] = false;
+                    stack.push((next_x, next_y, next_z));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        // every part of the rest of the maze gets one random door into the block
+        let mut visited =
+            vec![vec![vec![false; self.cells[0][0].len()]; self.cells[0].len()]; self.cells.len()];
+
+        for z in 0..self.cells.len() {
+            for y in 0..self.cells[z].len() {
+                for x in 0..self.cells[z][y].len() {
+                    if self.cells[z][y][x] || visited[z][y][x] || inside((x, y, z)) {
+                        continue;
                     }
 
-                    Direction3D::Left => {
-                        if x > 1 && self.cells[z][y][x - 2] {
-                            self.cells[z][y][x - 1] = false;
-                            next = Some((x - 2, y, z));
+                    let mut doors = vec![];
+                    let mut stack = vec![(x, y, z)];
+                    visited[z][y][x] = true;
+
+                    while let Some((x, y, z)) = stack.pop() {
+                        for (dx, dy, dz) in [
+                            (-1, 0, 0),
+                            (1, 0, 0),
+                            (0, -1, 0),
+                            (0, 1, 0),
+                            (0, 0, -1),
+                            (0, 0, 1),
+                        ] {
+                            let door = (
+                                x.wrapping_add_signed(dx),
+                                y.wrapping_add_signed(dy),
+                                z.wrapping_add_signed(dz),
+                            );
+                            let beyond = (
+                                door.0.wrapping_add_signed(dx),
+                                door.1.wrapping_add_signed(dy),
+                                door.2.wrapping_add_signed(dz),
+                            );
+
+                            // doors only ever lead from one logical cell to another
+                            if x % 2 == 1
+                                && y % 2 == 1
+                                && z % 2 == 1
+                                && inside(beyond)
+                                && self.cells[door.2][door.1][door.0]
+                            {
+                                doors.push(door);
+                            }
                         }
-                    }
 
-                    Direction3D::Right => {
-                        if x < self.width * 2 - 1 && self.cells[z][y][x + 2] {
-                            self.cells[z][y][x + 1] = false;
-                            next = Some((x + 2, y, z));
+                        for (x, y, z) in self.neighbours((x, y, z)) {
+                            if !visited[z][y][x] && !inside((x, y, z)) {
+                                visited[z][y][x] = true;
+                                stack.push((x, y, z));
+                            }
                         }
                     }
 
-                    Direction3D::In => {
-                        if z > 1 && self.cells[z - 2][y][x] {
-                            self.cells[z - 1][y][x] = false;
-                            next = Some((x, y, z - 2));
-                        }
+                    if !doors.is_empty() {
+                        let (x, y, z) = doors[rng.usize(..doors.len())];
+                        self.cells[z][y][x] = false;
                     }
+                }
+            }
+        }
 
-                    Direction3D::Out => {
-                        if z < self.depth * 2 - 1 && self.cells[z + 2][y][x] {
-                            self.cells[z + 1][y][x] = false;
-                            next = Some((x, y, z + 2));
-                        }
+        Some([
+            from.0 * 2..=to.0 * 2,
+            from.1 * 2..=to.1 * 2,
+            from.2 * 2..=to.2 * 2,
+        ])
+    }
+
+    // shortest path of cells from `start` to `goal`, both included
+    pub fn solve(&self) -> Option<Vec<(usize, usize, usize)>> {
+        let mut previous =
+            vec![vec![vec![None; self.cells[0][0].len()]; self.cells[0].len()]; self.cells.len()];
+        let mut queue = VecDeque::from([self.start]);
+
+        let (x, y, z) = self.start;
+        previous[z][y][x] = Some(self.start);
+
+        while let Some(cell) = queue.pop_front() {
+            if cell == self.goal {
+                let mut path = vec![cell];
+
+                while let Some(&(x, y, z)) = path.last() {
+                    match previous[z][y][x] {
+                        Some(prev) if prev != (x, y, z) => path.push(prev),
+                        _ => break,
                     }
                 }
 
-                if next.is_some() {
-                    break;
+                path.reverse();
+                return Some(path);
+            }
+
+            for (x, y, z) in self.neighbours(cell) {
+                if previous[z][y][x].is_none() {
+                    previous[z][y][x] = Some(cell);
+                    queue.push_back((x, y, z));
                 }
             }
         }
+
+        None
     }
-}
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
-struct BoolVoxel(bool);
+    // connected groups of open cells that can't be reached from `start`
+    pub fn unreachable_regions(&self) -> Vec<Vec<(usize, usize, usize)>> {
+        let mut visited =
+            vec![vec![vec![false; self.cells[0][0].len()]; self.cells[0].len()]; self.cells.len()];
+        let mut regions = vec![];
+
+        let flood = |visited: &mut Vec<Vec<Vec<bool>>>, from: (usize, usize, usize)| {
+            let mut region = vec![from];
+            let mut stack = vec![from];
+            visited[from.2][from.1][from.0] = true;
+
+            while let Some(cell) = stack.pop() {
+                for (x, y, z) in self.neighbours(cell) {
+                    if !visited[z][y][x] {
+                        visited[z][y][x] = true;
+                        region.push((x, y, z));
+                        stack.push((x, y, z));
+                    }
+                }
+            }
+
+            region
+        };
 
-const EMPTY: BoolVoxel = BoolVoxel(false);
-const FULL: BoolVoxel = BoolVoxel(true);
+        flood(&mut visited, self.start);
 
-impl Voxel for BoolVoxel {
-    fn get_visibility(&self) -> VoxelVisibility {
-        if *self == EMPTY {
-            VoxelVisibility::Empty
-        } else {
-            VoxelVisibility::Opaque
+        for z in 0..self.cells.len() {
+            for y in 0..self.cells[z].len() {
+                for x in 0..self.cells[z][y].len() {
+                    if !self.cells[z][y][x] && !visited[z][y][x] {
+                        regions.push(flood(&mut visited, (x, y, z)));
+                    }
+                }
+            }
         }
+
+        regions
     }
-}
 
-impl MergeVoxel for BoolVoxel {
-    type MergeValue = Self;
+    // world space center of a cell, assuming the maze is spawned at the origin
+    pub fn cell_translation(&self, (x, y, z): (usize, usize, usize)) -> [f32; 3] {
+        [
+            MAZE_SCALE * (x as f32 - 1.0),
+            MAZE_SCALE * (y as f32 - 0.5),
+            MAZE_SCALE * (z as f32 - 1.0),
+        ]
+    }
 
-    fn merge_value(&self) -> Self::MergeValue {
-        *self
+    // the cell a world space position is in, the inverse of `cell_translation`
+    pub fn cell_at(&self, translation: [f32; 3]) -> Option<(usize, usize, usize)> {
+        let x = (translation[0] / MAZE_SCALE + 1.0).round();
+        let y = (translation[1] / MAZE_SCALE + 0.5).round();
+        let z = (translation[2] / MAZE_SCALE + 1.0).round();
+
+        (x >= 0.0 && y >= 0.0 && z >= 0.0)
+            .then_some((x as usize, y as usize, z as usize))
+            .filter(|&(x, y, z)| {
+                z < self.cells.len() && y < self.cells[z].len() && x < self.cells[z][y].len()
+            })
     }
-}
 
-#[derive(Bundle, Default)]
-pub struct Maze3DBundle {
-    pub maze: Maze3D,
-    pub collider: Collider,
-    pub mesh: Handle<Mesh>,
-    pub material: Handle<StandardMaterial>,
-    pub transform: Transform,
-    pub global_transform: GlobalTransform,
-    pub visibility: Visibility,
-    pub computed_visibility: ComputedVisibility,
-}
+    // greedy meshed walls in maze space, see `MAZE_OFFSET` and `MAZE_SCALE` for world space
+    pub fn geometry(&self) -> MazeGeometry {
+        self.chunk_geometry(
+            (0, 0, 0),
+            (
+                self.cells[0][0].len(),
+                self.cells[0].len(),
+                self.cells.len(),
+            ),
+        )
+    }
+
+    // see `Maze2D::chunk_geometry`
+    pub fn chunk_geometry(
+        &self,
+        from: (usize, usize, usize),
+        to: (usize, usize, usize),
+    ) -> MazeGeometry {
+        let size = [to.0 - from.0, to.1 - from.1, to.2 - from.2].map(|size| size as u32);
+        let shape = RuntimeShape::<u32, 3>::new(size.map(|size| size + 2));
 
-fn generate_maze3d_mesh(
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut query: Query<
-        (
-            &Maze3D,
-            &Handle<Mesh>,
-            &Handle<StandardMaterial>,
-            &mut Transform,
-            &mut Collider,
-        ),
-        Added<Maze3D>,
-    >,
-) {
-    for (maze, mesh_handle, material_handle, mut transform, mut collider) in query.iter_mut() {
-        let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
-        let shape = RuntimeShape::<u32, 3>::new([
-            maze.width as u32 * 2 + 3,
-            maze.height as u32 * 2 + 3,
-            maze.depth as u32 * 2 + 3,
-        ]);
-
-        // This chunk will cover just a single octant of a sphere SDF (radius 15).
         let mut voxels = vec![EMPTY; shape.size() as usize];
-        let mut cubes = vec![];
-
-        for z in 0..maze.cells.len() {
-            for y in 0..maze.cells[0].len() {
-                for x in 0..maze.cells[0][0].len() {
-                    if maze.cells[z][y][x] {
-                        voxels[shape.linearize([x as u32 + 1, y as u32 + 1, z as u32 + 1])
-                            as usize] = FULL;
-
-                        cubes.push((
-                            Vec3::new(x as f32 + 1.5, y as f32 + 1.5, z as f32 + 1.5),
-                            Rot::default(),
-                            Collider::cuboid(0.5, 0.5, 0.5),
-                        ));
+
+        for z in from.2.saturating_sub(1)..(to.2 + 1).min(self.cells.len()) {
+            for y in from.1.saturating_sub(1)..(to.1 + 1).min(self.cells[z].len()) {
+                for x in from.0.saturating_sub(1)..(to.0 + 1).min(self.cells[z][y].len()) {
+                    if self.cells[z][y][x] {
+                        let voxel = [x + 1 - from.0, y + 1 - from.1, z + 1 - from.2];
+                        voxels[shape.linearize(voxel.map(|i| i as u32)) as usize] = FULL;
                     }
                 }
             }
         }
 
-        let mut buffer = GreedyQuadsBuffer::new(voxels.len());
-        greedy_quads(
-            &voxels,
-            &shape,
-            [0, 0, 0],
-            [
-                maze.width as u32 * 2 + 2,
-                maze.height as u32 * 2 + 2,
-                maze.depth as u32 * 2 + 2,
-            ],
-            &faces,
-            &mut buffer,
-        );
+        MazeGeometry::from_voxels(&voxels, &shape, size.map(|size| size + 1))
+            .transformed(1.0, [from.0, from.1, from.2].map(|i| i as f32))
+    }
+}
 
-        let num_indices = buffer.quads.num_quads() * 6;
-        let num_vertices = buffer.quads.num_quads() * 4;
-        let mut indices = Vec::with_capacity(num_indices);
-        let mut positions = Vec::with_capacity(num_vertices);
-        let mut normals = Vec::with_capacity(num_vertices);
-        for (group, face) in buffer.quads.groups.into_iter().zip(faces.into_iter()) {
-            for quad in group.into_iter() {
-                indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-                positions.extend_from_slice(&face.quad_mesh_positions(&quad, 1.0));
-                normals.extend_from_slice(&face.quad_mesh_normals());
+// one layer per z, separated by blank lines, see `Maze2D`'s `Display` impl
+impl fmt::Display for Maze3D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (z, layer) in self.cells.iter().enumerate() {
+            if z > 0 {
+                writeln!(f)?;
             }
-        }
 
-        let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        render_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            VertexAttributeValues::Float32x3(positions.clone()),
-        );
-        render_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            VertexAttributeValues::Float32x3(normals),
-        );
-        render_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            VertexAttributeValues::Float32x2(vec![[0.0; 2]; num_vertices]),
-        );
-        render_mesh.set_indices(Some(Indices::U32(indices.clone())));
-
-        meshes.set_untracked(mesh_handle, render_mesh);
-        materials.set_untracked(
-            material_handle,
-            StandardMaterial {
-                base_color: Color::hex("0000ff").unwrap(),
-                perceptual_roughness: 0.9,
-                metallic: 0.0,
-                ..default()
-            },
-        );
+            for (y, row) in layer.iter().enumerate() {
+                for (x, &wall) in row.iter().enumerate() {
+                    let c = match (x, y, z) {
+                        cell if cell == self.start => 'S',
+                        cell if cell == self.goal => 'G',
+                        _ if wall => '#',
+                        _ => ' ',
+                    };
+
+                    write!(f, "{c}")?;
+                }
 
-        transform.translation += Vec3::new(-MAZE_SCALE * 2.5, -2.0 * MAZE_SCALE, -MAZE_SCALE * 2.5);
-        transform.scale *= Vec3::from([MAZE_SCALE; 3]);
+                writeln!(f)?;
+            }
+        }
 
-        *collider = Collider::compound(cubes);
+        Ok(())
     }
 }
 
-fn setup_maze3d(
-    mut commands: Commands,
-    server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-) {
-    // spawn maze
-    commands.spawn(Maze3DBundle {
-        maze: Maze3D::hunt_and_kill(10, 10, 10),
-
-        // We need to preset a mesh here don't ask
-        mesh: meshes.add(shape::Cube::new(50.0).into()),
-
-        ..default()
-    });
-
-    // spawn goal
-    let scene = server.load("goal.glb#Scene0");
-
-    commands
-        .spawn(SceneBundle {
-            scene,
-            transform: Transform {
-                translation: Vec3::new(90.0, 92.0, 90.0),
-                scale: Vec3::new(0.3, 0.3, 0.3),
-                ..default()
-            },
-            ..default()
-        })
-        .insert(Collider::ball(1.0))
-        .insert(SpinBouncing)
-        .insert(Goal);
+#[cfg(test)]
+impl Maze3D {
+    // see `Maze2D::is_perfect`
+    pub fn is_perfect(&self) -> bool {
+        let open: Vec<_> = (0..self.cells.len())
+            .flat_map(|z| {
+                (0..self.cells[z].len())
+                    .flat_map(move |y| (0..self.cells[z][y].len()).map(move |x| (x, y, z)))
+            })
+            .filter(|&(x, y, z)| !self.cells[z][y][x])
+            .collect();
+
+        let passages: usize = open.iter().map(|&cell| self.neighbours(cell).count()).sum();
+
+        self.unreachable_regions().is_empty() && passages / 2 == open.len() - 1
+    }
 }
 
-fn cleanup_maze3d(
-    mut commands: Commands,
-    query: Query<Entity, Or<(With<Goal>, With<Maze3D>, With<Marker>)>>,
-) {
-    for entity in query.iter() {
-        commands.entity(entity).despawn_recursive();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recarving_keeps_mazes_perfect() {
+        let rng = Rng::with_seed(3);
+
+        for seed in 0..4 {
+            let mut maze = Maze3D::hunt_and_kill(5, 4, 4, seed);
+
+            for _ in 0..8 {
+                let size = (rng.usize(1..=3), rng.usize(1..=3), rng.usize(1..=3));
+                let from = (rng.usize(..5), rng.usize(..4), rng.usize(..4));
+
+                let before = maze.clone();
+                let [xs, ys, zs] = maze.recarve(from, size, &rng).unwrap();
+
+                assert!(maze.is_perfect(), "{maze}");
+
+                for z in 0..maze.cells.len() {
+                    for y in 0..maze.cells[z].len() {
+                        for x in 0..maze.cells[z][y].len() {
+                            if !xs.contains(&x) || !ys.contains(&y) || !zs.contains(&z) {
+                                assert_eq!(maze.cells[z][y][x], before.cells[z][y][x]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
-}
 
-pub struct Maze3DPlugin;
-impl Plugin for Maze3DPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_system(generate_maze3d_mesh)
-            .add_system_set(SystemSet::on_enter(AppState::Maze3D).with_system(setup_maze3d))
-            .add_system_set(SystemSet::on_exit(AppState::Maze3D).with_system(cleanup_maze3d));
+    #[test]
+    fn chunks_have_as_many_faces_as_the_whole_maze() {
+        let maze = Maze3D::hunt_and_kill(4, 3, 3, 5);
+        let size = [
+            maze.cells[0][0].len(),
+            maze.cells[0].len(),
+            maze.cells.len(),
+        ];
+
+        // one cell per chunk, so nothing gets merged and every wall face is its own quad
+        let whole = maze.chunk_geometry((0, 0, 0), (size[0], size[1], size[2]));
+        let mut faces = 0;
+
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    faces += maze
+                        .chunk_geometry((x, y, z), (x + 1, y + 1, z + 1))
+                        .indices
+                        .len()
+                        / 6;
+                }
+            }
+        }
+
+        let exposed: usize = (0..size[2])
+            .flat_map(|z| (0..size[1]).flat_map(move |y| (0..size[0]).map(move |x| (x, y, z))))
+            .filter(|&(x, y, z)| maze.cells[z][y][x])
+            .map(|(x, y, z)| {
+                6 - [
+                    (x.wrapping_sub(1), y, z),
+                    (x + 1, y, z),
+                    (x, y.wrapping_sub(1), z),
+                    (x, y + 1, z),
+                    (x, y, z.wrapping_sub(1)),
+                    (x, y, z + 1),
+                ]
+                .into_iter()
+                .filter(|&(x, y, z)| {
+                    z < size[2] && y < size[1] && x < size[0] && maze.cells[z][y][x]
+                })
+                .count()
+            })
+            .sum();
+
+        assert_eq!(faces, exposed);
+        assert!(whole.indices.len() / 6 <= exposed);
     }
 }
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use mazemazemaze::analysis::MazeStats;
+use mazemazemaze::maze3d::Maze3D;
+
+use crate::chunks::{update_chunks, MazeChunks};
+use crate::editor3d::EditedMaze3D;
+use crate::marker::{spawn_marker_at, Marker};
+use crate::state::AppState;
+use crate::win::{CurrentMazeStats, TargetDifficulty};
+use crate::{spawn_goal, Goal, Settings};
+
+#[derive(Bundle, Default)]
+pub struct Maze3DBundle {
+    pub maze: Maze3D,
+    pub chunks: MazeChunks,
+    pub material: Handle<StandardMaterial>,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub computed_visibility: ComputedVisibility,
+}
+
+fn paint_maze3d(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<&Handle<StandardMaterial>, Added<Maze3D>>,
+) {
+    for material_handle in query.iter() {
+        materials.set_untracked(
+            material_handle,
+            StandardMaterial {
+                base_color: Color::hex("0000ff").unwrap(),
+                perceptual_roughness: 0.9,
+                metallic: 0.0,
+                ..default()
+            },
+        );
+    }
+}
+
+fn setup_maze3d(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    target: Res<TargetDifficulty>,
+    settings: Res<Settings>,
+    mut edited: Option<ResMut<EditedMaze3D>>,
+) {
+    // play the maze from the editor if it asked for it, otherwise generate one
+    let (maze, markers) = match edited.as_deref_mut() {
+        Some(edited) if edited.playtest => {
+            edited.playtest = false;
+            (edited.maze.clone(), edited.markers.clone())
+        }
+        _ => (
+            settings
+                .generator
+                .rated_maze3d(target.0, fastrand::u64(..), settings.bias),
+            vec![],
+        ),
+    };
+
+    commands.insert_resource(CurrentMazeStats(MazeStats::of_maze3d(&maze)));
+
+    let goal = Vec3::from(maze.cell_translation(maze.goal));
+
+    for marker in markers {
+        let translation = Vec3::from(maze.cell_translation(marker));
+        spawn_marker_at(&mut commands, &mut meshes, &mut materials, translation);
+    }
+
+    // spawn maze
+    commands.spawn(Maze3DBundle { maze, ..default() });
+
+    // spawn goal
+    spawn_goal(&mut commands, &server, goal);
+}
+
+fn cleanup_maze3d(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Goal>, With<Maze3D>, With<Marker>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct Maze3DPlugin;
+impl Plugin for Maze3DPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_chunks::<Maze3D>)
+            .add_system(paint_maze3d)
+            .add_system_set(SystemSet::on_enter(AppState::Maze3D).with_system(setup_maze3d))
+            .add_system_set(SystemSet::on_exit(AppState::Maze3D).with_system(cleanup_maze3d));
+    }
+}
@@ -0,0 +1,621 @@
+use std::fmt;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::direction::Bias;
+use crate::maze2d::Maze2D;
+use crate::maze3d::Maze3D;
+
+pub const MAZE_FILE_VERSION: u32 = 3;
+
+// version 2 files are the same without a bias, and version 1 files don't have portals either
+const OLDEST_MAZE_FILE_VERSION: u32 = 1;
+
+// binary files start with this, anything else is parsed as json
+const BINARY_MAGIC: &[u8; 4] = b"MAZE";
+
+// On disk representation of a `Maze2D` or `Maze3D`. Positions are cell coordinates with one
+// entry per dimension, in the same order as `dimensions`.
+#[cfg_attr(
+    feature = "game",
+    derive(bevy::reflect::TypeUuid),
+    uuid = "5b1f4c3e-9a57-4a8e-8d0c-2f6b1e7a9c41"
+)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(try_from = "MazeFileV3")]
+pub struct MazeFile {
+    pub version: u32,
+
+    // logical size, [width, height] or [width, height, depth]
+    pub dimensions: Vec<usize>,
+
+    pub seed: u64,
+    pub generator: String,
+    #[serde(default)]
+    pub bias: Bias,
+
+    pub cells: Cells,
+
+    pub start: Vec<usize>,
+    pub goal: Vec<usize>,
+    pub markers: Vec<Vec<usize>>,
+
+    // only 2D mazes have portals
+    #[serde(default)]
+    pub portals: Vec<[Vec<usize>; 2]>,
+}
+
+#[derive(Deserialize)]
+struct MazeFileV1 {
+    // already checked, migrated files get the current version
+    _version: u32,
+    dimensions: Vec<usize>,
+    seed: u64,
+    generator: String,
+    cells: EncodedCells,
+    start: Vec<usize>,
+    goal: Vec<usize>,
+    markers: Vec<Vec<usize>>,
+}
+
+impl TryFrom<MazeFileV1> for MazeFile {
+    type Error = MazeFileError;
+
+    fn try_from(file: MazeFileV1) -> Result<Self, Self::Error> {
+        Ok(MazeFile {
+            version: MAZE_FILE_VERSION,
+            cells: file.cells.decode(&file.dimensions)?,
+            dimensions: file.dimensions,
+            seed: file.seed,
+            generator: file.generator,
+            bias: Bias::UNIFORM,
+            start: file.start,
+            goal: file.goal,
+            markers: file.markers,
+            portals: vec![],
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct MazeFileV2 {
+    // already checked, migrated files get the current version
+    _version: u32,
+    dimensions: Vec<usize>,
+    seed: u64,
+    generator: String,
+    cells: EncodedCells,
+    start: Vec<usize>,
+    goal: Vec<usize>,
+    markers: Vec<Vec<usize>>,
+    portals: Vec<[Vec<usize>; 2]>,
+}
+
+impl TryFrom<MazeFileV2> for MazeFile {
+    type Error = MazeFileError;
+
+    fn try_from(file: MazeFileV2) -> Result<Self, Self::Error> {
+        Ok(MazeFile {
+            version: MAZE_FILE_VERSION,
+            cells: file.cells.decode(&file.dimensions)?,
+            dimensions: file.dimensions,
+            seed: file.seed,
+            generator: file.generator,
+            bias: Bias::UNIFORM,
+            start: file.start,
+            goal: file.goal,
+            markers: file.markers,
+            portals: file.portals,
+        })
+    }
+}
+
+// The current layout, which `MazeFile` is read through so the cells can be checked against the
+// dimensions before they're unpacked. Older json files are read as this too, with the fields
+// they're missing left at their defaults.
+#[derive(Deserialize)]
+struct MazeFileV3 {
+    version: u32,
+    dimensions: Vec<usize>,
+    seed: u64,
+    generator: String,
+    #[serde(default)]
+    bias: Bias,
+    cells: EncodedCells,
+    start: Vec<usize>,
+    goal: Vec<usize>,
+    markers: Vec<Vec<usize>>,
+    #[serde(default)]
+    portals: Vec<[Vec<usize>; 2]>,
+}
+
+impl TryFrom<MazeFileV3> for MazeFile {
+    type Error = MazeFileError;
+
+    fn try_from(file: MazeFileV3) -> Result<Self, Self::Error> {
+        Ok(MazeFile {
+            version: file.version,
+            cells: file.cells.decode(&file.dimensions)?,
+            dimensions: file.dimensions,
+            seed: file.seed,
+            generator: file.generator,
+            bias: file.bias,
+            start: file.start,
+            goal: file.goal,
+            markers: file.markers,
+            portals: file.portals,
+        })
+    }
+}
+
+// Every cell of the maze flattened with x varying fastest. Bit-packed in binary files and run
+// length encoded in json, where runs alternate between walls and paths starting with walls.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cells(pub Vec<bool>);
+
+impl Serialize for Cells {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut runs = vec![];
+            let mut current = true;
+            let mut length = 0u64;
+
+            for &cell in self.0.iter() {
+                if cell != current {
+                    runs.push(length);
+                    current = cell;
+                    length = 0;
+                }
+
+                length += 1;
+            }
+
+            runs.push(length);
+            runs.serialize(serializer)
+        } else {
+            let mut bytes = vec![0u8; self.0.len().div_ceil(8)];
+
+            for (i, &cell) in self.0.iter().enumerate() {
+                if cell {
+                    bytes[i / 8] |= 1 << (i % 8);
+                }
+            }
+
+            (self.0.len() as u64, bytes).serialize(serializer)
+        }
+    }
+}
+
+// `Cells` as read from a file, left packed until the dimensions say how many there should be
+// so a single huge run can't take all the memory there is
+enum EncodedCells {
+    Runs(Vec<u64>),
+    Packed(u64, Vec<u8>),
+}
+
+impl<'de> Deserialize<'de> for EncodedCells {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(EncodedCells::Runs(Vec::deserialize(deserializer)?))
+        } else {
+            let (len, bytes) = <(u64, Vec<u8>)>::deserialize(deserializer)?;
+
+            if bytes.len() as u64 != len.div_ceil(8) {
+                return Err(D::Error::custom(format!(
+                    "expected {} bytes of cells, found {}",
+                    len.div_ceil(8),
+                    bytes.len()
+                )));
+            }
+
+            Ok(EncodedCells::Packed(len, bytes))
+        }
+    }
+}
+
+impl EncodedCells {
+    // fails as soon as there are more cells than `dimensions` has room for, too few are left for
+    // `MazeFile::validate` to report
+    fn decode(self, dimensions: &[usize]) -> Result<Cells, MazeFileError> {
+        let expected = dimensions
+            .iter()
+            .try_fold(1usize, |total, d| {
+                d.checked_mul(2)?.checked_add(1)?.checked_mul(total)
+            })
+            .ok_or_else(|| MazeFileError::TooLarge(dimensions.to_vec()))?;
+
+        let too_many = |found: u64| MazeFileError::WrongCellCount {
+            expected,
+            found: found.try_into().unwrap_or(usize::MAX),
+        };
+
+        match self {
+            EncodedCells::Runs(runs) => {
+                let mut cells = vec![];
+                let mut total = 0u64;
+                let mut current = true;
+
+                for run in runs {
+                    total = total.saturating_add(run);
+                    if total > expected as u64 {
+                        return Err(too_many(total));
+                    }
+
+                    cells.extend(std::iter::repeat_n(current, run as usize));
+                    current = !current;
+                }
+
+                Ok(Cells(cells))
+            }
+            EncodedCells::Packed(len, bytes) => {
+                if len > expected as u64 {
+                    return Err(too_many(len));
+                }
+
+                Ok(Cells(
+                    (0..len as usize)
+                        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+                        .collect(),
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MazeFileError {
+    UnsupportedVersion(u32),
+    WrongDimensions { expected: usize, found: usize },
+    WrongCellCount { expected: usize, found: usize },
+    OutOfBounds(Vec<usize>),
+    TooLarge(Vec<usize>),
+    Binary(bincode::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for MazeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeFileError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported maze file version {version}, expected {OLDEST_MAZE_FILE_VERSION} to {MAZE_FILE_VERSION}"
+            ),
+            MazeFileError::WrongDimensions { expected, found } => {
+                write!(f, "expected a {expected}D maze, found {found} dimensions")
+            }
+            MazeFileError::WrongCellCount { expected, found } => {
+                write!(f, "expected {expected} cells, found {found}")
+            }
+            MazeFileError::OutOfBounds(position) => {
+                write!(f, "position {position:?} is outside of the maze")
+            }
+            MazeFileError::TooLarge(dimensions) => {
+                write!(f, "a maze of {dimensions:?} cells is too large")
+            }
+            MazeFileError::Binary(e) => write!(f, "invalid binary maze file: {e}"),
+            MazeFileError::Json(e) => write!(f, "invalid json maze file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MazeFileError {}
+
+impl MazeFile {
+    pub fn from_maze2d(maze: &Maze2D, markers: &[(usize, usize)]) -> MazeFile {
+        MazeFile {
+            version: MAZE_FILE_VERSION,
+            dimensions: vec![maze.width, maze.height],
+            seed: maze.seed,
+            generator: maze.generator.clone(),
+            bias: maze.bias,
+            cells: Cells(maze.cells.iter().flatten().copied().collect()),
+            start: vec![maze.start.0, maze.start.1],
+            goal: vec![maze.goal.0, maze.goal.1],
+            markers: markers.iter().map(|&(x, y)| vec![x, y]).collect(),
+            portals: maze
+                .portals
+                .iter()
+                .map(|&[(ax, ay), (bx, by)]| [vec![ax, ay], vec![bx, by]])
+                .collect(),
+        }
+    }
+
+    pub fn from_maze3d(maze: &Maze3D, markers: &[(usize, usize, usize)]) -> MazeFile {
+        MazeFile {
+            version: MAZE_FILE_VERSION,
+            dimensions: vec![maze.width, maze.height, maze.depth],
+            seed: maze.seed,
+            generator: maze.generator.clone(),
+            bias: maze.bias,
+            cells: Cells(maze.cells.iter().flatten().flatten().copied().collect()),
+            start: vec![maze.start.0, maze.start.1, maze.start.2],
+            goal: vec![maze.goal.0, maze.goal.1, maze.goal.2],
+            markers: markers.iter().map(|&(x, y, z)| vec![x, y, z]).collect(),
+            portals: vec![],
+        }
+    }
+
+    pub fn to_maze2d(&self) -> Result<Maze2D, MazeFileError> {
+        self.validate(2)?;
+
+        let columns = self.dimensions[0] * 2 + 1;
+
+        Ok(Maze2D {
+            cells: self
+                .cells
+                .0
+                .chunks(columns)
+                .map(|row| row.to_vec())
+                .collect(),
+            width: self.dimensions[0],
+            height: self.dimensions[1],
+            seed: self.seed,
+            generator: self.generator.clone(),
+            bias: self.bias,
+            start: (self.start[0], self.start[1]),
+            goal: (self.goal[0], self.goal[1]),
+            portals: self
+                .portals
+                .iter()
+                .map(|[a, b]| [(a[0], a[1]), (b[0], b[1])])
+                .collect(),
+        })
+    }
+
+    pub fn to_maze3d(&self) -> Result<Maze3D, MazeFileError> {
+        self.validate(3)?;
+
+        let columns = self.dimensions[0] * 2 + 1;
+        let rows = self.dimensions[1] * 2 + 1;
+
+        Ok(Maze3D {
+            cells: self
+                .cells
+                .0
+                .chunks(columns * rows)
+                .map(|layer| layer.chunks(columns).map(|row| row.to_vec()).collect())
+                .collect(),
+            width: self.dimensions[0],
+            height: self.dimensions[1],
+            depth: self.dimensions[2],
+            seed: self.seed,
+            generator: self.generator.clone(),
+            bias: self.bias,
+            start: (self.start[0], self.start[1], self.start[2]),
+            goal: (self.goal[0], self.goal[1], self.goal[2]),
+        })
+    }
+
+    pub fn markers2d(&self) -> Vec<(usize, usize)> {
+        self.markers.iter().map(|m| (m[0], m[1])).collect()
+    }
+
+    pub fn markers3d(&self) -> Vec<(usize, usize, usize)> {
+        self.markers.iter().map(|m| (m[0], m[1], m[2])).collect()
+    }
+
+    pub fn to_binary(&self) -> Result<Vec<u8>, MazeFileError> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, self).map_err(MazeFileError::Binary)?;
+
+        Ok(bytes)
+    }
+
+    pub fn to_json(&self) -> Result<String, MazeFileError> {
+        serde_json::to_string(self).map_err(MazeFileError::Json)
+    }
+
+    // accepts either encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<MazeFile, MazeFileError> {
+        let mut file: MazeFile = if let Some(bytes) = bytes.strip_prefix(BINARY_MAGIC) {
+            // the version comes first, and binary files can't leave fields out
+            let version: u32 = bincode::deserialize(bytes).map_err(MazeFileError::Binary)?;
+
+            match version {
+                1 => bincode::deserialize::<MazeFileV1>(bytes)
+                    .map_err(MazeFileError::Binary)?
+                    .try_into()?,
+                2 => bincode::deserialize::<MazeFileV2>(bytes)
+                    .map_err(MazeFileError::Binary)?
+                    .try_into()?,
+                _ => bincode::deserialize(bytes).map_err(MazeFileError::Binary)?,
+            }
+        } else {
+            serde_json::from_slice(bytes).map_err(MazeFileError::Json)?
+        };
+
+        file.check_version()?;
+
+        // older json files are read straight into the current layout, missing fields and all, so
+        // they're saved as the current version from now on too
+        file.version = MAZE_FILE_VERSION;
+
+        Ok(file)
+    }
+
+    fn check_version(&self) -> Result<(), MazeFileError> {
+        if !(OLDEST_MAZE_FILE_VERSION..=MAZE_FILE_VERSION).contains(&self.version) {
+            return Err(MazeFileError::UnsupportedVersion(self.version));
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self, dimensions: usize) -> Result<(), MazeFileError> {
+        self.check_version()?;
+
+        if self.dimensions.len() != dimensions {
+            return Err(MazeFileError::WrongDimensions {
+                expected: dimensions,
+                found: self.dimensions.len(),
+            });
+        }
+
+        let expected = self.dimensions.iter().map(|d| d * 2 + 1).product();
+        if self.cells.0.len() != expected {
+            return Err(MazeFileError::WrongCellCount {
+                expected,
+                found: self.cells.0.len(),
+            });
+        }
+
+        let positions = [&self.start, &self.goal]
+            .into_iter()
+            .chain(&self.markers)
+            .chain(self.portals.iter().flatten());
+
+        for position in positions {
+            let in_bounds = position.len() == dimensions
+                && position
+                    .iter()
+                    .zip(&self.dimensions)
+                    .all(|(p, d)| *p < d * 2 + 1);
+
+            if !in_bounds {
+                return Err(MazeFileError::OutOfBounds(position.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 9x7 and 5x5x7 cells, neither of which packs into whole bytes
+    fn files() -> [MazeFile; 2] {
+        let maze2d = Maze2D::hunt_and_kill(4, 3, 7);
+        let maze3d = Maze3D::hunt_and_kill(2, 2, 3, 7);
+
+        [
+            MazeFile::from_maze2d(&maze2d, &[(1, 3), (5, 5)]),
+            MazeFile::from_maze3d(&maze3d, &[(3, 1, 5)]),
+        ]
+    }
+
+    #[test]
+    fn binary_files_round_trip() {
+        for file in files() {
+            assert!(file.cells.0.len() % 8 != 0);
+
+            let bytes = file.to_binary().unwrap();
+            assert_eq!(MazeFile::from_bytes(&bytes).unwrap(), file);
+        }
+    }
+
+    #[test]
+    fn json_files_round_trip() {
+        for file in files() {
+            let json = file.to_json().unwrap();
+            assert_eq!(MazeFile::from_bytes(json.as_bytes()).unwrap(), file);
+        }
+    }
+
+    #[test]
+    fn files_round_trip_back_to_the_same_mazes() {
+        let [file2d, file3d] = files();
+
+        assert_eq!(
+            MazeFile::from_maze2d(&file2d.to_maze2d().unwrap(), &file2d.markers2d()),
+            file2d
+        );
+        assert_eq!(
+            MazeFile::from_maze3d(&file3d.to_maze3d().unwrap(), &file3d.markers3d()),
+            file3d
+        );
+    }
+
+    #[test]
+    fn cells_starting_with_a_path_start_with_an_empty_run_of_walls() {
+        let cells = Cells(vec![false, false, true, true, true, false]);
+        let json = serde_json::to_string(&cells).unwrap();
+
+        assert_eq!(json, "[0,2,3,1]");
+        let decoded = serde_json::from_str::<EncodedCells>(&json).unwrap();
+        assert_eq!(decoded.decode(&[1, 1]).unwrap(), cells);
+    }
+
+    #[test]
+    fn binary_cells_need_exactly_enough_bytes() {
+        let cells = Cells(vec![true; 9]);
+        let mut bytes = bincode::serialize(&cells).unwrap();
+
+        let decoded = bincode::deserialize::<EncodedCells>(&bytes).unwrap();
+        assert_eq!(decoded.decode(&[1, 1]).unwrap(), cells);
+
+        bytes.pop();
+        assert!(bincode::deserialize::<EncodedCells>(&bytes).is_err());
+    }
+
+    #[test]
+    fn the_first_maze_in_assets_loads() {
+        let file = MazeFile::from_bytes(include_bytes!("../assets/first.maze")).unwrap();
+        let maze = file.to_maze2d().unwrap();
+
+        // what it was searched for with, see `maze2d_plugin::FIRST_MAZE`
+        let stats = crate::analysis::MazeStats::of_maze2d(&maze);
+        assert!((24..=30).contains(&stats.solution_length));
+        assert!((4..=6).contains(&stats.dead_ends));
+    }
+
+    #[test]
+    fn oversized_runs_are_rejected_before_they_are_unpacked() {
+        let mut json = serde_json::to_value(&files()[0]).unwrap();
+        json["cells"] = serde_json::json!([0, u64::MAX]);
+
+        let error = MazeFile::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("expected 63 cells"), "{error}");
+
+        // one cell too many is caught the same way, across runs as well as within one
+        json["cells"] = serde_json::json!([60, 2, 2]);
+        assert!(MazeFile::from_bytes(&serde_json::to_vec(&json).unwrap()).is_err());
+
+        json["cells"] = serde_json::json!([60, 3]);
+        assert!(MazeFile::from_bytes(&serde_json::to_vec(&json).unwrap()).is_ok());
+
+        json["dimensions"] = serde_json::json!([usize::MAX, 2]);
+        let error = MazeFile::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn old_files_are_saved_as_the_current_version() {
+        let file = MazeFile::from_maze2d(&Maze2D::hunt_and_kill(4, 3, 7), &[(1, 3)]);
+
+        // bincode lays structs out as their fields in order, so tuples can stand in for them
+        let fields = (
+            &file.dimensions,
+            file.seed,
+            &file.generator,
+            &file.cells,
+            &file.start,
+            &file.goal,
+            &file.markers,
+        );
+
+        let mut v1 = BINARY_MAGIC.to_vec();
+        bincode::serialize_into(&mut v1, &(1u32, fields)).unwrap();
+
+        let mut v2 = BINARY_MAGIC.to_vec();
+        bincode::serialize_into(&mut v2, &(2u32, fields, &file.portals)).unwrap();
+
+        let mut json = serde_json::to_value(&file).unwrap();
+        json["version"] = 1.into();
+        json.as_object_mut().unwrap().remove("bias");
+        json.as_object_mut().unwrap().remove("portals");
+
+        for old in [v1, v2, serde_json::to_vec(&json).unwrap()] {
+            let migrated = MazeFile::from_bytes(&old).unwrap();
+            assert_eq!(migrated, file);
+
+            let binary = migrated.to_binary().unwrap();
+            let json = migrated.to_json().unwrap();
+
+            assert_eq!(MazeFile::from_bytes(&binary).unwrap(), file);
+            assert_eq!(MazeFile::from_bytes(json.as_bytes()).unwrap(), file);
+        }
+    }
+}
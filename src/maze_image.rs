@@ -0,0 +1,433 @@
+use std::fmt::{self, Write as _};
+use std::io::Cursor;
+
+use image::{ImageOutputFormat, Rgb, RgbImage};
+
+use crate::maze2d::Maze2D;
+
+const WALL: [u8; 3] = [0x00, 0x00, 0x00];
+const FLOOR: [u8; 3] = [0xff, 0xff, 0xff];
+const SOLUTION: [u8; 3] = [0xff, 0x00, 0x00];
+const MARKER: [u8; 3] = [0xff, 0xff, 0x00];
+const START: [u8; 3] = [0x00, 0xaa, 0x00];
+const GOAL: [u8; 3] = [0x00, 0x00, 0xff];
+
+// Top down picture of a `Maze2D` for printing, with one square block per cell of `Maze2D::cells`.
+// Later layers are drawn over earlier ones: walls, solution, markers, then start and goal.
+pub struct MazeImage<'a> {
+    maze: &'a Maze2D,
+    solution: Option<&'a [(usize, usize)]>,
+    markers: &'a [(usize, usize)],
+}
+
+impl<'a> MazeImage<'a> {
+    pub fn new(maze: &'a Maze2D) -> Self {
+        MazeImage {
+            maze,
+            solution: None,
+            markers: &[],
+        }
+    }
+
+    pub fn with_solution(mut self, solution: &'a [(usize, usize)]) -> Self {
+        self.solution = Some(solution);
+        self
+    }
+
+    pub fn with_markers(mut self, markers: &'a [(usize, usize)]) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    fn columns(&self) -> usize {
+        self.maze.cells.first().map_or(0, |row| row.len())
+    }
+
+    fn rows(&self) -> usize {
+        self.maze.cells.len()
+    }
+
+    // every cell that is not a plain wall or floor, in drawing order
+    fn overlays(&self) -> impl Iterator<Item = ((usize, usize), [u8; 3])> + '_ {
+        let solution = self.solution.unwrap_or_default();
+
+        solution
+            .iter()
+            .map(|&cell| (cell, SOLUTION))
+            .chain(self.markers.iter().map(|&cell| (cell, MARKER)))
+            .chain([(self.maze.start, START), (self.maze.goal, GOAL)])
+    }
+
+    // exactly `cell_size` by `cell_size` pixels per cell
+    pub fn to_rgb(&self, cell_size: u32) -> RgbImage {
+        let mut image = RgbImage::new(
+            self.columns() as u32 * cell_size,
+            self.rows() as u32 * cell_size,
+        );
+
+        let mut fill = |(x, y): (usize, usize), color: [u8; 3]| {
+            for py in 0..cell_size {
+                for px in 0..cell_size {
+                    image.put_pixel(
+                        x as u32 * cell_size + px,
+                        y as u32 * cell_size + py,
+                        Rgb(color),
+                    );
+                }
+            }
+        };
+
+        for (y, row) in self.maze.cells.iter().enumerate() {
+            for (x, &wall) in row.iter().enumerate() {
+                fill((x, y), if wall { WALL } else { FLOOR });
+            }
+        }
+
+        for (cell, color) in self.overlays() {
+            fill(cell, color);
+        }
+
+        image
+    }
+
+    pub fn to_png(&self, cell_size: u32) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        self.to_rgb(cell_size)
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .expect("encoding a png in memory can't fail");
+
+        bytes
+    }
+
+    // one unit per cell, scaled to `cell_size` pixels per cell when displayed
+    pub fn to_svg(&self, cell_size: u32) -> String {
+        let (columns, rows) = (self.columns(), self.rows());
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {columns} {rows}" shape-rendering="crispEdges">"#,
+            columns as u32 * cell_size,
+            rows as u32 * cell_size,
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<rect width="{columns}" height="{rows}" fill="{}"/>"#,
+            hex(FLOOR)
+        )
+        .unwrap();
+
+        // merge horizontal runs of walls to keep the file small
+        for (y, row) in self.maze.cells.iter().enumerate() {
+            let mut x = 0;
+
+            while x < row.len() {
+                if row[x] {
+                    let start = x;
+                    while x < row.len() && row[x] {
+                        x += 1;
+                    }
+
+                    writeln!(
+                        svg,
+                        r#"<rect x="{start}" y="{y}" width="{}" height="1" fill="{}"/>"#,
+                        x - start,
+                        hex(WALL)
+                    )
+                    .unwrap();
+                } else {
+                    x += 1;
+                }
+            }
+        }
+
+        if let Some(solution) = self.solution {
+            let points: Vec<String> = solution
+                .iter()
+                .map(|(x, y)| format!("{}.5,{}.5", x, y))
+                .collect();
+
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="0.4" stroke-linecap="square" stroke-linejoin="miter"/>"#,
+                points.join(" "),
+                hex(SOLUTION)
+            )
+            .unwrap();
+        }
+
+        for (x, y) in self.markers.iter() {
+            writeln!(
+                svg,
+                r#"<circle cx="{x}.5" cy="{y}.5" r="0.4" fill="{}"/>"#,
+                hex(MARKER)
+            )
+            .unwrap();
+        }
+
+        for ((x, y), color) in [(self.maze.start, START), (self.maze.goal, GOAL)] {
+            writeln!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="1" height="1" fill="{}"/>"#,
+                hex(color)
+            )
+            .unwrap();
+        }
+
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[derive(Debug)]
+pub enum ImageImportError {
+    Decode(image::ImageError),
+    EvenSize { width: u32, height: u32 },
+    TooSmall { width: u32, height: u32 },
+    OpenBorder { x: usize, y: usize },
+    BlockedCell { x: usize, y: usize },
+}
+
+impl fmt::Display for ImageImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageImportError::Decode(e) => write!(f, "could not decode image: {e}"),
+            ImageImportError::EvenSize { width, height } => {
+                write!(f, "image is {width}x{height}, but both sides must be odd")
+            }
+            ImageImportError::TooSmall { width, height } => {
+                write!(f, "image is {width}x{height}, but must be at least 3x3")
+            }
+            ImageImportError::OpenBorder { x, y } => {
+                write!(f, "cell ({x}, {y}) on the border of the image is open")
+            }
+            ImageImportError::BlockedCell { x, y } => {
+                write!(f, "the start or goal at ({x}, {y}) is inside a wall")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImageImportError {}
+
+pub struct ImportedMaze {
+    pub maze: Maze2D,
+
+    // open cells that can't be reached from the start, see `Maze2D::unreachable_regions`
+    pub unreachable: Vec<Vec<(usize, usize)>>,
+}
+
+// Reads a maze drawn with one pixel per cell of `Maze2D::cells`, like the output of
+// `MazeImage::to_png(1)`. Dark pixels are walls, except for the colors the renderer draws on top
+// of paths. The start and goal can be marked with their colors, otherwise they default to
+// opposite corners.
+pub fn import_png(bytes: &[u8]) -> Result<ImportedMaze, ImageImportError> {
+    let image = image::load_from_memory(bytes)
+        .map_err(ImageImportError::Decode)?
+        .to_rgb8();
+
+    let (width, height) = image.dimensions();
+    if width % 2 == 0 || height % 2 == 0 {
+        return Err(ImageImportError::EvenSize { width, height });
+    }
+
+    if width < 3 || height < 3 {
+        return Err(ImageImportError::TooSmall { width, height });
+    }
+
+    let mut maze = Maze2D {
+        cells: vec![vec![true; width as usize]; height as usize],
+        width: width as usize / 2,
+        height: height as usize / 2,
+        generator: "image".to_string(),
+        start: (1, 1),
+        goal: (width as usize - 2, height as usize - 2),
+        ..Default::default()
+    };
+
+    for (x, y, &Rgb(color)) in image.enumerate_pixels() {
+        let (x, y) = (x as usize, y as usize);
+
+        if color == START {
+            maze.start = (x, y);
+        } else if color == GOAL {
+            maze.goal = (x, y);
+        }
+
+        let [r, g, b] = color.map(f32::from);
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+        // the solution is darker than a lot of wall colors, but it's only ever drawn on paths
+        maze.cells[y][x] = luminance < 128.0 && ![SOLUTION, MARKER, START, GOAL].contains(&color);
+
+        let on_border = x == 0 || y == 0 || x == width as usize - 1 || y == height as usize - 1;
+        if on_border && !maze.cells[y][x] {
+            return Err(ImageImportError::OpenBorder { x, y });
+        }
+    }
+
+    for (x, y) in [maze.start, maze.goal] {
+        if maze.cells[y][x] {
+            return Err(ImageImportError::BlockedCell { x, y });
+        }
+    }
+
+    let unreachable = maze.unreachable_regions();
+
+    Ok(ImportedMaze { maze, unreachable })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const SEEDS: [u64; 3] = [1, 2, 3];
+    const CELL_SIZE: u32 = 4;
+
+    // Compares against `tests/golden/<name>`. Running with `UPDATE_GOLDEN=1` writes the current
+    // output there instead, check the differences before committing them.
+    fn assert_golden(name: &str, bytes: &[u8]) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, bytes).unwrap();
+            return;
+        }
+
+        let golden = std::fs::read(&path)
+            .unwrap_or_else(|e| panic!("could not read {}: {e}", path.display()));
+
+        assert!(golden == bytes, "{name} doesn't match its golden file");
+    }
+
+    fn maze(seed: u64) -> Maze2D {
+        Maze2D::hunt_and_kill(6, 5, seed)
+    }
+
+    fn png(image: &RgbImage) -> Vec<u8> {
+        let mut bytes = vec![];
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .unwrap();
+
+        bytes
+    }
+
+    // walls all around the edge and nothing inside
+    fn room(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                Rgb(WALL)
+            } else {
+                Rgb(FLOOR)
+            }
+        })
+    }
+
+    #[test]
+    fn svgs_match_their_golden_files() {
+        for seed in SEEDS {
+            let maze = maze(seed);
+            let solution = maze.solve().unwrap();
+            let markers = [(1, 3), (5, 1)];
+
+            let plain = MazeImage::new(&maze).with_markers(&markers);
+            assert_golden(&format!("{seed}.svg"), plain.to_svg(CELL_SIZE).as_bytes());
+
+            let solved = MazeImage::new(&maze)
+                .with_markers(&markers)
+                .with_solution(&solution);
+            assert_golden(
+                &format!("{seed}_solution.svg"),
+                solved.to_svg(CELL_SIZE).as_bytes(),
+            );
+        }
+    }
+
+    #[test]
+    fn pngs_match_their_golden_files() {
+        for seed in SEEDS {
+            let maze = maze(seed);
+            let solution = maze.solve().unwrap();
+            let markers = [(1, 3), (5, 1)];
+
+            let plain = MazeImage::new(&maze).with_markers(&markers);
+            assert_golden(&format!("{seed}.png"), &plain.to_png(CELL_SIZE));
+
+            let solved = MazeImage::new(&maze)
+                .with_markers(&markers)
+                .with_solution(&solution);
+            assert_golden(&format!("{seed}_solution.png"), &solved.to_png(CELL_SIZE));
+        }
+    }
+
+    #[test]
+    fn exported_pngs_import_as_the_same_maze() {
+        for seed in SEEDS {
+            let maze = maze(seed);
+            let solution = maze.solve().unwrap();
+            let markers = [(1, 3), (5, 1)];
+
+            let bytes = MazeImage::new(&maze)
+                .with_markers(&markers)
+                .with_solution(&solution)
+                .to_png(1);
+            let imported = import_png(&bytes).unwrap();
+
+            assert_eq!(imported.maze.cells, maze.cells);
+            assert_eq!(imported.maze.start, maze.start);
+            assert_eq!(imported.maze.goal, maze.goal);
+            assert!(imported.unreachable.is_empty());
+        }
+    }
+
+    #[test]
+    fn even_sized_images_are_rejected() {
+        assert!(matches!(
+            import_png(&png(&room(6, 5))),
+            Err(ImageImportError::EvenSize {
+                width: 6,
+                height: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn open_borders_are_rejected() {
+        let mut image = room(5, 5);
+        image.put_pixel(4, 2, Rgb(FLOOR));
+
+        assert!(matches!(
+            import_png(&png(&image)),
+            Err(ImageImportError::OpenBorder { x: 4, y: 2 })
+        ));
+    }
+
+    #[test]
+    fn walled_off_regions_are_unreachable() {
+        let mut image = room(7, 5);
+        for y in 0..5 {
+            image.put_pixel(3, y, Rgb(WALL));
+        }
+
+        let imported = import_png(&png(&image)).unwrap();
+
+        assert_eq!(imported.unreachable.len(), 1);
+        assert_eq!(imported.unreachable[0].len(), 6);
+
+        // the goal defaults to the far corner, on the other side of the wall
+        assert!(imported.maze.solve().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use mazemazemaze::analysis::MazeStats;
+use mazemazemaze::maze_file::MazeFile;
+use mazemazemaze::maze_image;
+
+use crate::marker::spawn_marker_at;
+use crate::maze2d_plugin::Maze2DBundle;
+use crate::maze3d_plugin::Maze3DBundle;
+use crate::portal::spawn_portal;
+use crate::spawn_goal;
+use crate::win::CurrentMazeStats;
+
+// drawn mazes, see `maze_image::import_png`
+const PNG_MAGIC: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Default)]
+pub struct MazeFileLoader;
+
+impl AssetLoader for MazeFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file = if bytes.starts_with(PNG_MAGIC) {
+                let imported = maze_image::import_png(bytes)?;
+
+                for region in imported.unreachable.iter() {
+                    warn!(
+                        "{} cells starting at {:?} can't be reached from the start",
+                        region.len(),
+                        region[0]
+                    );
+                }
+
+                MazeFile::from_maze2d(&imported.maze, &[])
+            } else {
+                MazeFile::from_bytes(bytes)?
+            };
+
+            load_context.set_default_asset(LoadedAsset::new(file));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["maze", "maze.png"]
+    }
+}
+
+// Replaced with a `Maze2DBundle` or `Maze3DBundle` once the file has loaded, along with the
+// goal and any markers and portals saved in it. The entity's `Transform` is kept, and the maze
+// becomes the one being played.
+#[derive(Component)]
+pub struct PendingMaze(pub Handle<MazeFile>);
+
+fn spawn_pending_mazes(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    files: Res<Assets<MazeFile>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<(Entity, &PendingMaze, Option<&Transform>)>,
+) {
+    for (entity, pending, transform) in query.iter() {
+        if let Some(file) = files.get(&pending.0) {
+            commands.entity(entity).remove::<PendingMaze>();
+
+            let transform = transform.copied().unwrap_or_default();
+
+            let spawned = match file.dimensions.len() {
+                3 => file.to_maze3d().map(|maze| {
+                    let goal = Vec3::from(maze.cell_translation(maze.goal));
+                    let markers: Vec<_> = file
+                        .markers3d()
+                        .into_iter()
+                        .map(|marker| Vec3::from(maze.cell_translation(marker)))
+                        .collect();
+
+                    commands.insert_resource(CurrentMazeStats(MazeStats::of_maze3d(&maze)));
+                    commands.entity(entity).insert(Maze3DBundle {
+                        maze,
+                        transform,
+                        ..default()
+                    });
+
+                    (goal, markers, vec![])
+                }),
+                _ => file.to_maze2d().map(|maze| {
+                    let goal = Vec3::from(maze.cell_translation(maze.goal));
+                    let markers: Vec<_> = file
+                        .markers2d()
+                        .into_iter()
+                        .map(|marker| Vec3::from(maze.cell_translation(marker)))
+                        .collect();
+                    let portals: Vec<_> = maze
+                        .portals
+                        .iter()
+                        .flatten()
+                        .map(|&cell| Vec3::from(maze.cell_translation(cell)))
+                        .collect();
+
+                    commands.insert_resource(CurrentMazeStats(MazeStats::of_maze2d(&maze)));
+                    commands.entity(entity).insert(Maze2DBundle {
+                        maze,
+                        transform,
+                        ..default()
+                    });
+
+                    (goal, markers, portals)
+                }),
+            };
+
+            match spawned {
+                Ok((goal, markers, portals)) => {
+                    spawn_goal(&mut commands, &server, transform.translation + goal);
+
+                    for marker in markers {
+                        spawn_marker_at(
+                            &mut commands,
+                            &mut meshes,
+                            &mut materials,
+                            transform.translation + marker,
+                        );
+                    }
+
+                    for portal in portals {
+                        spawn_portal(
+                            &mut commands,
+                            &mut meshes,
+                            &mut materials,
+                            transform.translation + portal,
+                        );
+                    }
+                }
+                Err(e) => error!("failed to spawn maze: {e}"),
+            }
+        }
+    }
+}
+
+pub struct MazeFilePlugin;
+impl Plugin for MazeFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<MazeFile>()
+            .init_asset_loader::<MazeFileLoader>()
+            .add_system(spawn_pending_mazes);
+    }
+}
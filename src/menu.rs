@@ -1,19 +1,42 @@
 use std::f32::consts::PI;
 
 use bevy::prelude::*;
+use bevy::window::CursorMoved;
 use bevy_rapier3d::prelude::*;
+use mazemazemaze::direction::Bias;
+use mazemazemaze::generator::Generator;
+use mazemazemaze::hunt_and_kill::HuntAndKill2D;
 
 use crate::{
+    controls::{gamepad_just_pressed, Action, Controls},
     filter::FilterCamera,
-    maze2d::{Maze2D, Maze2DBundle},
+    generation::Generating2D,
+    gravity::{toggle_gravity_mode, GravityMode},
+    maze2d_plugin::Maze2DBundle,
     state::AppState,
+    Settings,
 };
 
 pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(setup_menu))
-            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(menu))
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(menu)
+                    .with_system(menu_gamepad.after(menu)),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(cycle_generator)
+                    .with_system(cycle_bias)
+                    .with_system(
+                        update_settings_text
+                            .after(cycle_generator)
+                            .after(cycle_bias)
+                            .after(toggle_gravity_mode),
+                    ),
+            )
             .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(cleanup_menu));
     }
 }
@@ -21,6 +44,7 @@ impl Plugin for MenuPlugin {
 #[derive(Resource)]
 struct MenuData {
     root: Entity,
+    settings: Entity,
 }
 
 #[derive(Component)]
@@ -29,7 +53,31 @@ struct MenuCamera;
 #[derive(Component)]
 struct CursorLight;
 
+// what new mazes are generated with, along with how to change it
 #[derive(Component)]
+struct SettingsText;
+
+fn settings_text(settings: &Settings, gravity: &GravityMode, controls: &Controls) -> String {
+    let bias = match settings.bias {
+        bias if bias == Bias::UNIFORM => "uniform",
+        bias if bias == Bias::HORIZONTAL => "horizontal",
+        bias if bias == Bias::VERTICAL => "vertical",
+        _ => "custom",
+    };
+
+    let gravity_mode = if gravity.enabled { "on" } else { "off" };
+
+    format!(
+        "generator  {:<16}D or west\nbias       {:<16}B or north\ngravity    {:<16}{} or {}",
+        settings.generator.name(),
+        format!("{bias} {}", settings.bias),
+        gravity_mode,
+        controls.binding(Action::ToggleGravity),
+        format!("{:?}", controls.gamepad_binding(Action::ToggleGravity)).to_lowercase()
+    )
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
 enum TitleButton {
     Maze3D,
     Maze2D,
@@ -38,8 +86,10 @@ enum TitleButton {
 fn setup_menu(
     mut commands: Commands,
     server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<Settings>,
+    gravity: Res<GravityMode>,
+    controls: Res<Controls>,
 ) {
     let root = commands
         .spawn(TransformBundle::default())
@@ -104,25 +154,57 @@ fn setup_menu(
                 .insert(Collider::cuboid(4.0, 5.0, 35.0))
                 .insert(TitleButton::Maze2D);
 
-            parent.spawn(Maze2DBundle {
-                maze: Maze2D::hunt_and_kill(18, 32),
+            let generating = Generating2D::new(
+                HuntAndKill2D::new(18, 32, fastrand::u64(..), Bias::UNIFORM),
+                120.0,
+            );
+
+            parent
+                .spawn(Maze2DBundle {
+                    maze: generating.maze(),
 
-                // We need to preset a mesh here don't ask
-                mesh: meshes.add(shape::Cube::new(50.0).into()),
+                    material: materials.add(Color::hex("ffff00").unwrap().into()),
 
-                material: materials.add(Color::hex("ffff00").unwrap().into()),
+                    transform: Transform {
+                        translation: Vec3::new(-20.0, -4.0, -30.0),
+                        scale: Vec3::new(0.2, 0.2, 0.2),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(generating);
+        })
+        .id();
 
-                transform: Transform {
-                    translation: Vec3::new(-10.0, 0.0, -20.0),
-                    scale: Vec3::new(0.2, 0.2, 0.2),
+    // the window title can't be seen in fullscreen or on the web, see `win::setup_win`
+    let settings = commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
                     ..default()
                 },
                 ..default()
-            });
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    settings_text(&settings, &gravity, &controls),
+                    TextStyle {
+                        font: server.load("DejaVuSansMono.ttf"),
+                        font_size: 20.0,
+                        color: Color::GRAY,
+                    },
+                ))
+                .insert(SettingsText);
         })
         .id();
 
-    commands.insert_resource(MenuData { root });
+    commands.insert_resource(MenuData { root, settings });
 }
 
 fn menu(
@@ -178,16 +260,7 @@ fn menu(
                         window.set_cursor_icon(CursorIcon::Hand);
 
                         if buttons.just_released(MouseButton::Left) {
-                            match button {
-                                TitleButton::Maze3D => {
-                                    bevy::log::info!("3D Maze Mode");
-                                    state.set(AppState::Maze3D).unwrap();
-                                }
-                                TitleButton::Maze2D => {
-                                    bevy::log::info!("2D Maze Mode");
-                                    state.set(AppState::Maze2D).unwrap();
-                                }
-                            }
+                            press(*button, &mut state);
                         }
                     }
                 }
@@ -196,6 +269,117 @@ fn menu(
     }
 }
 
+fn press(button: TitleButton, state: &mut State<AppState>) {
+    match button {
+        TitleButton::Maze3D => {
+            bevy::log::info!("3D Maze Mode");
+            state.set(AppState::Maze3D).unwrap();
+        }
+        TitleButton::Maze2D => {
+            bevy::log::info!("2D Maze Mode");
+            state.set(AppState::Maze2D).unwrap();
+        }
+    }
+}
+
+// Up and down on the d-pad pick a button and south presses it. Moving the mouse hands the menu
+// back to `menu`.
+fn menu_gamepad(
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut selected: Local<Option<TitleButton>>,
+    mut state: ResMut<State<AppState>>,
+    mut light_query: Query<&mut Transform, (With<CursorLight>, Without<TitleButton>)>,
+    mut title_text_query: Query<(&mut Transform, &TitleButton), Without<CursorLight>>,
+) {
+    let pressed = |button| gamepad_just_pressed(&gamepads, &gamepad_buttons, button);
+
+    if cursor_moved.iter().count() > 0 {
+        *selected = None;
+    }
+
+    if pressed(GamepadButtonType::DPadUp) {
+        *selected = Some(TitleButton::Maze3D);
+    } else if pressed(GamepadButtonType::DPadDown) {
+        *selected = Some(TitleButton::Maze2D);
+    }
+
+    let Some(selected) = *selected else {
+        return;
+    };
+
+    for (mut transform, &button) in title_text_query.iter_mut() {
+        if button == selected {
+            transform.scale = Vec3::splat(0.2);
+
+            for mut light_transform in light_query.iter_mut() {
+                light_transform.translation = transform.translation + Vec3::new(0.0, 10.0, 0.0);
+            }
+        } else {
+            transform.scale = Vec3::splat(0.05);
+        }
+    }
+
+    if pressed(GamepadButtonType::South) {
+        press(selected, &mut state);
+    }
+}
+
+fn cycle_generator(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut settings: ResMut<Settings>,
+) {
+    if keys.just_pressed(KeyCode::D)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::West)
+    {
+        let next = Generator::ALL
+            .iter()
+            .position(|&generator| generator == settings.generator)
+            .map_or(0, |i| (i + 1) % Generator::ALL.len());
+
+        settings.generator = Generator::ALL[next];
+        info!("generator: {}", settings.generator.name());
+    }
+}
+
+fn cycle_bias(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut settings: ResMut<Settings>,
+) {
+    if keys.just_pressed(KeyCode::B)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::North)
+    {
+        let next = Bias::PRESETS
+            .iter()
+            .position(|&bias| bias == settings.bias)
+            .map_or(0, |i| (i + 1) % Bias::PRESETS.len());
+
+        settings.bias = Bias::PRESETS[next];
+        info!("bias: {}", settings.bias);
+    }
+}
+
+fn update_settings_text(
+    settings: Res<Settings>,
+    gravity: Res<GravityMode>,
+    controls: Res<Controls>,
+    mut query: Query<&mut Text, With<SettingsText>>,
+) {
+    if !settings.is_changed() && !gravity.is_changed() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = settings_text(&settings, &gravity, &controls);
+    }
+}
+
 fn cleanup_menu(mut commands: Commands, menu_data: Res<MenuData>) {
     commands.entity(menu_data.root).despawn_recursive();
+    commands.entity(menu_data.settings).despawn_recursive();
 }
@@ -0,0 +1,330 @@
+use bevy::prelude::*;
+use bevy::time::TimeSystem;
+use bevy::transform::TransformSystem;
+
+// Players are simulated in fixed steps, so the same inputs end up in the same place at any frame
+// rate. What's on screen is interpolated between the last two steps.
+pub const TIMESTEP: f32 = 1.0 / 60.0;
+
+// frames longer than this slow the game down instead of running a burst of steps to catch up
+const MAX_FRAME: f32 = 0.25;
+
+#[derive(Resource, Default)]
+pub struct PhysicsClock {
+    accumulator: f32,
+
+    // steps to run this frame
+    pub steps: u32,
+}
+
+impl PhysicsClock {
+    // adds a frame's worth of time and returns how many steps are due
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        self.accumulator += delta.min(MAX_FRAME);
+        self.steps = (self.accumulator / TIMESTEP) as u32;
+        self.accumulator = (self.accumulator - self.steps as f32 * TIMESTEP).max(0.0);
+
+        self.steps
+    }
+
+    // how far it is from the last step to the next one, from 0.0 to 1.0
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / TIMESTEP).min(1.0)
+    }
+}
+
+// Where a simulated body was after each of the last two steps. Its `Transform` is only for
+// showing it and gets overwritten, anything that moves it has to go through here.
+#[derive(Component)]
+pub struct PhysicsPosition {
+    pub previous: Vec3,
+    pub current: Vec3,
+}
+
+impl PhysicsPosition {
+    pub fn new(translation: Vec3) -> Self {
+        PhysicsPosition {
+            previous: translation,
+            current: translation,
+        }
+    }
+
+    // moves without interpolating, like spawning or going through a portal
+    pub fn teleport(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
+    }
+}
+
+// What a shape cast ran into, `toi` is how far along the motion it was as a fraction of it.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub toi: f32,
+    pub normal: Vec3,
+}
+
+// How bodies slide along what they run into, see `slide`.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SlideSettings {
+    // how many times a motion gets slid along something before the rest of it is given up on
+    pub max_slides: u32,
+
+    // how far short of whatever they run into bodies stop, so the next cast doesn't start off
+    // touching it
+    pub skin: f32,
+
+    // how far casts look as a fraction of the motion, more than 1.0 only finds hits that `slide`
+    // ignores as they aren't in the way yet
+    pub max_toi: f32,
+}
+
+impl Default for SlideSettings {
+    fn default() -> Self {
+        SlideSettings {
+            max_slides: 4,
+            skin: 0.01,
+            max_toi: 1.0,
+        }
+    }
+}
+
+// Moves `position` by `motion`, sliding along anything in the way. `cast` looks for something in
+// the way of a motion from a position.
+//
+// Each hit moves the body up to it, short by the skin, and what's left of the motion goes along
+// it instead. Running into a second surface while sliding along the first goes along the crease
+// between them, so a body pushed into a crevice doesn't bounce from side to side.
+pub fn slide(
+    position: &mut Vec3,
+    motion: Vec3,
+    settings: &SlideSettings,
+    mut cast: impl FnMut(Vec3, Vec3) -> Option<Hit>,
+) {
+    let mut motion = motion;
+    let mut previous_normal: Option<Vec3> = None;
+
+    for _ in 0..settings.max_slides {
+        let length = motion.length();
+
+        if length <= f32::EPSILON {
+            return;
+        }
+
+        // looks a skin further so that stopping a skin short of a hit is still before it
+        let direction = motion / length;
+        let reach = length + settings.skin;
+
+        let Some(hit) = cast(*position, direction * reach).filter(|hit| hit.toi <= 1.0) else {
+            *position += motion;
+            return;
+        };
+
+        let travel = (hit.toi * reach - settings.skin).clamp(0.0, length);
+        *position += direction * travel;
+
+        // only the part of what's left going into the surface is taken away
+        let left = direction * (length - travel);
+        let along = left - hit.normal * left.dot(hit.normal).min(0.0);
+
+        motion = match previous_normal {
+            Some(previous) if along.dot(previous) < 0.0 => {
+                let crease = previous.cross(hit.normal).normalize_or_zero();
+                crease * left.dot(crease)
+            }
+            _ => along,
+        };
+
+        previous_normal = Some(hit.normal);
+    }
+}
+
+fn tick(time: Res<Time>, mut clock: ResMut<PhysicsClock>) {
+    clock.advance(time.delta_seconds());
+}
+
+fn interpolate(clock: Res<PhysicsClock>, mut query: Query<(&PhysicsPosition, &mut Transform)>) {
+    for (position, mut transform) in query.iter_mut() {
+        transform.translation = position.previous.lerp(position.current, clock.alpha());
+    }
+}
+
+pub struct PhysicsPlugin;
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsClock>()
+            .init_resource::<SlideSettings>()
+            .add_system_to_stage(CoreStage::First, tick.after(TimeSystem))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+// A headless rapier scene of fixed boxes for tests, each a centre, half extents and a turn.
+#[cfg(test)]
+pub struct Scene {
+    bodies: bevy_rapier3d::rapier::prelude::RigidBodySet,
+    colliders: bevy_rapier3d::rapier::prelude::ColliderSet,
+    pipeline: bevy_rapier3d::rapier::prelude::QueryPipeline,
+}
+
+#[cfg(test)]
+impl Scene {
+    pub fn new(boxes: &[(Vec3, Vec3, Quat)]) -> Scene {
+        use bevy_rapier3d::rapier::prelude::*;
+
+        let bodies = RigidBodySet::new();
+        let mut colliders = ColliderSet::new();
+        let mut pipeline = QueryPipeline::new();
+
+        for &(centre, half, turn) in boxes {
+            colliders.insert(
+                ColliderBuilder::cuboid(half.x, half.y, half.z)
+                    .position((centre, turn).into())
+                    .build(),
+            );
+        }
+
+        pipeline.update(&IslandManager::new(), &bodies, &colliders);
+
+        Scene {
+            bodies,
+            colliders,
+            pipeline,
+        }
+    }
+
+    // what the players' `player_physics` cast against, but in this scene
+    pub fn cast<'a>(
+        &'a self,
+        shape: &'a bevy_rapier3d::prelude::Collider,
+        rotation: Quat,
+    ) -> impl Fn(Vec3, Vec3) -> Option<Hit> + 'a {
+        move |from, motion| {
+            let (_, toi) = self.pipeline.cast_shape(
+                &self.bodies,
+                &self.colliders,
+                &(from, rotation).into(),
+                &motion.into(),
+                &*shape.raw,
+                SlideSettings::default().max_toi,
+                true,
+                Default::default(),
+            )?;
+
+            Some(Hit {
+                toi: toi.toi,
+                normal: Vec3::from(*toi.normal1),
+            })
+        }
+    }
+
+    pub fn overlaps(&self, position: Vec3, shape: &bevy_rapier3d::prelude::Collider) -> bool {
+        self.pipeline
+            .intersection_with_shape(
+                &self.bodies,
+                &self.colliders,
+                &position.into(),
+                &*shape.raw,
+                Default::default(),
+            )
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier3d::prelude::Collider;
+
+    use super::*;
+
+    // a box that's solid on the far side of a plane through `point`, facing `normal`
+    fn wall(point: Vec3, normal: Vec3) -> (Vec3, Vec3, Quat) {
+        (
+            point - normal,
+            Vec3::new(10.0, 1.0, 10.0),
+            Quat::from_rotation_arc(Vec3::Y, normal),
+        )
+    }
+
+    // slides a ball from `from` by `motion` through `walls`, making sure it doesn't end up in one
+    fn slide_ball(walls: &[(Vec3, Vec3, Quat)], from: Vec3, motion: Vec3) -> Vec3 {
+        let scene = Scene::new(walls);
+        let ball = Collider::ball(1.0);
+        let mut position = from;
+
+        slide(
+            &mut position,
+            motion,
+            &SlideSettings::default(),
+            scene.cast(&ball, Quat::IDENTITY),
+        );
+
+        assert!(!scene.overlaps(position, &ball));
+        position
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let end = slide_ball(
+            &[wall(Vec3::new(2.0, 0.0, 0.0), Vec3::NEG_X)],
+            Vec3::ZERO,
+            Vec3::new(3.0, 0.0, 3.0),
+        );
+
+        // reaches the wall a skin short and keeps going along it
+        assert!((end.x - 1.0).abs() < 0.02);
+        assert!(end.z > 2.95);
+    }
+
+    #[test]
+    fn stops_in_corners() {
+        let end = slide_ball(
+            &[
+                wall(Vec3::new(2.0, 0.0, 0.0), Vec3::NEG_X),
+                wall(Vec3::new(0.0, 0.0, 2.0), Vec3::NEG_Z),
+            ],
+            Vec3::ZERO,
+            Vec3::new(5.0, 0.0, 4.0),
+        );
+
+        assert!((end.x - 1.0).abs() < 0.02);
+        assert!((end.z - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn slides_along_the_bottom_of_crevices() {
+        let end = slide_ball(
+            &[
+                wall(Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0).normalize()),
+                wall(Vec3::ZERO, Vec3::new(-1.0, 1.0, 0.0).normalize()),
+            ],
+            Vec3::new(0.3, 4.0, 0.0),
+            Vec3::new(0.5, -6.0, 2.0),
+        );
+
+        // wedged in where it touches both sides, and still going along the crevice
+        assert!(end.x.abs() < 0.05);
+        assert!((end.y - 2f32.sqrt()).abs() < 0.05);
+        assert!(end.z > 0.5);
+    }
+
+    #[test]
+    fn gives_up_on_surfaces_that_never_let_go() {
+        let mut position = Vec3::ZERO;
+        let mut casts = 0;
+
+        // always in the way, facing a different way each time
+        slide(&mut position, Vec3::X, &SlideSettings::default(), |_, _| {
+            casts += 1;
+
+            Some(Hit {
+                toi: 0.0,
+                normal: Quat::from_rotation_y(casts as f32) * Vec3::NEG_X,
+            })
+        });
+
+        assert_eq!(casts, SlideSettings::default().max_slides);
+    }
+}
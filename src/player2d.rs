@@ -1,47 +1,513 @@
-use std::f32::consts::PI;
+use std::f32::consts::{PI, TAU};
+use std::fs;
 
 use bevy::{input::mouse::MouseMotion, prelude::*, window::CursorGrabMode};
 use bevy_rapier3d::prelude::*;
+use mazemazemaze::maze2d::Maze2D;
+use mazemazemaze::maze3d::Maze3D;
+use serde::{Deserialize, Serialize};
 
+use crate::controls::{action_axis, Action, InputFocus, Sticks};
+use crate::gravity::GravityMode;
+use crate::physics::{slide, Hit, PhysicsClock, PhysicsPosition, SlideSettings, TIMESTEP};
+use crate::touch::TouchControls;
 use crate::{filter::FilterCamera, state::AppState, Goal, Settings};
 
+const MOVEMENT_FILE: &str = "movement.json";
+
+// how far below the player the ground is looked for, on top of however far they're falling
+const GROUND_PROBE: f32 = 0.05;
+
+// players are kept this far above the ground so that casts along it don't run into it
+const GROUND_GAP: f32 = 0.01;
+
+// the player is a capsule this wide, standing up it's 3.0 tall and crouching it's a ball
+const RADIUS: f32 = 1.0;
+const STANDING_HALF_HEIGHT: f32 = 0.5;
+const CROUCHING_HALF_HEIGHT: f32 = 0.0;
+
+// how high the camera is above the player's feet
+const STANDING_EYE: f32 = 2.5;
+const CROUCHING_EYE: f32 = 1.5;
+
+// how quickly the camera follows crouching and head-bob starting or stopping, per second
+const EYE_SPEED: f32 = 12.0;
+
+// How the player moves, read from `MOVEMENT_FILE` at startup. Speeds are in units per second,
+// anything the file doesn't mention keeps its default.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Movement2D {
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub crouch_speed: f32,
+
+    pub gravity: f32,
+    pub jump_power: f32,
+
+    // how long after walking off a ledge jumping still works
+    pub coyote_time: f32,
+
+    // how long before landing jump can be pressed
+    pub jump_buffer: f32,
+
+    // how much of the upward speed is kept when jump is let go early
+    pub jump_cut: f32,
+
+    // the steepest floor that's still ground, in degrees, anything steeper can't be walked up
+    pub max_slope: f32,
+
+    // how high a ledge can be walked onto without jumping, and walked off without falling
+    pub step_height: f32,
+
+    // seconds of sprinting on a full meter, running out means waiting for it to fill up again
+    pub stamina: f32,
+
+    // seconds of stamina regained per second, after not sprinting for `stamina_delay` seconds
+    pub stamina_regen: f32,
+    pub stamina_delay: f32,
+
+    // how far the camera bobs up and down, 0.0 turns it off, and how far a full bob is walked
+    pub head_bob: f32,
+    pub head_bob_stride: f32,
+}
+
+impl Default for Movement2D {
+    fn default() -> Self {
+        Movement2D {
+            walk_speed: 12.0,
+            sprint_speed: 18.0,
+            crouch_speed: 5.0,
+            gravity: 30.0,
+            jump_power: 10.8,
+            coyote_time: 0.1,
+            jump_buffer: 0.12,
+            jump_cut: 0.5,
+            max_slope: 50.0,
+            step_height: 0.5,
+            stamina: 4.0,
+            stamina_regen: 1.0,
+            stamina_delay: 0.75,
+            head_bob: 0.06,
+            head_bob_stride: 5.0,
+        }
+    }
+}
+
+impl Movement2D {
+    pub fn load() -> Movement2D {
+        let Ok(json) = fs::read(MOVEMENT_FILE) else {
+            return Movement2D::default();
+        };
+
+        match serde_json::from_slice::<Movement2D>(&json) {
+            Ok(movement) => movement,
+            Err(e) => {
+                error!("failed to load movement from {MOVEMENT_FILE}: {e}");
+                Movement2D::default()
+            }
+        }
+    }
+}
+
+// what the player wants to do during the next steps, in world space
+#[derive(Clone, Copy, Default)]
+pub struct PlayerInput {
+    // across `Player2D::up`, up to 1.0 long
+    pub movement: Vec3,
+
+    // whether jump is held, and whether it's been pressed since the last step
+    pub jump: bool,
+    pub jump_pressed: bool,
+
+    pub sprint: bool,
+    pub crouch: bool,
+}
+
 #[derive(Component)]
 pub struct Player2D {
+    pub input: PlayerInput,
+    pub movement: Movement2D,
     velocity: Vec3,
     grounded: bool,
 
-    speed: f32,
-    gravity: f32,
-    jump_power: f32,
+    // the opposite of the way gravity pulls, see `set_up`
+    up: Vec3,
+
+    // seconds since the player was last on the ground
+    since_grounded: f32,
+
+    // seconds left for a jump that was pressed before landing to still happen
+    buffered_jump: f32,
+
+    // rising from a jump that letting go of jump can still cut short
+    jumping: bool,
+
+    crouching: bool,
+
+    // seconds of sprinting left, and whether it ran out and hasn't filled up again
+    stamina: f32,
+    exhausted: bool,
+    since_sprint: f32,
+
+    // camera height above the feet, and how far through a bob it is in radians
+    eye: f32,
+    bob: f32,
+    bob_weight: f32,
 }
 
 impl Default for Player2D {
     fn default() -> Self {
+        Player2D::new(Movement2D::default())
+    }
+}
+
+impl Player2D {
+    pub fn new(movement: Movement2D) -> Self {
         Player2D {
+            input: PlayerInput::default(),
             velocity: Vec3::ZERO,
             grounded: false,
+            up: Vec3::Y,
+            since_grounded: f32::INFINITY,
+            buffered_jump: 0.0,
+            jumping: false,
+            crouching: false,
+            stamina: movement.stamina,
+            exhausted: false,
+            since_sprint: f32::INFINITY,
+            eye: STANDING_EYE,
+            bob: 0.0,
+            bob_weight: 0.0,
+            movement,
+        }
+    }
 
-            speed: 12.0,
-            gravity: 30.0,
-            jump_power: 10.8,
+    pub fn up(&self) -> Vec3 {
+        self.up
+    }
+
+    // Points gravity the other way to `up`. The player curls up into a ball so that turning to
+    // it doesn't push them into a wall, and stands up again once there's room.
+    pub fn set_up(&mut self, up: Vec3) {
+        if up == self.up {
+            return;
+        }
+
+        self.up = up;
+        self.crouching = true;
+        self.jumping = false;
+        self.since_grounded = f32::INFINITY;
+    }
+
+    // how the collider is turned, it's always upright whichever way the player is looking
+    pub fn shape_rotation(&self) -> Quat {
+        Quat::from_rotation_arc(Vec3::Y, self.up)
+    }
+
+    fn half_height(&self) -> f32 {
+        if self.crouching {
+            CROUCHING_HALF_HEIGHT
+        } else {
+            STANDING_HALF_HEIGHT
+        }
+    }
+
+    // the shape to collide with, it changes when crouching
+    pub fn collider(&self) -> Collider {
+        Collider::capsule_y(self.half_height(), RADIUS)
+    }
+
+    // from 0.0 when out of stamina to 1.0 when it's full
+    pub fn stamina(&self) -> f32 {
+        if self.movement.stamina > 0.0 {
+            self.stamina / self.movement.stamina
+        } else {
+            1.0
+        }
+    }
+
+    // where the camera goes relative to the player's position
+    pub fn eye_offset(&self) -> f32 {
+        let bob = self.movement.head_bob * self.bob_weight * self.bob.sin();
+
+        self.eye - (self.half_height() + RADIUS) + bob
+    }
+
+    // One fixed step of walking, jumping and falling. See `physics::slide` for `cast`, which
+    // should use `collider` from before the step.
+    pub fn step(
+        &mut self,
+        position: &mut PhysicsPosition,
+        settings: &SlideSettings,
+        mut cast: impl FnMut(Vec3, Vec3) -> Option<Hit>,
+    ) {
+        // anything past the end of a motion isn't in its way
+        let mut cast = move |from, motion| cast(from, motion).filter(|hit| hit.toi <= 1.0);
+
+        let moving = self.input.movement.length_squared() > 0.0;
+        let sprinting = self.input.sprint && moving && !self.crouching && !self.exhausted;
+
+        let speed = if self.crouching {
+            self.movement.crouch_speed
+        } else if sprinting {
+            self.movement.sprint_speed
+        } else {
+            self.movement.walk_speed
+        };
+
+        self.update_stamina(sprinting);
+
+        let movement = self.input.movement.clamp_length_max(1.0) * speed;
+
+        // how fast the player is going up, everything else is down to `movement`
+        let mut rise = self.velocity.dot(self.up);
+
+        if self.input.jump_pressed {
+            self.buffered_jump = self.movement.jump_buffer;
+            self.input.jump_pressed = false;
+        }
+
+        if self.buffered_jump > 0.0 && self.since_grounded <= self.movement.coyote_time {
+            rise = self.movement.jump_power;
+            self.jumping = true;
+            self.buffered_jump = 0.0;
+            self.since_grounded = f32::INFINITY;
+        }
+
+        if self.jumping && (rise <= 0.0 || !self.input.jump) {
+            if rise > 0.0 {
+                rise *= self.movement.jump_cut;
+            }
+
+            self.jumping = false;
+        }
+
+        rise -= TIMESTEP * self.movement.gravity;
+        self.buffered_jump = (self.buffered_jump - TIMESTEP).max(0.0);
+
+        position.previous = position.current;
+
+        let up = self.up;
+        let mut motion = TIMESTEP * (movement + up * rise);
+        let max_slope = self.movement.max_slope.to_radians();
+
+        // Anything flat enough within this step's fall is landed on. Walking down stairs or slopes
+        // would leave the ground for a moment every step, so it's looked for further down while on
+        // it.
+        let reach = if self.grounded && rise <= 0.0 {
+            self.movement.step_height
+        } else {
+            0.0
+        };
+        let probe = (TIMESTEP * rise).min(0.0) - GROUND_PROBE - reach;
+        let ground =
+            cast(position.current, up * probe).filter(|hit| hit.normal.dot(up) >= max_slope.cos());
+
+        self.grounded = false;
+
+        if let Some(hit) = ground {
+            if rise <= 0.0 {
+                position.current += up * (hit.toi * probe + GROUND_GAP).min(0.0);
+                motion -= up * motion.dot(up);
+
+                rise = 0.0;
+                self.grounded = true;
+            }
+        }
+
+        let start = position.current;
+        let grounded = self.grounded;
+        let mut ceiling = false;
+        let mut blocked = false;
+
+        slide(&mut position.current, motion, settings, |from, motion| {
+            let hit = cast(from, motion)?;
+            let slope = hit.normal.dot(up);
+
+            ceiling |= slope <= -max_slope.cos();
+
+            if !grounded || slope.abs() >= max_slope.cos() {
+                return Some(hit);
+            }
+
+            // on the ground anything too steep to walk up is a wall, which might be stepped onto
+            blocked = true;
+
+            Some(Hit {
+                normal: (hit.normal - up * slope).normalize(),
+                ..hit
+            })
+        });
+
+        if blocked {
+            if let Some(stepped) =
+                self.step_up(start, position.current, motion, settings, &mut cast)
+            {
+                position.current = stepped;
+            }
+        }
+
+        // bumping into a ceiling ends the jump instead of sticking to it
+        if ceiling && rise > 0.0 {
+            rise = 0.0;
+        }
+
+        self.velocity = movement + up * rise;
+
+        if self.grounded {
+            self.since_grounded = 0.0;
+        } else {
+            self.since_grounded += TIMESTEP;
+        }
+
+        // the shape only changes once everything else is done, so all of this step's casts are
+        // with the same one
+        self.update_crouch(position, cast);
+        self.update_eye(movement.length());
+    }
+
+    // Tries lifting the player by up to `step_height`, moving them by `motion` from `from` and
+    // putting them back down. Gives back where they end up if that's onto something low enough
+    // and further along than `slid`, which is where they got to without stepping.
+    fn step_up(
+        &self,
+        from: Vec3,
+        slid: Vec3,
+        motion: Vec3,
+        settings: &SlideSettings,
+        mut cast: impl FnMut(Vec3, Vec3) -> Option<Hit>,
+    ) -> Option<Vec3> {
+        let up = self.up;
+        let height = self.movement.step_height;
+
+        let lift = cast(from, up * height)
+            .map_or(height, |hit| hit.toi * height - GROUND_GAP)
+            .max(0.0);
+
+        let mut ahead = from + up * lift;
+        slide(&mut ahead, motion, settings, &mut cast);
+
+        let drop = lift + GROUND_PROBE;
+        let hit = cast(ahead, -up * drop)?;
+        let landed = ahead - up * (hit.toi * drop - GROUND_GAP).max(0.0);
+
+        // the rounded bottom touches the ledge this far above the bottom of the player
+        let ledge = (landed - from).dot(up) + RADIUS * (1.0 - hit.normal.dot(up));
+
+        let across = |position: Vec3| {
+            let offset = position - from;
+            (offset - up * offset.dot(up)).length()
+        };
+
+        (hit.normal.dot(up) > 0.0 && ledge <= height && across(landed) > across(slid) + 0.001)
+            .then_some(landed)
+    }
+
+    fn update_stamina(&mut self, sprinting: bool) {
+        if sprinting {
+            self.stamina = (self.stamina - TIMESTEP).max(0.0);
+            self.since_sprint = 0.0;
+            self.exhausted = self.stamina == 0.0;
+        } else {
+            self.since_sprint += TIMESTEP;
+        }
+
+        if self.since_sprint >= self.movement.stamina_delay {
+            self.stamina =
+                (self.stamina + TIMESTEP * self.movement.stamina_regen).min(self.movement.stamina);
+        }
+
+        if self.stamina >= self.movement.stamina {
+            self.exhausted = false;
+        }
+    }
+
+    // crouching keeps the feet where they are and shrinks from the top
+    fn update_crouch(
+        &mut self,
+        position: &mut PhysicsPosition,
+        mut cast: impl FnMut(Vec3, Vec3) -> Option<Hit>,
+    ) {
+        let shift = STANDING_HALF_HEIGHT - CROUCHING_HALF_HEIGHT;
+
+        if self.input.crouch && !self.crouching {
+            self.crouching = true;
+        } else if !self.input.crouch
+            && self.crouching
+            && cast(position.current, self.up * shift * 2.0).is_none()
+        {
+            self.crouching = false;
+        } else {
+            return;
+        }
+
+        // the eye is kept relative to the feet, so the camera doesn't jump along with this
+        let offset = if self.crouching { -shift } else { shift };
+
+        position.previous += self.up * offset;
+        position.current += self.up * offset;
+    }
+
+    fn update_eye(&mut self, speed: f32) {
+        let target = if self.crouching {
+            CROUCHING_EYE
+        } else {
+            STANDING_EYE
+        };
+        let follow = (EYE_SPEED * TIMESTEP).min(1.0);
+
+        self.eye += (target - self.eye) * follow;
+
+        // bobbing fades in and out instead of snapping when starting and stopping
+        let walking = self.grounded && speed > 0.0;
+        self.bob_weight += (walking as i32 as f32 - self.bob_weight) * follow;
+
+        if self.movement.head_bob_stride > 0.0 {
+            self.bob = (self.bob + TAU * speed * TIMESTEP / self.movement.head_bob_stride) % TAU;
         }
     }
 }
 
 #[derive(Component)]
-struct PlayerCamera;
+pub struct PlayerCamera;
+
+#[derive(Component)]
+struct StaminaMeter;
+
+#[derive(Component)]
+struct StaminaFill;
+
+fn setup_player(
+    mut commands: Commands,
+    mut windows: ResMut<Windows>,
+    touch: Res<TouchControls>,
+    movement: Res<Movement2D>,
+    gravity: Res<GravityMode>,
+    state: Res<State<AppState>>,
+) {
+    // 3D mazes are flown through unless they have gravity
+    if *state.current() == AppState::Maze3D && !gravity.enabled {
+        return;
+    }
 
-fn setup_player(mut commands: Commands, mut windows: ResMut<Windows>) {
     let window = windows.get_primary_mut().unwrap();
 
-    // grab cursor
-    window.set_cursor_grab_mode(CursorGrabMode::Locked);
-    window.set_cursor_visibility(false);
+    // grab cursor, there isn't one to grab with the touch controls
+    if !touch.active {
+        window.set_cursor_grab_mode(CursorGrabMode::Locked);
+        window.set_cursor_visibility(false);
+    }
+
+    let player = Player2D::new(movement.clone());
+    let collider = player.collider();
+    let eye = player.eye_offset();
 
     commands
-        .spawn(Player2D::default())
+        .spawn(player)
+        .insert(PhysicsPosition::new(Vec3::new(0.0, 2.0, 0.0)))
         .insert(RigidBody::KinematicPositionBased)
-        .insert(Collider::capsule_y(0.5, 1.0))
+        .insert(collider)
         .insert(VisibilityBundle::default())
         .insert(TransformBundle::from(Transform::from_xyz(0.0, 2.0, 0.0)))
         .insert(ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC)
@@ -52,7 +518,7 @@ fn setup_player(mut commands: Commands, mut windows: ResMut<Windows>) {
                         fov: PI / 2.0,
                         ..default()
                     }),
-                    transform: Transform::from_xyz(0.0, 1.0, 0.0),
+                    transform: Transform::from_xyz(0.0, eye, 0.0),
                     ..default()
                 })
                 .insert(PlayerCamera)
@@ -69,12 +535,44 @@ fn setup_player(mut commands: Commands, mut windows: ResMut<Windows>) {
                 ..default()
             });
         });
+
+    // a bar along the bottom that only shows up while stamina isn't full
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(35.0),
+                    bottom: Val::Px(24.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(30.0), Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::rgba(1.0, 1.0, 1.0, 0.15).into(),
+            visibility: Visibility::INVISIBLE,
+            ..default()
+        })
+        .insert(StaminaMeter)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.6).into(),
+                    ..default()
+                })
+                .insert(StaminaFill);
+        });
 }
 
 fn cleanup_player(
     mut windows: ResMut<Windows>,
     mut commands: Commands,
     query: Query<Entity, With<Player2D>>,
+    meter_query: Query<Entity, With<StaminaMeter>>,
 ) {
     let window = windows.get_primary_mut().unwrap();
 
@@ -82,127 +580,189 @@ fn cleanup_player(
     window.set_cursor_grab_mode(CursorGrabMode::None);
     window.set_cursor_visibility(true);
 
-    for entity in query.iter() {
+    for entity in query.iter().chain(meter_query.iter()) {
         commands.entity(entity).despawn_recursive();
     }
 }
 
-fn player_move(
-    keys: Res<Input<KeyCode>>,
-    windows: Res<Windows>,
-    mut query: Query<(&Transform, &mut Player2D)>,
+// the maze is spawned alongside the player, so the player is moved to its start once both exist
+fn move_to_start(
+    maze_query: Query<&Maze2D>,
+    maze3d_query: Query<&Maze3D>,
+    mut player_query: Query<&mut PhysicsPosition, Added<Player2D>>,
 ) {
-    if let Some(window) = windows.get_primary() {
-        for (transform, mut player) in query.iter_mut() {
-            let mut velocity = Vec3::ZERO;
-
-            let local_z = transform.local_z();
-            let forward = -Vec3::new(local_z.x, 0., local_z.z);
-            let right = Vec3::new(local_z.z, 0., -local_z.x);
-
-            for key in keys.get_pressed() {
-                match window.cursor_grab_mode() {
-                    CursorGrabMode::None => (),
-                    _ => match key {
-                        KeyCode::W => velocity += forward,
-                        KeyCode::S => velocity -= forward,
-                        KeyCode::A => velocity -= right,
-                        KeyCode::D => velocity += right,
-                        KeyCode::Space => {
-                            if player.grounded {
-                                player.velocity.y += player.jump_power;
-                                player.grounded = false;
-                            }
-                        }
-                        _ => (),
-                    },
-                }
-            }
-
-            velocity = velocity.normalize_or_zero() * player.speed;
+    let starts = maze_query
+        .iter()
+        .map(|maze| maze.cell_translation(maze.start))
+        .chain(
+            maze3d_query
+                .iter()
+                .map(|maze| maze.cell_translation(maze.start)),
+        );
 
-            player.velocity.x = velocity.x;
-            player.velocity.z = velocity.z;
+    for start in starts {
+        for mut position in player_query.iter_mut() {
+            position.teleport(Vec3::from(start));
         }
     }
 }
 
-fn player_gravity(time: Res<Time>, mut query: Query<&mut Player2D>) {
-    for mut player in query.iter_mut() {
-        if !player.grounded {
-            player.velocity.y -= time.delta_seconds() * player.gravity;
-        } else {
-            player.velocity.y = 0.0;
+// the controls are read once a frame and used for every step in it
+fn player_input(
+    actions: Res<Input<Action>>,
+    sticks: Res<Sticks>,
+    focus: Res<InputFocus>,
+    mut query: Query<(&Transform, &mut Player2D)>,
+) {
+    for (transform, mut player) in query.iter_mut() {
+        let mut movement = Vec3::ZERO;
+        let mut jump = false;
+        let mut sprint = false;
+        let mut crouch = false;
+
+        // a press stays around until a step has seen it, there might not be one this frame
+        let mut jump_pressed = player.input.jump_pressed;
+
+        // along the floor, whichever way up that is
+        let up = player.up();
+        let local_z = transform.local_z();
+        let forward = -(local_z - up * local_z.dot(up)).normalize_or_zero();
+        let right = forward.cross(up);
+
+        if focus.active() {
+            movement += forward * action_axis(&actions, Action::MoveBack, Action::MoveForward);
+            movement += right * action_axis(&actions, Action::StrafeLeft, Action::StrafeRight);
+            movement += forward * sticks.movement.y + right * sticks.movement.x;
+
+            jump = actions.pressed(Action::Jump);
+            jump_pressed |= actions.just_pressed(Action::Jump);
+            sprint = actions.pressed(Action::Sprint);
+            crouch = actions.pressed(Action::Crouch);
         }
+
+        player.input = PlayerInput {
+            movement,
+            jump,
+            jump_pressed,
+            sprint,
+            crouch,
+        };
     }
 }
 
-fn player_velocity(
+fn player_physics(
     rapier_context: Res<RapierContext>,
-    time: Res<Time>,
+    clock: Res<PhysicsClock>,
+    settings: Res<SlideSettings>,
     mut state: ResMut<State<AppState>>,
-    mut query: Query<(Entity, &Collider, &mut Transform, &mut Player2D)>,
+    mut query: Query<(Entity, &mut Collider, &mut PhysicsPosition, &mut Player2D)>,
     goal_query: Query<Entity, With<Goal>>,
 ) {
-    let max_toi = 4.0;
-
-    for (entity, collider, mut transform, mut player) in query.iter_mut() {
-        let mut velocity = Some(time.delta_seconds() * player.velocity);
+    for (entity, mut collider, mut position, mut player) in query.iter_mut() {
         let filter = QueryFilter::default().exclude_collider(entity);
+        let mut won = false;
+
+        for _ in 0..clock.steps {
+            // crouching changes the shape during a step, rapier only finds out about it after
+            let shape = player.collider();
+            let rotation = player.shape_rotation();
+
+            player.step(&mut position, &settings, |from, motion| {
+                let (entity, collision) = rapier_context.cast_shape(
+                    from,
+                    rotation,
+                    motion,
+                    &shape,
+                    settings.max_toi,
+                    filter,
+                )?;
 
-        while let Some(adjusted_velocity) = velocity {
-            if let Some((entity, collision)) = rapier_context.cast_shape(
-                transform.translation,
-                transform.rotation,
-                adjusted_velocity,
-                collider,
-                max_toi,
-                filter,
-            ) {
                 // test if entity is a goal component
                 if goal_query.get(entity).is_ok() {
-                    state.set(AppState::MainMenu).unwrap();
-                    println!("You won!");
+                    won = true;
                 }
 
-                let normal = collision.normal1;
-
-                if (1.0 - normal.dot(Vec3::Y)).abs() < 0.01 {
-                    player.grounded = true;
-                }
+                Some(Hit {
+                    toi: collision.toi,
+                    normal: collision.normal1,
+                })
+            });
 
-                // slide along wall of collision
-                velocity = Some(adjusted_velocity - (normal * adjusted_velocity.dot(normal)));
-            } else {
-                transform.translation += adjusted_velocity;
-                velocity = None;
+            // the rest of the frame's steps would only carry on past the goal
+            if won {
+                break;
             }
         }
+
+        // only crouching changes the shape, so there's no need to rebuild it every frame
+        let half_height = collider
+            .raw
+            .as_capsule()
+            .map(|capsule| capsule.half_height());
+        if half_height != Some(player.half_height()) {
+            *collider = player.collider();
+        }
+
+        // another system might have changed the state already this frame
+        if won {
+            let _ = state.set(AppState::Won);
+        }
+    }
+}
+
+fn update_camera(
+    player_query: Query<&Player2D>,
+    mut camera_query: Query<&mut Transform, With<PlayerCamera>>,
+) {
+    for player in player_query.iter() {
+        for mut transform in camera_query.iter_mut() {
+            transform.translation.y = player.eye_offset();
+        }
+    }
+}
+
+fn update_stamina_meter(
+    player_query: Query<&Player2D>,
+    mut meter_query: Query<&mut Visibility, With<StaminaMeter>>,
+    mut fill_query: Query<&mut Style, With<StaminaFill>>,
+) {
+    for player in player_query.iter() {
+        let stamina = player.stamina();
+
+        for mut visibility in meter_query.iter_mut() {
+            visibility.is_visible = stamina < 1.0;
+        }
+
+        for mut style in fill_query.iter_mut() {
+            style.size.width = Val::Percent(stamina * 100.0);
+        }
     }
 }
 
 fn player_look(
-    windows: Res<Windows>,
+    focus: Res<InputFocus>,
     settings: Res<Settings>,
+    sticks: Res<Sticks>,
+    time: Res<Time>,
     mut motion: EventReader<MouseMotion>,
     mut player_query: Query<&mut Transform, (With<Player2D>, Without<PlayerCamera>)>,
     mut camera_query: Query<&mut Transform, (Without<Player2D>, With<PlayerCamera>)>,
 ) {
-    let window = windows.get_primary().unwrap();
-
     let mut new_yaw = 0.0;
     let mut new_pitch = 0.0;
 
     for ev in motion.iter() {
-        match window.cursor_grab_mode() {
-            CursorGrabMode::None => (),
-            _ => {
-                new_pitch -= (settings.sensitivity * ev.delta.y).to_radians();
-                new_yaw -= (settings.sensitivity * ev.delta.x).to_radians();
-            }
+        if focus.grabbed {
+            new_pitch -= (settings.sensitivity * ev.delta.y).to_radians();
+            new_yaw -= (settings.sensitivity * ev.delta.x).to_radians();
         }
     }
 
+    if focus.active() {
+        new_pitch += sticks.look.y * time.delta_seconds();
+        new_yaw -= sticks.look.x * time.delta_seconds();
+    }
+
     new_pitch = new_pitch.clamp(-1.54, 1.54);
 
     for mut player_transform in player_query.iter_mut() {
@@ -218,14 +778,329 @@ pub struct Player2DPlugin;
 
 impl Plugin for Player2DPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(AppState::Maze2D).with_system(setup_player))
-            .add_system_set(SystemSet::on_exit(AppState::Maze2D).with_system(cleanup_player))
-            .add_system_set(
-                SystemSet::on_update(AppState::Maze2D)
-                    .with_system(player_look)
-                    .with_system(player_velocity)
-                    .with_system(player_gravity.after(player_velocity))
-                    .with_system(player_move.after(player_velocity)),
-            );
+        app.insert_resource(Movement2D::load());
+
+        // 3D mazes with gravity are walked through too, see `setup_player`
+        for state in [AppState::Maze2D, AppState::Maze3D] {
+            app.add_system_set(SystemSet::on_enter(state.clone()).with_system(setup_player))
+                .add_system_set(SystemSet::on_exit(state.clone()).with_system(cleanup_player))
+                .add_system_set(
+                    SystemSet::on_update(state)
+                        .with_system(move_to_start)
+                        .with_system(player_look)
+                        .with_system(player_input.after(player_look))
+                        .with_system(player_physics.after(player_input))
+                        .with_system(update_camera.after(player_physics))
+                        .with_system(update_stamina_meter.after(player_physics)),
+                );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::Scene;
+
+    const STEPS: u32 = 180;
+
+    // A floor for x < `ledge` that a capsule stands on at y = 1.5, and a wall that stops it at
+    // x = 4. Rapier's casts get less exact against huge boxes, so they only go on a bit past
+    // anywhere these tests get to.
+    fn world(ledge: f32) -> Vec<(Vec3, Vec3, Quat)> {
+        vec![
+            (
+                Vec3::new(ledge / 2.0 - 10.0, -0.5, 0.0),
+                Vec3::new(ledge / 2.0 + 10.0, 0.5, 20.0),
+                Quat::IDENTITY,
+            ),
+            (
+                Vec3::new(4.5 + RADIUS, 0.0, 0.0),
+                Vec3::new(0.5, 10.0, 20.0),
+                Quat::IDENTITY,
+            ),
+        ]
+    }
+
+    // one step against `scene`, the way `player_physics` takes them
+    fn step(player: &mut Player2D, position: &mut PhysicsPosition, scene: &Scene) {
+        let shape = player.collider();
+        let rotation = player.shape_rotation();
+
+        player.step(
+            position,
+            &SlideSettings::default(),
+            scene.cast(&shape, rotation),
+        );
+    }
+
+    // runs into the wall, jumps, slides along it, jumps again and turns back
+    fn script(step: u32) -> PlayerInput {
+        PlayerInput {
+            movement: match step {
+                0..=59 => Vec3::X,
+                60..=119 => Vec3::new(0.6, 0.0, -0.8),
+                _ => Vec3::new(0.0, 0.0, 0.5),
+            },
+            jump: (30..35).contains(&step) || (100..105).contains(&step),
+            jump_pressed: step == 30 || step == 100,
+            ..default()
+        }
+    }
+
+    // plays the script with frames taking turns being as long as each of `frames`
+    fn simulate(frames: &[f32]) -> Vec3 {
+        let scene = Scene::new(&world(10.0));
+        let mut clock = PhysicsClock::default();
+        let mut player = Player2D::default();
+        let mut position = PhysicsPosition::new(Vec3::new(0.0, 2.0, 0.0));
+        let mut steps = 0;
+
+        for &frame in frames.iter().cycle() {
+            for _ in 0..clock.advance(frame) {
+                player.input = script(steps);
+                step(&mut player, &mut position, &scene);
+
+                steps += 1;
+
+                if steps == STEPS {
+                    return position.current;
+                }
+            }
+        }
+
+        unreachable!()
+    }
+
+    // Starts at `height` and walks right, towards a ledge at x = `ledge`, pressing and holding
+    // jump from step `jump_at` on. Gives back the height after each step.
+    fn heights(ledge: f32, height: f32, jump_at: usize) -> Vec<f32> {
+        let scene = Scene::new(&world(ledge));
+        let mut player = Player2D::default();
+        let mut position = PhysicsPosition::new(Vec3::new(0.0, height + GROUND_GAP, 0.0));
+
+        (0..60)
+            .map(|steps| {
+                player.input = PlayerInput {
+                    movement: Vec3::X,
+                    jump: steps >= jump_at,
+                    jump_pressed: steps == jump_at,
+                    ..default()
+                };
+                step(&mut player, &mut position, &scene);
+
+                position.current.y
+            })
+            .collect()
+    }
+
+    fn highest(heights: &[f32]) -> f32 {
+        heights.iter().copied().fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    #[test]
+    fn replays_end_in_the_same_place() {
+        let first = simulate(&[1.0 / 60.0]);
+        let second = simulate(&[1.0 / 60.0]);
+
+        assert_eq!(first, second);
+        assert!(first.x > 3.0 && first.z < -1.0, "the script went nowhere");
+    }
+
+    #[test]
+    fn frame_rate_does_not_change_where_replays_end() {
+        let slow = simulate(&[1.0 / 30.0]);
+        let uneven = simulate(&[1.0 / 144.0, 1.0 / 50.0, 0.003, 1.0 / 75.0]);
+
+        assert_eq!(slow, uneven);
+    }
+
+    #[test]
+    fn walking_off_a_ledge_falls() {
+        // the middle goes past the edge on step 10, and the rounded bottom rolls off it over the
+        // next few steps
+        assert!(heights(2.0, 1.5, 60)[59] < 0.0);
+    }
+
+    #[test]
+    fn jumping_just_after_leaving_a_ledge_still_works() {
+        assert!(highest(&heights(2.0, 1.5, 12)[12..]) > 1.6);
+        assert!(highest(&heights(2.0, 1.5, 18)[18..]) > 1.6);
+        assert!(highest(&heights(2.0, 1.5, 24)[24..]) < 1.5);
+    }
+
+    #[test]
+    fn jumping_just_before_landing_jumps_on_landing() {
+        // falling from 2.5 lands on step 15
+        assert!(highest(&heights(10.0, 2.5, 10)[20..]) > 1.6);
+        assert!(highest(&heights(10.0, 2.5, 2)[20..]) < 1.6);
+    }
+
+    #[test]
+    fn letting_go_of_jump_early_jumps_lower() {
+        let held = heights(10.0, 1.5, 5);
+
+        let scene = Scene::new(&world(10.0));
+        let mut player = Player2D::default();
+        let mut position = PhysicsPosition::new(Vec3::new(0.0, 1.5 + GROUND_GAP, 0.0));
+        let mut tapped = vec![];
+
+        for steps in 0..60 {
+            player.input = PlayerInput {
+                movement: Vec3::X,
+                jump: (5..8).contains(&steps),
+                jump_pressed: steps == 5,
+                ..default()
+            };
+            step(&mut player, &mut position, &scene);
+
+            tapped.push(position.current.y);
+        }
+
+        assert!(highest(&tapped) > 1.6);
+        assert!(highest(&tapped) < highest(&held) - 0.5);
+    }
+
+    #[test]
+    fn sprinting_runs_out_of_stamina_until_it_fills_up_again() {
+        let movement = Movement2D::default();
+
+        // a long way to run down
+        let scene = Scene::new(&[(
+            Vec3::new(0.0, -0.5, -80.0),
+            Vec3::new(5.0, 0.5, 90.0),
+            Quat::IDENTITY,
+        )]);
+        let mut player = Player2D::default();
+        let mut position = PhysicsPosition::new(Vec3::new(0.0, 1.5 + GROUND_GAP, 0.0));
+
+        // how far each step went, holding sprint the whole time
+        let speeds: Vec<f32> = (0..600)
+            .map(|_| {
+                player.input = PlayerInput {
+                    movement: Vec3::NEG_Z,
+                    sprint: true,
+                    ..default()
+                };
+
+                let from = position.current;
+                step(&mut player, &mut position, &scene);
+
+                from.distance(position.current) / TIMESTEP
+            })
+            .collect();
+
+        // runs out after 4 seconds and is full again 4.75 seconds later
+        assert!((speeds[60] - movement.sprint_speed).abs() < 0.01);
+        assert!((speeds[300] - movement.walk_speed).abs() < 0.01);
+        assert!((speeds[590] - movement.sprint_speed).abs() < 0.01);
+    }
+
+    #[test]
+    fn gravity_works_the_same_along_any_axis() {
+        // dropped onto the floor, turning the world isn't exact enough to start right on it
+        let upright = heights(10.0, 1.6, 5);
+
+        for up in [Vec3::X, Vec3::NEG_X, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z] {
+            // the same world as `heights`, turned so that its up is `up`
+            let turn = Quat::from_rotation_arc(Vec3::Y, up);
+            let turned: Vec<_> = world(10.0)
+                .into_iter()
+                .map(|(centre, half, rotation)| (turn * centre, half, turn * rotation))
+                .collect();
+
+            let scene = Scene::new(&turned);
+            let mut player = Player2D::default();
+            let mut position = PhysicsPosition::new(turn * Vec3::new(0.0, 1.6 + GROUND_GAP, 0.0));
+
+            // turning to it curls up into a ball, which the floor isn't made for
+            player.up = up;
+
+            for (steps, height) in upright.iter().enumerate() {
+                player.input = PlayerInput {
+                    movement: turn * Vec3::X,
+                    jump: steps >= 5,
+                    jump_pressed: steps == 5,
+                    ..default()
+                };
+                step(&mut player, &mut position, &scene);
+
+                assert!((position.current.dot(up) - height).abs() < 0.001);
+            }
+        }
+    }
+
+    // walks along x from standing at the origin for a second and gives back where that ends
+    fn walk(boxes: &[(Vec3, Vec3, Quat)]) -> Vec3 {
+        let floor = (
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(50.0, 0.5, 50.0),
+            Quat::IDENTITY,
+        );
+
+        let scene = Scene::new(&[boxes, &[floor]].concat());
+        let mut player = Player2D::default();
+        let mut position = PhysicsPosition::new(Vec3::new(0.0, 1.5 + GROUND_GAP, 0.0));
+
+        for _ in 0..60 {
+            player.input = PlayerInput {
+                movement: Vec3::X,
+                ..default()
+            };
+
+            step(&mut player, &mut position, &scene);
+        }
+
+        position.current
+    }
+
+    // a ledge `height` high from x = 3 on
+    fn ledge(height: f32) -> (Vec3, Vec3, Quat) {
+        (
+            Vec3::new(13.0, height / 2.0, 0.0),
+            Vec3::new(10.0, height / 2.0, 5.0),
+            Quat::IDENTITY,
+        )
+    }
+
+    // a slope going up at `degrees` from x = 3 on
+    fn ramp(degrees: f32) -> (Vec3, Vec3, Quat) {
+        let turn = Quat::from_rotation_z(degrees.to_radians());
+
+        (
+            Vec3::new(3.0, 0.0, 0.0) - turn * Vec3::new(-10.0, 1.0, 0.0),
+            Vec3::new(10.0, 1.0, 5.0),
+            turn,
+        )
+    }
+
+    #[test]
+    fn walks_onto_ledges_up_to_the_step_height() {
+        let end = walk(&[ledge(0.45)]);
+
+        assert!(end.x > 8.0);
+        assert!((end.y - 1.95).abs() < 0.05);
+    }
+
+    #[test]
+    fn does_not_walk_onto_higher_ledges() {
+        let end = walk(&[ledge(0.8)]);
+
+        assert!(end.x < 3.0);
+        assert!(end.y < 1.6);
+    }
+
+    #[test]
+    fn walks_up_gentle_slopes() {
+        // ends up somewhere past x = 9, where the slope is over 3.5 high
+        assert!(walk(&[ramp(30.0)]).y > 5.0);
+    }
+
+    #[test]
+    fn does_not_walk_up_steep_slopes() {
+        let end = walk(&[ramp(60.0)]);
+
+        assert!(end.x < 3.0);
+        assert!(end.y < 1.6);
     }
 }
@@ -1,37 +1,230 @@
 use std::f32::consts::PI;
+use std::fmt;
+use std::fs;
 
 use bevy::{input::mouse::MouseMotion, prelude::*, window::CursorGrabMode};
 use bevy_rapier3d::prelude::*;
+use mazemazemaze::maze3d::Maze3D;
+use serde::{Deserialize, Serialize};
 
+use crate::controls::{action_axis, Action, InputFocus, Sticks};
+use crate::gravity::GravityMode;
+use crate::physics::{slide, Hit, PhysicsClock, PhysicsPosition, SlideSettings, TIMESTEP};
+use crate::touch::TouchControls;
 use crate::{filter::FilterCamera, state::AppState, Goal, Settings};
 
+const FLIGHT_FILE: &str = "flight.json";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FlightModel {
+    // moving and turning start and stop as soon as the controls do
+    Arcade,
+
+    // speeding up, slowing down and spinning take time, like drifting in space
+    ZeroG,
+}
+
+impl FlightModel {
+    pub fn toggled(self) -> Self {
+        match self {
+            FlightModel::Arcade => FlightModel::ZeroG,
+            FlightModel::ZeroG => FlightModel::Arcade,
+        }
+    }
+}
+
+impl fmt::Display for FlightModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlightModel::Arcade => write!(f, "arcade"),
+            FlightModel::ZeroG => write!(f, "zero-g"),
+        }
+    }
+}
+
+// How the player flies, read from `FLIGHT_FILE` at startup, see `Movement2D`. Everything but
+// `speed` is only for zero-g.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Flight3D {
+    pub model: FlightModel,
+    pub speed: f32,
+
+    // units per second squared at full thrust, and how much of the speed is lost per second
+    pub acceleration: f32,
+    pub damping: f32,
+
+    // how quickly spinning dies down, the lower the longer turns carry on
+    pub angular_damping: f32,
+
+    // how hard the player is rolled back level when they aren't rolling, 0.0 turns it off
+    pub auto_level: f32,
+}
+
+impl Default for Flight3D {
+    fn default() -> Self {
+        Flight3D {
+            model: FlightModel::Arcade,
+            speed: 12.0,
+            acceleration: 15.0,
+            damping: 1.25,
+            angular_damping: 6.0,
+            auto_level: 4.0,
+        }
+    }
+}
+
+impl Flight3D {
+    pub fn load() -> Flight3D {
+        let Ok(json) = fs::read(FLIGHT_FILE) else {
+            return Flight3D::default();
+        };
+
+        match serde_json::from_slice::<Flight3D>(&json) {
+            Ok(flight) => flight,
+            Err(e) => {
+                error!("failed to load flight from {FLIGHT_FILE}: {e}");
+                Flight3D::default()
+            }
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Player3D {
+    // where the player wants to go during the next steps, in world space and up to 1.0 long
+    pub input: Vec3,
+    pub flight: Flight3D,
     velocity: Vec3,
-    speed: f32,
+
+    // Turning waiting for the next step in zero-g, pitch, yaw and roll in radians, and whether
+    // the player is rolling, which stops them from being rolled back level.
+    pub look: Vec3,
+    pub rolling: bool,
+
+    // how fast the player is turning in zero-g, pitch, yaw and roll in radians per second
+    spin: Vec3,
 }
 
 impl Default for Player3D {
     fn default() -> Self {
+        Player3D::new(Flight3D::default())
+    }
+}
+
+impl Player3D {
+    pub fn new(flight: Flight3D) -> Self {
         Player3D {
+            input: Vec3::ZERO,
+            flight,
             velocity: Vec3::ZERO,
-            speed: 12.0,
+            look: Vec3::ZERO,
+            rolling: false,
+            spin: Vec3::ZERO,
+        }
+    }
+
+    // one fixed step of flying, see `physics::slide` for `cast`
+    pub fn step(
+        &mut self,
+        position: &mut PhysicsPosition,
+        settings: &SlideSettings,
+        cast: impl FnMut(Vec3, Vec3) -> Option<Hit>,
+    ) {
+        // not normalized so that a stick pushed halfway moves at half speed
+        let input = self.input.clamp_length_max(1.0);
+
+        match self.flight.model {
+            FlightModel::Arcade => self.velocity = input * self.flight.speed,
+            FlightModel::ZeroG => {
+                self.velocity += TIMESTEP * self.flight.acceleration * input;
+                self.velocity *= (-self.flight.damping * TIMESTEP).exp();
+                self.velocity = self.velocity.clamp_length_max(self.flight.speed);
+            }
+        }
+
+        position.previous = position.current;
+        slide(
+            &mut position.current,
+            TIMESTEP * self.velocity,
+            settings,
+            cast,
+        );
+
+        // whatever was slid along takes away the speed going into it
+        self.velocity = (position.current - position.previous) / TIMESTEP;
+    }
+
+    // Turns `rotation` by `look`, pitch, yaw and roll in radians, straight away in arcade. In
+    // zero-g it waits for `spin` in the next step instead.
+    pub fn turn(&mut self, rotation: Quat, look: Vec3) -> Quat {
+        match self.flight.model {
+            FlightModel::Arcade => {
+                self.look = Vec3::ZERO;
+                self.spin = Vec3::ZERO;
+
+                turned(rotation, look)
+            }
+            FlightModel::ZeroG => {
+                self.look += look;
+                rotation
+            }
         }
     }
+
+    // One fixed step of spinning in zero-g. What's been looked since the last step starts a spin
+    // that adds up to the same turn as it dies down.
+    pub fn spin(&mut self, rotation: Quat) -> Quat {
+        if self.flight.model == FlightModel::Arcade {
+            return rotation;
+        }
+
+        let damping = (-self.flight.angular_damping * TIMESTEP).exp();
+
+        self.spin += std::mem::take(&mut self.look) * (1.0 - damping) / TIMESTEP;
+
+        // how far the right hand side is tipped up is how far off level the player is
+        if !self.rolling {
+            self.spin.z -= TIMESTEP * self.flight.auto_level * (rotation * Vec3::X).y;
+        }
+
+        let turn = self.spin * TIMESTEP;
+        self.spin *= damping;
+
+        turned(rotation, turn)
+    }
+}
+
+fn turned(rotation: Quat, look: Vec3) -> Quat {
+    rotation * Quat::from_euler(EulerRot::YXZ, look.y, look.x, look.z)
 }
 
 #[derive(Component)]
 struct PlayerCamera;
 
-fn setup_player(mut commands: Commands, mut windows: ResMut<Windows>) {
+fn setup_player(
+    mut commands: Commands,
+    mut windows: ResMut<Windows>,
+    touch: Res<TouchControls>,
+    flight: Res<Flight3D>,
+    gravity: Res<GravityMode>,
+) {
+    // the player walks instead, see `player2d::setup_player`
+    if gravity.enabled {
+        return;
+    }
+
     let window = windows.get_primary_mut().unwrap();
 
-    // grab cursor
-    window.set_cursor_grab_mode(CursorGrabMode::Locked);
-    window.set_cursor_visibility(false);
+    // grab cursor, there isn't one to grab with the touch controls
+    if !touch.active {
+        window.set_cursor_grab_mode(CursorGrabMode::Locked);
+        window.set_cursor_visibility(false);
+    }
 
     commands
-        .spawn(Player3D::default())
+        .spawn(Player3D::new(flight.clone()))
+        .insert(PhysicsPosition::new(Vec3::new(0.0, 2.0, 0.0)))
         .insert(RigidBody::KinematicPositionBased)
         .insert(Collider::ball(1.0))
         .insert(VisibilityBundle::default())
@@ -76,117 +269,147 @@ fn cleanup_player(
     }
 }
 
-fn player_move(
-    keys: Res<Input<KeyCode>>,
-    windows: Res<Windows>,
+// see `player2d::move_to_start`
+fn move_to_start(
+    maze_query: Query<&Maze3D>,
+    mut player_query: Query<&mut PhysicsPosition, Added<Player3D>>,
+) {
+    for maze in maze_query.iter() {
+        for mut position in player_query.iter_mut() {
+            position.teleport(Vec3::from(maze.cell_translation(maze.start)));
+        }
+    }
+}
+
+// see `player2d::player_input`
+fn player_input(
+    actions: Res<Input<Action>>,
+    sticks: Res<Sticks>,
+    focus: Res<InputFocus>,
     mut query: Query<(&Transform, &mut Player3D)>,
 ) {
-    if let Some(window) = windows.get_primary() {
-        for (transform, mut player) in query.iter_mut() {
-            let mut velocity = Vec3::ZERO;
-
-            let forward = transform.forward();
-            let right = transform.right();
-            let up = transform.up();
-
-            for key in keys.get_pressed() {
-                match window.cursor_grab_mode() {
-                    CursorGrabMode::None => (),
-                    _ => match key {
-                        KeyCode::W => velocity += forward,
-                        KeyCode::S => velocity -= forward,
-                        KeyCode::A => velocity -= right,
-                        KeyCode::D => velocity += right,
-                        KeyCode::Space => velocity += up,
-                        KeyCode::LShift => velocity -= up,
-                        _ => (),
-                    },
-                }
-            }
+    for (transform, mut player) in query.iter_mut() {
+        let mut input = Vec3::ZERO;
 
-            velocity = velocity.normalize_or_zero() * player.speed;
+        let forward = transform.forward();
+        let right = transform.right();
+        let up = transform.up();
 
-            player.velocity.x = velocity.x;
-            player.velocity.y = velocity.y;
-            player.velocity.z = velocity.z;
+        if focus.active() {
+            input += forward * action_axis(&actions, Action::MoveBack, Action::MoveForward);
+            input += right * action_axis(&actions, Action::StrafeLeft, Action::StrafeRight);
+            input += up * action_axis(&actions, Action::Descend, Action::Ascend);
+            input += forward * sticks.movement.y + right * sticks.movement.x;
         }
+
+        player.input = input;
     }
 }
 
-fn player_velocity(
+fn player_physics(
     rapier_context: Res<RapierContext>,
-    time: Res<Time>,
+    clock: Res<PhysicsClock>,
+    settings: Res<SlideSettings>,
     mut state: ResMut<State<AppState>>,
-    mut query: Query<(Entity, &Collider, &mut Transform, &Player3D)>,
+    mut query: Query<(
+        Entity,
+        &Collider,
+        &mut Transform,
+        &mut PhysicsPosition,
+        &mut Player3D,
+    )>,
     goal_query: Query<Entity, With<Goal>>,
 ) {
-    let max_toi = 4.0;
-
-    for (entity, collider, mut transform, player) in query.iter_mut() {
-        let mut velocity = Some(time.delta_seconds() * player.velocity);
+    for (entity, collider, mut transform, mut position, mut player) in query.iter_mut() {
         let filter = QueryFilter::default().exclude_collider(entity);
+        let mut won = false;
+
+        for _ in 0..clock.steps {
+            transform.rotation = player.spin(transform.rotation);
+
+            player.step(&mut position, &settings, |from, motion| {
+                let (entity, collision) = rapier_context.cast_shape(
+                    from,
+                    transform.rotation,
+                    motion,
+                    collider,
+                    settings.max_toi,
+                    filter,
+                )?;
 
-        while let Some(adjusted_velocity) = velocity {
-            if let Some((entity, collision)) = rapier_context.cast_shape(
-                transform.translation,
-                transform.rotation,
-                adjusted_velocity,
-                collider,
-                max_toi,
-                filter,
-            ) {
                 // test if entity is a goal component
                 if goal_query.get(entity).is_ok() {
-                    state.set(AppState::MainMenu).unwrap();
-                    println!("You won!");
+                    won = true;
                 }
 
-                let normal = collision.normal1;
+                Some(Hit {
+                    toi: collision.toi,
+                    normal: collision.normal1,
+                })
+            });
 
-                // slide along wall of collision
-                velocity = Some(adjusted_velocity - (normal * adjusted_velocity.dot(normal)));
-            } else {
-                transform.translation += adjusted_velocity;
-                velocity = None;
+            // see `player2d::player_physics`
+            if won {
+                break;
             }
         }
+
+        if won {
+            let _ = state.set(AppState::Won);
+        }
     }
 }
 
 fn player_look(
-    windows: Res<Windows>,
+    focus: Res<InputFocus>,
     settings: Res<Settings>,
-    buttons: Res<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
+    sticks: Res<Sticks>,
     time: Res<Time>,
     mut motion: EventReader<MouseMotion>,
-    mut query: Query<&mut Transform, With<Player3D>>,
+    mut query: Query<(&mut Transform, &mut Player3D)>,
 ) {
-    let window = windows.get_primary().unwrap();
-
     let mut new_yaw = 0.0;
     let mut new_pitch = 0.0;
     let mut new_roll = 0.0;
 
     for ev in motion.iter() {
-        match window.cursor_grab_mode() {
-            CursorGrabMode::None => (),
-            _ => {
-                new_pitch -= (settings.sensitivity * ev.delta.y).to_radians();
-                new_yaw -= (settings.sensitivity * ev.delta.x).to_radians();
-            }
+        if focus.grabbed {
+            new_pitch -= (settings.sensitivity * ev.delta.y).to_radians();
+            new_yaw -= (settings.sensitivity * ev.delta.x).to_radians();
         }
     }
 
-    if buttons.pressed(MouseButton::Left) {
-        new_roll += 2.0 * time.delta_seconds();
+    if focus.active() {
+        new_pitch += sticks.look.y * time.delta_seconds();
+        new_yaw -= sticks.look.x * time.delta_seconds();
     }
 
-    if buttons.pressed(MouseButton::Right) {
-        new_roll -= 2.0 * time.delta_seconds();
+    let roll = action_axis(&actions, Action::RollRight, Action::RollLeft);
+    new_roll += 2.0 * time.delta_seconds() * roll;
+
+    let look = Vec3::new(new_pitch, new_yaw, new_roll);
+
+    for (mut transform, mut player) in query.iter_mut() {
+        transform.rotation = player.turn(transform.rotation, look);
+        player.rolling = roll != 0.0;
     }
+}
 
-    for mut transform in query.iter_mut() {
-        transform.rotation *= Quat::from_euler(EulerRot::YXZ, new_yaw, new_pitch, new_roll);
+fn toggle_flight(
+    actions: Res<Input<Action>>,
+    mut flight: ResMut<Flight3D>,
+    mut query: Query<&mut Player3D>,
+) {
+    if !actions.just_pressed(Action::ToggleFlight) {
+        return;
+    }
+
+    flight.model = flight.model.toggled();
+    info!("flight model: {}", flight.model);
+
+    for mut player in query.iter_mut() {
+        player.flight.model = flight.model;
     }
 }
 
@@ -194,13 +417,115 @@ pub struct Player3DPlugin;
 
 impl Plugin for Player3DPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(AppState::Maze3D).with_system(setup_player))
+        app.insert_resource(Flight3D::load())
+            .add_system_set(SystemSet::on_enter(AppState::Maze3D).with_system(setup_player))
             .add_system_set(SystemSet::on_exit(AppState::Maze3D).with_system(cleanup_player))
             .add_system_set(
                 SystemSet::on_update(AppState::Maze3D)
-                    .with_system(player_look)
-                    .with_system(player_move)
-                    .with_system(player_velocity),
+                    .with_system(move_to_start)
+                    .with_system(toggle_flight)
+                    .with_system(player_look.after(toggle_flight))
+                    .with_system(player_input.after(player_look))
+                    .with_system(player_physics.after(player_input)),
             );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(model: FlightModel) -> Player3D {
+        Player3D::new(Flight3D { model, ..default() })
+    }
+
+    // how fast the player goes for each of `steps` steps of flying through nothing
+    fn speeds(player: &mut Player3D, position: &mut PhysicsPosition, steps: usize) -> Vec<f32> {
+        (0..steps)
+            .map(|_| {
+                player.step(position, &SlideSettings::default(), |_, _| None);
+                position.current.distance(position.previous) / TIMESTEP
+            })
+            .collect()
+    }
+
+    // how far `rotation` is rolled away from level, in radians
+    fn roll(rotation: Quat) -> f32 {
+        (rotation * Vec3::X).y.asin()
+    }
+
+    #[test]
+    fn zero_g_speeds_up_to_full_speed_and_drifts_to_a_stop() {
+        let mut player = player(FlightModel::ZeroG);
+        let mut position = PhysicsPosition::new(Vec3::ZERO);
+        let speed = player.flight.speed;
+
+        player.input = Vec3::X;
+        let thrusting = speeds(&mut player, &mut position, 600);
+
+        assert!(thrusting[0] < speed * 0.1);
+        assert!(thrusting.windows(2).all(|pair| pair[1] >= pair[0]));
+
+        // a bit short of it, as each step speeds up before slowing down
+        assert!(thrusting[599] > speed * 0.98 && thrusting[599] <= speed + 0.001);
+
+        player.input = Vec3::ZERO;
+        let drifting = speeds(&mut player, &mut position, 300);
+
+        assert!(drifting[0] > speed * 0.9);
+        assert!(drifting.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(drifting[299] < speed * 0.01);
+    }
+
+    #[test]
+    fn arcade_starts_and_stops_at_once() {
+        let mut player = player(FlightModel::Arcade);
+        let mut position = PhysicsPosition::new(Vec3::ZERO);
+        let speed = player.flight.speed;
+
+        player.input = Vec3::X;
+        assert!((speeds(&mut player, &mut position, 1)[0] - speed).abs() < 0.001);
+
+        player.input = Vec3::ZERO;
+        assert_eq!(speeds(&mut player, &mut position, 1)[0], 0.0);
+    }
+
+    #[test]
+    fn zero_g_spins_die_down_having_turned_as_far_as_was_looked() {
+        let mut player = player(FlightModel::ZeroG);
+        let mut rotation = player.turn(Quat::IDENTITY, Vec3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(rotation, Quat::IDENTITY);
+
+        for _ in 0..300 {
+            rotation = player.spin(rotation);
+        }
+
+        assert!(player.spin.length() < 0.001);
+        assert!(rotation.angle_between(Quat::from_rotation_y(1.0)) < 0.001);
+    }
+
+    #[test]
+    fn zero_g_rolls_back_level_unless_rolling() {
+        let mut player = player(FlightModel::ZeroG);
+        let tipped = Quat::from_rotation_z(0.5);
+
+        player.rolling = true;
+        let mut rotation = tipped;
+
+        for _ in 0..300 {
+            rotation = player.spin(rotation);
+        }
+
+        assert!((roll(rotation) - 0.5).abs() < 0.001);
+
+        player.rolling = false;
+
+        for _ in 0..600 {
+            rotation = player.spin(rotation);
+        }
+
+        assert!(roll(rotation).abs() < 0.01);
+        assert!(player.spin.length() < 0.01);
+    }
+}
@@ -0,0 +1,73 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use mazemazemaze::maze2d::Maze2D;
+
+use crate::{physics::PhysicsPosition, player2d::Player2D, state::AppState, SpinBouncing};
+
+#[derive(Component)]
+pub struct Portal;
+
+pub fn spawn_portal(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    translation: Vec3,
+) {
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(
+                shape::Torus {
+                    radius: 1.5,
+                    ring_radius: 0.2,
+                    ..default()
+                }
+                .into(),
+            ),
+            material: materials.add(StandardMaterial {
+                base_color: Color::hex("aa00ff").unwrap(),
+                emissive: Color::hex("aa00ff").unwrap(),
+                ..default()
+            }),
+            transform: Transform {
+                translation,
+                rotation: Quat::from_rotation_x(PI / 2.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(SpinBouncing)
+        .insert(Portal);
+}
+
+// Walking into a portal's cell moves the player to the other end. Arriving at the other end
+// doesn't count as walking into it, otherwise the player would bounce back and forth.
+fn use_portals(
+    maze_query: Query<&Maze2D>,
+    mut player_query: Query<&mut PhysicsPosition, With<Player2D>>,
+    mut last_cell: Local<Option<(usize, usize)>>,
+) {
+    for maze in maze_query.iter() {
+        for mut position in player_query.iter_mut() {
+            let cell = maze.cell_at(position.current.into());
+
+            if cell == *last_cell {
+                continue;
+            }
+
+            *last_cell = cell;
+
+            if let Some(destination) = cell.and_then(|cell| maze.portal_destination(cell)) {
+                position.teleport(Vec3::from(maze.cell_translation(destination)));
+                *last_cell = Some(destination);
+            }
+        }
+    }
+}
+
+pub struct PortalPlugin;
+impl Plugin for PortalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(AppState::Maze2D).with_system(use_portals));
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use fastrand::Rng;
+use mazemazemaze::maze2d::Maze2D;
+use mazemazemaze::maze3d::Maze3D;
+
+use crate::{chunks::MazeChunks, physics::PhysicsPosition, player2d::Player2D, state::AppState};
+
+// how many blocks to try before giving up on a shift, the player can be near all of them in a
+// small maze
+const SHIFT_ATTEMPTS: usize = 16;
+
+// Mazes that rearrange themselves while they're played. Every `interval` seconds a block of
+// `size` logical cells away from the player is carved again.
+#[derive(Resource)]
+pub struct ShiftingMazes {
+    pub enabled: bool,
+    pub interval: f32,
+    pub size: usize,
+}
+
+impl Default for ShiftingMazes {
+    fn default() -> Self {
+        ShiftingMazes {
+            enabled: false,
+            interval: 10.0,
+            size: 3,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ShiftTimer(Timer);
+
+// whether a logical cell is inside the block starting at `from` or right next to it
+fn near_block(cell: &[usize], from: &[usize], size: usize) -> bool {
+    cell.iter()
+        .zip(from)
+        .all(|(&cell, &from)| cell + 1 >= from && cell <= from + size)
+}
+
+// a random block inside a maze of `bounds` logical cells that keeps clear of `player`
+fn block_away_from(
+    player: &[usize],
+    bounds: &[usize],
+    size: usize,
+    rng: &Rng,
+) -> Option<Vec<usize>> {
+    (0..SHIFT_ATTEMPTS)
+        .map(|_| {
+            bounds
+                .iter()
+                .map(|&bound| rng.usize(..=bound.saturating_sub(size)))
+                .collect::<Vec<_>>()
+        })
+        .find(|from| !near_block(player, from, size))
+}
+
+fn toggle_shifting(keys: Res<Input<KeyCode>>, mut shifting: ResMut<ShiftingMazes>) {
+    if keys.just_pressed(KeyCode::T) {
+        shifting.enabled = !shifting.enabled;
+        info!("shifting mazes: {}", shifting.enabled);
+    }
+}
+
+fn reset_shift_timer(mut commands: Commands, shifting: Res<ShiftingMazes>) {
+    commands.insert_resource(ShiftTimer(Timer::from_seconds(
+        shifting.interval,
+        TimerMode::Repeating,
+    )));
+}
+
+fn shift_maze2d(
+    time: Res<Time>,
+    shifting: Res<ShiftingMazes>,
+    mut timer: ResMut<ShiftTimer>,
+    player_query: Query<&Transform, With<Player2D>>,
+    mut maze_query: Query<(&mut Maze2D, &mut MazeChunks)>,
+) {
+    if !shifting.enabled || !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let rng = Rng::new();
+
+    for (mut maze, mut chunks) in maze_query.iter_mut() {
+        for transform in player_query.iter() {
+            if let Some((x, y)) = maze.cell_at(transform.translation.into()) {
+                let bounds = [maze.width, maze.height];
+
+                if let Some(from) = block_away_from(&[x / 2, y / 2], &bounds, shifting.size, &rng) {
+                    let size = (shifting.size, shifting.size);
+
+                    // only the chunks around the block get rebuilt
+                    if let Some(region) = maze.recarve((from[0], from[1]), size, &rng) {
+                        chunks.dirty.push(region.to_vec());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn shift_maze3d(
+    time: Res<Time>,
+    shifting: Res<ShiftingMazes>,
+    mut timer: ResMut<ShiftTimer>,
+    // the player, whether they're flying or walking
+    player_query: Query<&PhysicsPosition>,
+    mut maze_query: Query<(&mut Maze3D, &mut MazeChunks)>,
+) {
+    if !shifting.enabled || !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let rng = Rng::new();
+
+    for (mut maze, mut chunks) in maze_query.iter_mut() {
+        for position in player_query.iter() {
+            if let Some((x, y, z)) = maze.cell_at(position.current.into()) {
+                let player = [x / 2, y / 2, z / 2];
+                let bounds = [maze.width, maze.height, maze.depth];
+
+                if let Some(from) = block_away_from(&player, &bounds, shifting.size, &rng) {
+                    let region = maze.recarve(
+                        (from[0], from[1], from[2]),
+                        (shifting.size, shifting.size, shifting.size),
+                        &rng,
+                    );
+
+                    if let Some(region) = region {
+                        chunks.dirty.push(region.to_vec());
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct ShiftingPlugin;
+impl Plugin for ShiftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShiftingMazes>()
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(toggle_shifting))
+            .add_system_set(SystemSet::on_enter(AppState::Maze2D).with_system(reset_shift_timer))
+            .add_system_set(SystemSet::on_enter(AppState::Maze3D).with_system(reset_shift_timer))
+            .add_system_set(SystemSet::on_update(AppState::Maze2D).with_system(shift_maze2d))
+            .add_system_set(SystemSet::on_update(AppState::Maze3D).with_system(shift_maze3d));
+    }
+}
@@ -0,0 +1,117 @@
+use fastrand::Rng;
+
+use crate::direction::{Bias, Direction2D, Direction3D};
+use crate::maze2d::Maze2D;
+use crate::maze3d::Maze3D;
+
+// Goes along each row carving runs to the right, and whenever a run ends one random cell of it
+// gets a passage up. Only the current run has to be remembered, and the top row is always one
+// long corridor.
+pub fn maze2d(width: usize, height: usize, seed: u64, bias: Bias) -> Maze2D {
+    let rng = Rng::with_seed(seed);
+
+    let mut maze = Maze2D {
+        cells: vec![vec![true; width * 2 + 1]; height * 2 + 1],
+        width,
+        height,
+        seed,
+        generator: "sidewinder".to_string(),
+        bias,
+        start: (1, 1),
+        goal: (width * 2 - 1, height * 2 - 1),
+        portals: vec![],
+    };
+
+    let mut directions = Vec::with_capacity(2);
+
+    for y in (1..height * 2).step_by(2) {
+        let mut run_start = 1;
+
+        for x in (1..width * 2).step_by(2) {
+            maze.cells[y][x] = false;
+
+            directions.clear();
+            if x < width * 2 - 1 {
+                directions.push(Direction2D::Right);
+            }
+            if y > 1 {
+                directions.push(Direction2D::Up);
+            }
+
+            if directions.is_empty() {
+                continue;
+            }
+
+            match Direction2D::biased_choice(&rng, &bias, &directions) {
+                Direction2D::Right => maze.cells[y][x + 1] = false,
+                _ => {
+                    let cell = run_start + rng.usize(..=(x - run_start) / 2) * 2;
+                    maze.cells[y - 1][cell] = false;
+                    run_start = x + 2;
+                }
+            }
+        }
+    }
+
+    maze
+}
+
+// see `maze2d`, runs go along x and end with a passage up or in
+pub fn maze3d(width: usize, height: usize, depth: usize, seed: u64, bias: Bias) -> Maze3D {
+    let rng = Rng::with_seed(seed);
+
+    let mut maze = Maze3D {
+        cells: vec![vec![vec![true; width * 2 + 1]; height * 2 + 1]; depth * 2 + 1],
+        width,
+        height,
+        depth,
+        seed,
+        generator: "sidewinder".to_string(),
+        bias,
+        start: (1, 1, 1),
+        goal: (width * 2 - 1, height * 2 - 1, depth * 2 - 1),
+    };
+
+    let mut directions = Vec::with_capacity(3);
+
+    for z in (1..depth * 2).step_by(2) {
+        for y in (1..height * 2).step_by(2) {
+            let mut run_start = 1;
+
+            for x in (1..width * 2).step_by(2) {
+                maze.cells[z][y][x] = false;
+
+                directions.clear();
+                if x < width * 2 - 1 {
+                    directions.push(Direction3D::Right);
+                }
+                if y > 1 {
+                    directions.push(Direction3D::Up);
+                }
+                if z > 1 {
+                    directions.push(Direction3D::In);
+                }
+
+                if directions.is_empty() {
+                    continue;
+                }
+
+                let direction = Direction3D::biased_choice(&rng, &bias, &directions);
+
+                if let Direction3D::Right = direction {
+                    maze.cells[z][y][x + 1] = false;
+                    continue;
+                }
+
+                let cell = run_start + rng.usize(..=(x - run_start) / 2) * 2;
+                match direction {
+                    Direction3D::In => maze.cells[z - 1][y][cell] = false,
+                    _ => maze.cells[z][y - 1][cell] = false,
+                }
+                run_start = x + 2;
+            }
+        }
+    }
+
+    maze
+}
@@ -4,4 +4,18 @@ pub enum AppState {
 
     Maze2D,
     Maze3D,
+
+    // watching a maze being generated
+    Generation,
+
+    // drawing a 2D maze by hand
+    Editor,
+
+    // drawing a 3D maze by hand, one layer at a time
+    Editor3D,
+
+    // rebinding the controls
+    Controls,
+
+    Won,
 }
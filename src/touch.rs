@@ -0,0 +1,336 @@
+use bevy::input::{touch::Touches, InputSystem};
+use bevy::prelude::*;
+
+use crate::controls::{Action, ControlsSystem};
+use crate::gravity::GravityMode;
+use crate::state::AppState;
+
+const JOYSTICK_SIZE: f32 = 160.0;
+const KNOB_SIZE: f32 = 64.0;
+const MARGIN: f32 = 40.0;
+
+// On-screen joysticks and buttons for phones and tablets. They stay hidden until the first touch,
+// after that they're shown in every maze and `cursor_grab` leaves the cursor alone.
+#[derive(Resource, Default)]
+pub struct TouchControls {
+    pub active: bool,
+
+    // how far each joystick is pushed, up to 1.0 with up being +y like a gamepad stick
+    pub movement: Vec2,
+    pub look: Vec2,
+
+    held: Vec<Action>,
+
+    // the touch holding each joystick, indexed by `Joystick`
+    fingers: [Option<u64>; 2],
+}
+
+impl TouchControls {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum Joystick {
+    Movement,
+    Look,
+}
+
+#[derive(Component)]
+struct Knob(Joystick);
+
+#[derive(Component)]
+struct TouchButton(Action);
+
+#[derive(Component)]
+struct TouchOverlay;
+
+// whether `position` is inside a ui node, both are in logical pixels from the top left
+fn contains(node: &Node, transform: &GlobalTransform, position: Vec2) -> bool {
+    let centre = transform.translation().truncate();
+
+    (position - centre).abs().cmple(node.size() / 2.0).all()
+}
+
+fn read_touches(
+    touches: Res<Touches>,
+    mut touch: ResMut<TouchControls>,
+    joysticks: Query<(&Node, &GlobalTransform, &Joystick)>,
+    buttons: Query<(&Node, &GlobalTransform, &TouchButton)>,
+) {
+    if touches.any_just_pressed() && !touch.active {
+        info!("touch controls on");
+        touch.active = true;
+    }
+
+    touch.movement = Vec2::ZERO;
+    touch.look = Vec2::ZERO;
+    touch.held.clear();
+
+    for finger in touch.fingers.iter_mut() {
+        if finger.is_some_and(|id| touches.get_pressed(id).is_none()) {
+            *finger = None;
+        }
+    }
+
+    for finger in touches.iter() {
+        let mut on_joystick = false;
+
+        for (node, transform, &joystick) in joysticks.iter() {
+            let slot = &mut touch.fingers[joystick as usize];
+
+            if slot.is_none()
+                && touches.just_pressed(finger.id())
+                && contains(node, transform, finger.position())
+            {
+                *slot = Some(finger.id());
+            }
+
+            if *slot != Some(finger.id()) {
+                continue;
+            }
+
+            on_joystick = true;
+
+            let offset =
+                (finger.position() - transform.translation().truncate()) / (JOYSTICK_SIZE / 2.0);
+            let value = Vec2::new(offset.x, -offset.y).clamp_length_max(1.0);
+
+            match joystick {
+                Joystick::Movement => touch.movement = value,
+                Joystick::Look => touch.look = value,
+            }
+        }
+
+        // sliding onto a button from somewhere else presses it too
+        if on_joystick {
+            continue;
+        }
+
+        for (node, transform, button) in buttons.iter() {
+            if contains(node, transform, finger.position()) && !touch.pressed(button.0) {
+                touch.held.push(button.0);
+            }
+        }
+    }
+}
+
+fn show_overlay(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    touch: Res<TouchControls>,
+    state: Res<State<AppState>>,
+    gravity: Res<GravityMode>,
+    overlay_query: Query<(), With<TouchOverlay>>,
+) {
+    if !touch.active || !overlay_query.is_empty() {
+        return;
+    }
+
+    let actions: &[Action] = match state.current() {
+        AppState::Maze2D => &[Action::Jump, Action::PlaceMarker],
+        _ if gravity.enabled => &[Action::Jump, Action::FlipGravity, Action::PlaceMarker],
+        _ => &[Action::Ascend, Action::Descend, Action::PlaceMarker],
+    };
+
+    let style = TextStyle {
+        font: server.load("DejaVuSansMono.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    let translucent = Color::rgba(1.0, 1.0, 1.0, 0.15);
+
+    let joystick = |parent: &mut ChildBuilder, joystick: Joystick, position: UiRect| {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    size: Size::new(Val::Px(JOYSTICK_SIZE), Val::Px(JOYSTICK_SIZE)),
+                    ..default()
+                },
+                background_color: translucent.into(),
+                ..default()
+            })
+            .insert(joystick)
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            size: Size::new(Val::Px(KNOB_SIZE), Val::Px(KNOB_SIZE)),
+                            ..default()
+                        },
+                        background_color: translucent.into(),
+                        ..default()
+                    })
+                    .insert(Knob(joystick));
+            });
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(TouchOverlay)
+        .with_children(|parent| {
+            joystick(
+                parent,
+                Joystick::Movement,
+                UiRect {
+                    left: Val::Px(MARGIN),
+                    bottom: Val::Px(MARGIN),
+                    ..default()
+                },
+            );
+            joystick(
+                parent,
+                Joystick::Look,
+                UiRect {
+                    right: Val::Px(MARGIN),
+                    bottom: Val::Px(MARGIN),
+                    ..default()
+                },
+            );
+
+            // stacked above the look joystick
+            for (i, &action) in actions.iter().enumerate() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                right: Val::Px(MARGIN),
+                                bottom: Val::Px(MARGIN * 2.0 + JOYSTICK_SIZE + i as f32 * 88.0),
+                                ..default()
+                            },
+                            size: Size::new(Val::Px(JOYSTICK_SIZE), Val::Px(72.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: translucent.into(),
+                        ..default()
+                    })
+                    .insert(TouchButton(action))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(action.name(), style.clone()));
+                    });
+            }
+        });
+}
+
+fn update_overlay(
+    touch: Res<TouchControls>,
+    mut knob_query: Query<(&mut Style, &Knob)>,
+    mut button_query: Query<(&mut BackgroundColor, &TouchButton)>,
+) {
+    for (mut style, knob) in knob_query.iter_mut() {
+        let value = match knob.0 {
+            Joystick::Movement => touch.movement,
+            Joystick::Look => touch.look,
+        };
+
+        let centre = (JOYSTICK_SIZE - KNOB_SIZE) / 2.0;
+
+        style.position = UiRect {
+            left: Val::Px(centre + value.x * JOYSTICK_SIZE / 2.0),
+            top: Val::Px(centre - value.y * JOYSTICK_SIZE / 2.0),
+            ..default()
+        };
+    }
+
+    for (mut color, button) in button_query.iter_mut() {
+        let alpha = if touch.pressed(button.0) { 0.4 } else { 0.15 };
+
+        *color = Color::rgba(1.0, 1.0, 1.0, alpha).into();
+    }
+}
+
+fn hide_overlay(mut commands: Commands, overlay_query: Query<Entity, With<TouchOverlay>>) {
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// The winit this bevy uses turns taps in browsers into mouse clicks and never sends touch events,
+// so they're read from the page instead and handed to bevy like any other touch.
+#[cfg(target_family = "wasm")]
+mod web {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::input::touch::{TouchInput, TouchPhase};
+    use bevy::prelude::*;
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    #[derive(Resource, Default)]
+    pub struct WebTouches(Arc<Mutex<Vec<TouchInput>>>);
+
+    pub fn listen(touches: Res<WebTouches>) {
+        let document = web_sys::window().unwrap().document().unwrap();
+
+        for (name, phase) in [
+            ("touchstart", TouchPhase::Started),
+            ("touchmove", TouchPhase::Moved),
+            ("touchend", TouchPhase::Ended),
+            ("touchcancel", TouchPhase::Cancelled),
+        ] {
+            let queue = touches.0.clone();
+
+            let listener = Closure::<dyn FnMut(web_sys::TouchEvent)>::new(
+                move |event: web_sys::TouchEvent| {
+                    let changed = event.changed_touches();
+                    let mut queue = queue.lock().unwrap();
+
+                    for touch in (0..changed.length()).filter_map(|i| changed.get(i)) {
+                        queue.push(TouchInput {
+                            phase,
+                            position: Vec2::new(touch.client_x() as f32, touch.client_y() as f32),
+                            force: None,
+                            id: touch.identifier() as u64,
+                        });
+                    }
+                },
+            );
+
+            document
+                .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                .unwrap();
+
+            // the listeners last as long as the page does
+            listener.forget();
+        }
+    }
+
+    pub fn send_touches(touches: Res<WebTouches>, mut events: EventWriter<TouchInput>) {
+        events.send_batch(touches.0.lock().unwrap().drain(..));
+    }
+}
+
+pub struct TouchPlugin;
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                read_touches.after(InputSystem).before(ControlsSystem),
+            )
+            .add_system(update_overlay);
+
+        for state in [AppState::Maze2D, AppState::Maze3D] {
+            app.add_system_set(SystemSet::on_update(state.clone()).with_system(show_overlay))
+                .add_system_set(SystemSet::on_exit(state).with_system(hide_overlay));
+        }
+
+        #[cfg(target_family = "wasm")]
+        app.init_resource::<web::WebTouches>()
+            .add_startup_system(web::listen)
+            .add_system_to_stage(CoreStage::First, web::send_touches);
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::{prelude::*, window::CursorGrabMode};
+use mazemazemaze::analysis::{Difficulty, MazeStats};
+
+use crate::{
+    controls::{gamepad_just_pressed, Action},
+    filter::FilterCamera,
+    state::AppState,
+    SpinBouncing,
+};
+
+// Difficulty the next maze is generated at. Solving a maze sets it one above the rating of the
+// maze that was solved, and giving up on one sets it one below.
+#[derive(Resource, Default)]
+pub struct TargetDifficulty(pub Difficulty);
+
+// statistics of the maze being played, inserted when it's generated
+#[derive(Resource, Default)]
+pub struct CurrentMazeStats(pub MazeStats);
+
+#[derive(Resource)]
+struct WinData {
+    root: Entity,
+    rating: Entity,
+    title: String,
+}
+
+fn setup_win(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut windows: ResMut<Windows>,
+    stats: Res<CurrentMazeStats>,
+    mut target: ResMut<TargetDifficulty>,
+) {
+    let window = windows.get_primary_mut().unwrap();
+
+    window.set_cursor_grab_mode(CursorGrabMode::None);
+    window.set_cursor_visibility(true);
+
+    let difficulty = stats.0.difficulty();
+    target.0 = difficulty.harder();
+
+    info!("solved a {difficulty} maze\n{}", stats.0);
+
+    let title = window.title().to_string();
+    window.set_title(format!(
+        "You won! {difficulty} maze, {} steps, {} dead ends, score {:.0}",
+        stats.0.solution_length,
+        stats.0.dead_ends,
+        stats.0.score()
+    ));
+
+    // one spinning goal per level of difficulty
+    let stars = difficulty as usize + 1;
+
+    let root = commands
+        .spawn(TransformBundle::default())
+        .insert(VisibilityBundle::default())
+        .with_children(|parent| {
+            parent
+                .spawn(Camera3dBundle {
+                    transform: Transform::from_xyz(0.0, 0.0, 12.0).looking_at(Vec3::ZERO, Vec3::Y),
+                    ..default()
+                })
+                .insert(FilterCamera);
+
+            parent.spawn(PointLightBundle {
+                point_light: PointLight {
+                    intensity: 3200.0,
+                    radius: 200.0,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 5.0, 8.0),
+                ..default()
+            });
+
+            for i in 0..stars {
+                parent
+                    .spawn(SceneBundle {
+                        scene: server.load("goal.glb#Scene0"),
+                        transform: Transform {
+                            translation: Vec3::new(
+                                (i as f32 - (stars - 1) as f32 / 2.0) * 3.0,
+                                0.0,
+                                0.0,
+                            ),
+                            scale: Vec3::new(0.3, 0.3, 0.3),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(SpinBouncing);
+            }
+        })
+        .id();
+
+    // the window title can't be seen in fullscreen or on the web
+    let rating = commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(60.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    format!(
+                        "{difficulty} maze, score {:.0}\n{} steps, {} dead ends",
+                        stats.0.score(),
+                        stats.0.solution_length,
+                        stats.0.dead_ends
+                    ),
+                    TextStyle {
+                        font: server.load("DejaVuSansMono.ttf"),
+                        font_size: 28.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_text_alignment(TextAlignment::CENTER),
+            );
+        })
+        .id();
+
+    commands.insert_resource(WinData {
+        root,
+        rating,
+        title,
+    });
+}
+
+fn win(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if buttons.just_released(MouseButton::Left)
+        || keys.just_pressed(KeyCode::Space)
+        || keys.just_pressed(KeyCode::Return)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::South)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::Start)
+    {
+        state.set(AppState::MainMenu).unwrap();
+    }
+}
+
+fn give_up(
+    actions: Res<Input<Action>>,
+    stats: Res<CurrentMazeStats>,
+    mut target: ResMut<TargetDifficulty>,
+    mut windows: ResMut<Windows>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !actions.just_pressed(Action::GiveUp) {
+        return;
+    }
+
+    target.0 = stats.0.difficulty().easier();
+    info!("gave up, the next maze will be {}", target.0);
+
+    let window = windows.get_primary_mut().unwrap();
+    window.set_cursor_grab_mode(CursorGrabMode::None);
+    window.set_cursor_visibility(true);
+
+    let _ = state.set(AppState::MainMenu);
+}
+
+fn cleanup_win(mut commands: Commands, mut windows: ResMut<Windows>, win_data: Res<WinData>) {
+    windows
+        .get_primary_mut()
+        .unwrap()
+        .set_title(win_data.title.clone());
+
+    commands.entity(win_data.root).despawn_recursive();
+    commands.entity(win_data.rating).despawn_recursive();
+}
+
+pub struct WinPlugin;
+impl Plugin for WinPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TargetDifficulty>()
+            .init_resource::<CurrentMazeStats>()
+            .add_system_set(SystemSet::on_enter(AppState::Won).with_system(setup_win))
+            .add_system_set(SystemSet::on_update(AppState::Won).with_system(win))
+            .add_system_set(SystemSet::on_update(AppState::Maze2D).with_system(give_up))
+            .add_system_set(SystemSet::on_update(AppState::Maze3D).with_system(give_up))
+            .add_system_set(SystemSet::on_exit(AppState::Won).with_system(cleanup_win));
+    }
+}